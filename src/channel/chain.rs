@@ -0,0 +1,50 @@
+use primitive_types::H256;
+use thiserror::Error;
+
+use crate::utxo::cache::{CacheError, UtxoCache};
+use crate::utxo::models::{Transaction, Utxo};
+use crate::utxo::validation::{self, ValidationError};
+
+#[derive(Error, Debug)]
+pub enum ChainError {
+    #[error("Transaction rejected: {0}")]
+    Rejected(#[from] ValidationError),
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+}
+
+/// The chain channels settle on
+///
+/// Unilateral closes publish transactions without the other participants'
+/// help and then wait for them to confirm, which is all a backend provides.
+pub trait ChainBackend {
+    /// Submit a transaction for inclusion in the chain
+    fn broadcast(&self, tx: &Transaction) -> Result<(), ChainError>;
+
+    /// An unspent output, confirmed or not; `None` if unknown or spent
+    fn get_utxo(&self, txid: H256, index: u32) -> Result<Option<Utxo>, ChainError>;
+
+    /// Height of the latest block, `None` before the first one
+    fn chain_tip(&self) -> Result<Option<u32>, ChainError>;
+}
+
+/// The local UTXO set as a chain
+///
+/// Broadcast transactions are validated, including their absolute and
+/// relative timelocks against the cache's chain tip, and applied as
+/// unconfirmed; they confirm when a block containing them is connected.
+impl ChainBackend for UtxoCache {
+    fn broadcast(&self, tx: &Transaction) -> Result<(), ChainError> {
+        validation::validate_transaction(tx, self)?;
+        self.apply_transaction(tx, None)?;
+        Ok(())
+    }
+
+    fn get_utxo(&self, txid: H256, index: u32) -> Result<Option<Utxo>, ChainError> {
+        Ok(UtxoCache::get_utxo(self, txid, index)?)
+    }
+
+    fn chain_tip(&self) -> Result<Option<u32>, ChainError> {
+        Ok(UtxoCache::chain_tip(self)?)
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::funding::{self, FundingOutput};
+use super::state::{ChannelState, ChannelStatus};
+use super::transitions::ChannelError;
+use crate::crypto::{KeyPair, PublicKey};
+use crate::utxo::cache::{CacheError, UtxoCache};
+use crate::utxo::models::{Input, Output, SighashType, Transaction};
+use crate::utxo::script::{self, ScriptError};
+use crate::utxo::selection::{INPUT_BYTES, OUTPUT_BYTES, TX_OVERHEAD_BYTES};
+use crate::utxo::timelock::SEQUENCE_FINAL;
+use crate::utxo::validation::{self, ValidationError};
+
+#[derive(Error, Debug)]
+pub enum CloseError {
+    #[error("Channel has no funding output")]
+    NotFunded,
+    #[error("Channel is {0:?}, only active or closing channels can be closed")]
+    NotCloseable(ChannelStatus),
+    #[error("Channel balances sum to {balances}, but the funding output holds {funding}")]
+    BalanceMismatch { balances: i64, funding: u64 },
+    #[error("A balance of {balance} can't pay its {fee} share of the settlement fee")]
+    FeeTooHigh { balance: u64, fee: u64 },
+    #[error("Settlement was built for another channel or an older state")]
+    StaleSettlement,
+    #[error("Key is not a channel participant")]
+    UnknownParticipant,
+    /// Carries the participant's key bytes
+    #[error("Missing settlement signature from {}", hex::encode(.0))]
+    MissingSignature([u8; 32]),
+    #[error("Invalid settlement signature from {}", hex::encode(.0))]
+    InvalidSignature([u8; 32]),
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+    #[error("Settlement is invalid: {0}")]
+    Validation(#[from] ValidationError),
+    #[error("Script error: {0}")]
+    Script(#[from] ScriptError),
+    #[error("Channel error: {0}")]
+    Channel(#[from] ChannelError),
+}
+
+/// A participant's signature on the settlement transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloseSignature {
+    pub public_key: PublicKey,
+    /// Script signature element: signature followed by the sighash type
+    #[serde(with = "hex")]
+    pub signature: Vec<u8>,
+}
+
+/// Cooperative close of a funded channel
+///
+/// Every participant builds the same settlement transaction from the final
+/// channel state with [`propose`](Self::propose), signs it with
+/// [`sign`](Self::sign) and sends the signature to the others.
+/// [`close_channel`] then spends the funding output in the `UtxoCache` and
+/// settles the channel.
+#[derive(Debug, Clone)]
+pub struct CooperativeClose {
+    channel_id: [u8; 32],
+    sequence_number: u64,
+    /// Sorted, in the order of the funding multisig
+    participants: Vec<PublicKey>,
+    funding: FundingOutput,
+    settlement_tx: Transaction,
+    signatures: HashMap<PublicKey, Vec<u8>>,
+}
+
+impl CooperativeClose {
+    /// Build the settlement transaction for the channel's current balances
+    ///
+    /// Pays every participant with a non-zero balance to their public key
+    /// hash. The fee (`fee_rate` per estimated byte) is shared evenly between
+    /// the outputs, with any remainder paid by the first one.
+    ///
+    /// # Errors
+    /// * `NotCloseable` unless the channel is `Active` or `Closing`
+    /// * `BalanceMismatch` if the balances don't add up to the funding value
+    /// * `FeeTooHigh` if a balance can't cover its share of the fee
+    pub fn propose(channel: &ChannelState, fee_rate: u64) -> Result<Self, CloseError> {
+        if !matches!(channel.status, ChannelStatus::Active | ChannelStatus::Closing) {
+            return Err(CloseError::NotCloseable(channel.status));
+        }
+        let funding = channel.funding.clone().ok_or(CloseError::NotFunded)?;
+        let balances = channel.balances.values().try_fold(0i64, |sum, balance| sum.checked_add(*balance));
+        match balances {
+            Some(balances) if u64::try_from(balances) == Ok(funding.value) => {}
+            _ => return Err(CloseError::BalanceMismatch { balances: balances.unwrap_or(i64::MAX), funding: funding.value }),
+        }
+
+        let mut participants = channel.participants.clone();
+        participants.sort_by_key(|p| p.as_bytes());
+        let payees: Vec<(&PublicKey, u64)> = participants.iter()
+            .filter_map(|p| {
+                let balance = channel.balances.get(p).copied().unwrap_or(0);
+                (balance > 0).then_some((p, balance as u64))
+            })
+            .collect();
+
+        let count = payees.len() as u64;
+        let fee = fee_rate.saturating_mul(TX_OVERHEAD_BYTES + INPUT_BYTES + OUTPUT_BYTES * count);
+        let mut outputs = Vec::with_capacity(payees.len());
+        for (i, (p, balance)) in payees.into_iter().enumerate() {
+            let share = if i == 0 { fee / count + fee % count } else { fee / count };
+            if balance <= share {
+                return Err(CloseError::FeeTooHigh { balance, fee: share });
+            }
+            outputs.push(Output {
+                value: balance - share,
+                public_key_hash: p.public_key_hash().to_vec(),
+                lock_script: vec![],
+            });
+        }
+        let input = Input {
+            previous_output: funding.txid,
+            index: funding.index,
+            signature: vec![],
+            sequence: SEQUENCE_FINAL,
+        };
+        let settlement_tx = funding::new_transaction(vec![input], outputs);
+
+        Ok(Self {
+            channel_id: channel.channel_id,
+            sequence_number: channel.sequence_number,
+            participants,
+            funding,
+            settlement_tx,
+            signatures: HashMap::new(),
+        })
+    }
+
+    /// The unsigned settlement transaction
+    pub fn settlement_tx(&self) -> &Transaction {
+        &self.settlement_tx
+    }
+
+    /// Txid of the settlement, identical before and after signing
+    pub fn txid(&self) -> H256 {
+        self.settlement_tx.hash
+    }
+
+    /// Sign the settlement transaction as one of the participants
+    pub fn sign(&self, keypair: &KeyPair) -> Result<CloseSignature, CloseError> {
+        if !self.participants.contains(&keypair.public_key()) {
+            return Err(CloseError::UnknownParticipant);
+        }
+        let signature = script::sign(&self.settlement_tx, 0, keypair, SighashType::All)?;
+        Ok(CloseSignature { public_key: keypair.public_key(), signature })
+    }
+
+    /// Accept a participant's settlement signature after verifying it
+    pub fn add_signature(&mut self, close_signature: CloseSignature) -> Result<(), CloseError> {
+        let CloseSignature { public_key, signature } = close_signature;
+        if !self.participants.contains(&public_key) {
+            return Err(CloseError::UnknownParticipant);
+        }
+        if !funding::verify_input_signature(&self.settlement_tx, &public_key, &signature) {
+            return Err(CloseError::InvalidSignature(public_key.as_bytes()));
+        }
+        self.signatures.insert(public_key, signature);
+        Ok(())
+    }
+
+    /// The settlement transaction with its unlock script
+    ///
+    /// # Errors
+    /// * `MissingSignature` naming the first participant that hasn't signed
+    pub fn finalize(&self) -> Result<Transaction, CloseError> {
+        if let Some(missing) = self.participants.iter().find(|p| !self.signatures.contains_key(p)) {
+            return Err(CloseError::MissingSignature(missing.as_bytes()));
+        }
+        let mut tx = self.settlement_tx.clone();
+        tx.inputs[0].signature = funding::multisig_unlock(&self.participants, self.funding.threshold, &self.signatures);
+        script::verify_script(&tx.inputs[0].signature, &self.funding.lock_script, &tx, 0)?;
+        Ok(tx)
+    }
+}
+
+/// Settle `channel` with a fully signed cooperative close
+///
+/// The settlement is validated against `cache` and applied to it as an
+/// unconfirmed transaction, spending the funding output. The channel then
+/// moves through `Closing` to `Settled`. Returns the applied transaction.
+///
+/// # Errors
+/// * `StaleSettlement` if `close` was built for another channel or state
+/// * `Validation` if the funding output is unknown or already spent
+pub fn close_channel(
+    channel: &mut ChannelState,
+    close: &CooperativeClose,
+    cache: &UtxoCache,
+) -> Result<Transaction, CloseError> {
+    if close.channel_id != channel.channel_id
+        || close.sequence_number != channel.sequence_number
+        || channel.funding.as_ref() != Some(&close.funding)
+    {
+        return Err(CloseError::StaleSettlement);
+    }
+    if !matches!(channel.status, ChannelStatus::Active | ChannelStatus::Closing) {
+        return Err(CloseError::NotCloseable(channel.status));
+    }
+    let tx = close.finalize()?;
+    validation::validate_transaction(&tx, cache)?;
+    cache.apply_transaction(&tx, None)?;
+
+    if channel.status == ChannelStatus::Active {
+        channel.begin_close()?;
+    }
+    channel.settle()?;
+    Ok(tx)
+}
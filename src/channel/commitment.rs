@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::chain::{ChainBackend, ChainError};
+use super::funding::{self, FundingOutput};
+use super::state::{ChannelState, ChannelStatus};
+use super::transitions::ChannelError;
+use crate::crypto::{self, KeyPair, PublicKey};
+use crate::utxo::models::{Input, Output, SighashType, Transaction};
+use crate::utxo::script::{self, opcodes::*, Script, ScriptError};
+use crate::utxo::selection::{self, INPUT_BYTES, OUTPUT_BYTES, TX_OVERHEAD_BYTES};
+use crate::utxo::timelock::{RelativeLock, SEQUENCE_FINAL};
+
+/// Blocks a force-closing participant waits for their own funds, unless configured otherwise
+pub const DEFAULT_TO_SELF_DELAY: u16 = 144;
+
+#[derive(Error, Debug)]
+pub enum CommitmentError {
+    #[error("Channel has no funding output")]
+    NotFunded,
+    #[error("Channel is {0:?}, only active or closing channels can be force closed")]
+    NotCloseable(ChannelStatus),
+    #[error("Key is not a channel participant")]
+    UnknownParticipant,
+    #[error("Channel balances sum to {balances}, but the funding output holds {funding}")]
+    BalanceMismatch { balances: i64, funding: u64 },
+    #[error("A balance of {balance} can't pay the fee {fee}")]
+    FeeTooHigh { balance: u64, fee: u64 },
+    #[error("Commitment was built for another channel or an older state")]
+    StaleCommitment,
+    /// Carries the participant's key bytes
+    #[error("No revocation hash for {}", hex::encode(.0))]
+    MissingRevocationHash([u8; 32]),
+    #[error("Missing commitment signature from {}", hex::encode(.0))]
+    MissingSignature([u8; 32]),
+    #[error("Invalid commitment signature from {}", hex::encode(.0))]
+    InvalidSignature([u8; 32]),
+    #[error("Delayed output is unknown or already swept")]
+    OutputNotFound,
+    #[error("Delayed output can be swept in {remaining} blocks")]
+    NotMatured { remaining: u32 },
+    #[error("Chain error: {0}")]
+    Chain(#[from] ChainError),
+    #[error("Script error: {0}")]
+    Script(#[from] ScriptError),
+    #[error("Channel error: {0}")]
+    Channel(#[from] ChannelError),
+}
+
+/// Terms all participants agree on for their commitment transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentParams {
+    /// Blocks the holder of a commitment waits before sweeping their output
+    pub to_self_delay: u16,
+    /// Fee per estimated byte, paid by the holder
+    pub fee_rate: u64,
+}
+
+impl CommitmentParams {
+    pub fn new(fee_rate: u64) -> Self {
+        Self { to_self_delay: DEFAULT_TO_SELF_DELAY, fee_rate }
+    }
+
+    pub fn with_to_self_delay(mut self, to_self_delay: u16) -> Self {
+        self.to_self_delay = to_self_delay;
+        self
+    }
+}
+
+/// A participant's signature on someone's commitment transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitmentSignature {
+    pub public_key: PublicKey,
+    /// Script signature element: signature followed by the sighash type
+    #[serde(with = "hex")]
+    pub signature: Vec<u8>,
+}
+
+/// An output of the holder's own commitment, spendable after `to_self_delay` blocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DelayedOutput {
+    pub txid: H256,
+    pub index: u32,
+    pub value: u64,
+    pub owner: PublicKey,
+    pub to_self_delay: u16,
+    #[serde(with = "hex")]
+    pub lock_script: Vec<u8>,
+}
+
+/// One participant's version of a channel state, publishable on its own
+///
+/// Spends the funding output and pays every participant's balance. The
+/// holder's own output is locked for `to_self_delay` blocks, while everyone
+/// else can spend theirs immediately; the holder pays the fee. A commitment
+/// is built for each participant at every sequence number, and every
+/// participant signs all of them, so each can later close the channel
+/// without the others.
+///
+/// Until the delay expires, the other participants can take the holder's
+/// output with the secret behind `revocation_hash`, which the holder reveals
+/// once the state is replaced (see [`revocation`](super::revocation)). The
+/// transaction's `lock_time` carries the sequence number, which is never
+/// enforced since the funding input is final, so that watchers can tell
+/// which secret a published commitment was revoked with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitmentTransaction {
+    pub channel_id: [u8; 32],
+    pub sequence_number: u64,
+    pub holder: PublicKey,
+    /// SHA-256 of the holder's per-commitment secret for this state
+    pub revocation_hash: [u8; 32],
+    /// Sorted, in the order of the funding multisig
+    participants: Vec<PublicKey>,
+    funding: FundingOutput,
+    to_self_delay: u16,
+    tx: Transaction,
+    signatures: HashMap<PublicKey, Vec<u8>>,
+}
+
+impl CommitmentTransaction {
+    /// Build `holder`'s commitment for the channel's current state
+    ///
+    /// # Errors
+    /// * `BalanceMismatch` if the balances don't add up to the funding value
+    /// * `FeeTooHigh` if the holder's balance can't cover the fee
+    pub fn build(
+        channel: &ChannelState,
+        holder: &PublicKey,
+        revocation_hash: [u8; 32],
+        params: &CommitmentParams,
+    ) -> Result<Self, CommitmentError> {
+        let funding = channel.funding.clone().ok_or(CommitmentError::NotFunded)?;
+        if !channel.participants.contains(holder) {
+            return Err(CommitmentError::UnknownParticipant);
+        }
+        let balances = channel.balances.values().try_fold(0i64, |sum, balance| sum.checked_add(*balance));
+        match balances {
+            Some(balances) if u64::try_from(balances) == Ok(funding.value) => {}
+            _ => return Err(CommitmentError::BalanceMismatch { balances: balances.unwrap_or(i64::MAX), funding: funding.value }),
+        }
+
+        let mut participants = channel.participants.clone();
+        participants.sort_by_key(|p| p.as_bytes());
+        let payees: Vec<(&PublicKey, u64)> = participants.iter()
+            .filter_map(|p| {
+                let balance = channel.balances.get(p).copied().unwrap_or(0);
+                (balance > 0).then_some((p, balance as u64))
+            })
+            .collect();
+        let fee = params.fee_rate.saturating_mul(TX_OVERHEAD_BYTES + INPUT_BYTES + OUTPUT_BYTES * payees.len() as u64);
+        let holder_balance = channel.balances.get(holder).copied().unwrap_or(0).max(0) as u64;
+        if holder_balance <= fee {
+            return Err(CommitmentError::FeeTooHigh { balance: holder_balance, fee });
+        }
+
+        let counterparties: Vec<PublicKey> = participants.iter().filter(|p| *p != holder).cloned().collect();
+        let outputs = payees.into_iter()
+            .map(|(p, balance)| {
+                if p == holder {
+                    let lock_script = to_self_script(holder, &counterparties, &revocation_hash, params.to_self_delay)
+                        .into_bytes();
+                    Output {
+                        value: balance - fee,
+                        public_key_hash: crypto::hash160(&lock_script).to_vec(),
+                        lock_script,
+                    }
+                } else {
+                    Output {
+                        value: balance,
+                        public_key_hash: p.public_key_hash().to_vec(),
+                        lock_script: vec![],
+                    }
+                }
+            })
+            .collect();
+        let input = Input {
+            previous_output: funding.txid,
+            index: funding.index,
+            signature: vec![],
+            sequence: SEQUENCE_FINAL,
+        };
+        let mut tx = funding::new_transaction(vec![input], outputs);
+        tx.lock_time = channel.sequence_number;
+        tx.hash = tx.calculate_hash();
+
+        Ok(Self {
+            channel_id: channel.channel_id,
+            sequence_number: channel.sequence_number,
+            holder: holder.clone(),
+            revocation_hash,
+            participants,
+            funding,
+            to_self_delay: params.to_self_delay,
+            tx,
+            signatures: HashMap::new(),
+        })
+    }
+
+    /// The unsigned commitment transaction
+    pub fn tx(&self) -> &Transaction {
+        &self.tx
+    }
+
+    pub fn txid(&self) -> H256 {
+        self.tx.hash
+    }
+
+    /// The holder's time-locked output
+    pub fn delayed_output(&self) -> DelayedOutput {
+        let (index, output) = self.tx.outputs.iter()
+            .enumerate()
+            .find(|(_, output)| !output.lock_script.is_empty())
+            .expect("commitments always pay the holder");
+        DelayedOutput {
+            txid: self.tx.hash,
+            index: index as u32,
+            value: output.value,
+            owner: self.holder.clone(),
+            to_self_delay: self.to_self_delay,
+            lock_script: output.lock_script.clone(),
+        }
+    }
+
+    /// Sign the commitment as one of the participants
+    pub fn sign(&self, keypair: &KeyPair) -> Result<CommitmentSignature, CommitmentError> {
+        if !self.participants.contains(&keypair.public_key()) {
+            return Err(CommitmentError::UnknownParticipant);
+        }
+        let signature = script::sign(&self.tx, 0, keypair, SighashType::All)?;
+        Ok(CommitmentSignature { public_key: keypair.public_key(), signature })
+    }
+
+    /// Accept a participant's signature after verifying it
+    pub fn add_signature(&mut self, commitment_signature: CommitmentSignature) -> Result<(), CommitmentError> {
+        let CommitmentSignature { public_key, signature } = commitment_signature;
+        if !self.participants.contains(&public_key) {
+            return Err(CommitmentError::UnknownParticipant);
+        }
+        if !funding::verify_input_signature(&self.tx, &public_key, &signature) {
+            return Err(CommitmentError::InvalidSignature(public_key.as_bytes()));
+        }
+        self.signatures.insert(public_key, signature);
+        Ok(())
+    }
+
+    /// The commitment with its unlock script, ready to broadcast
+    ///
+    /// # Errors
+    /// * `MissingSignature` naming the first participant that hasn't signed
+    pub fn finalize(&self) -> Result<Transaction, CommitmentError> {
+        if let Some(missing) = self.participants.iter().find(|p| !self.signatures.contains_key(p)) {
+            return Err(CommitmentError::MissingSignature(missing.as_bytes()));
+        }
+        let mut tx = self.tx.clone();
+        tx.inputs[0].signature = funding::multisig_unlock(&self.participants, self.funding.threshold, &self.signatures);
+        script::verify_script(&tx.inputs[0].signature, &self.funding.lock_script, &tx, 0)?;
+        Ok(tx)
+    }
+}
+
+/// Build every participant's commitment for the channel's current state
+///
+/// `revocation_hashes` holds each participant's revocation hash for this
+/// state. Participants whose balance can't pay the fee have nothing to claim
+/// and get no commitment.
+pub fn build_commitments(
+    channel: &ChannelState,
+    revocation_hashes: &HashMap<PublicKey, [u8; 32]>,
+    params: &CommitmentParams,
+) -> Result<Vec<CommitmentTransaction>, CommitmentError> {
+    let mut commitments = Vec::with_capacity(channel.participants.len());
+    for participant in &channel.participants {
+        let revocation_hash = *revocation_hashes.get(participant)
+            .ok_or(CommitmentError::MissingRevocationHash(participant.as_bytes()))?;
+        match CommitmentTransaction::build(channel, participant, revocation_hash, params) {
+            Ok(commitment) => commitments.push(commitment),
+            Err(CommitmentError::FeeTooHigh { .. }) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(commitments)
+}
+
+/// Lock script of the holder's output
+///
+/// ```text
+/// IF
+///     SHA256 <revocation hash> EQUALVERIFY 1 <counterparties...> <n> CHECKMULTISIG
+/// ELSE
+///     <delay> CHECKSEQUENCEVERIFY DROP <holder> CHECKSIG
+/// ENDIF
+/// ```
+///
+/// The holder unlocks it with `<signature> 0` after the delay; any other
+/// participant with `<signature> <revocation secret> 1` at any time.
+pub fn to_self_script(
+    holder: &PublicKey,
+    counterparties: &[PublicKey],
+    revocation_hash: &[u8; 32],
+    to_self_delay: u16,
+) -> Script {
+    let revocation = counterparties.iter().fold(
+        Script::new()
+            .push_opcode(OP_IF)
+            .push_opcode(OP_SHA256)
+            .push_data(revocation_hash)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_int(1),
+        |script, key| script.push_data(&key.as_bytes()),
+    );
+    revocation
+        .push_int(counterparties.len() as i64)
+        .push_opcode(OP_CHECKMULTISIG)
+        .push_opcode(OP_ELSE)
+        .push_int(RelativeLock::Blocks(to_self_delay).to_sequence() as i64)
+        .push_opcode(OP_CHECKSEQUENCEVERIFY)
+        .push_opcode(OP_DROP)
+        .push_data(&holder.as_bytes())
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_ENDIF)
+}
+
+/// A published commitment whose delayed output hasn't been swept yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForceClose {
+    pub commitment_txid: H256,
+    pub delayed_output: DelayedOutput,
+}
+
+/// Close `channel` unilaterally by broadcasting the holder's latest commitment
+///
+/// The channel becomes `Disputed` until [`ForceClose::sweep`] claims the
+/// holder's delayed output.
+///
+/// # Errors
+/// * `StaleCommitment` if `commitment` isn't for the channel's latest state
+/// * `MissingSignature` if the commitment isn't signed by everyone
+pub fn force_close(
+    channel: &mut ChannelState,
+    commitment: &CommitmentTransaction,
+    backend: &impl ChainBackend,
+) -> Result<ForceClose, CommitmentError> {
+    if commitment.channel_id != channel.channel_id
+        || commitment.sequence_number != channel.sequence_number
+        || channel.funding.as_ref() != Some(&commitment.funding)
+    {
+        return Err(CommitmentError::StaleCommitment);
+    }
+    if !matches!(channel.status, ChannelStatus::Active | ChannelStatus::Closing) {
+        return Err(CommitmentError::NotCloseable(channel.status));
+    }
+    let tx = commitment.finalize()?;
+    backend.broadcast(&tx)?;
+    channel.dispute()?;
+
+    Ok(ForceClose {
+        commitment_txid: tx.hash,
+        delayed_output: commitment.delayed_output(),
+    })
+}
+
+impl ForceClose {
+    /// Blocks until the delayed output can be swept, 0 once it can
+    ///
+    /// An unconfirmed commitment still has the full delay ahead of it.
+    pub fn remaining_blocks(&self, backend: &impl ChainBackend) -> Result<u32, CommitmentError> {
+        let output = &self.delayed_output;
+        let utxo = backend.get_utxo(output.txid, output.index)?
+            .ok_or(CommitmentError::OutputNotFound)?;
+        let confirmations = selection::confirmations(&utxo, backend.chain_tip()?);
+        Ok((output.to_self_delay as u32).saturating_sub(confirmations))
+    }
+
+    /// Sweep the matured delayed output to the owner's public key hash
+    ///
+    /// The channel is `Resolved` once the sweep is broadcast.
+    ///
+    /// # Errors
+    /// * `NotMatured` if the relative lock hasn't expired yet
+    pub fn sweep(
+        &self,
+        channel: &mut ChannelState,
+        owner: &KeyPair,
+        fee_rate: u64,
+        backend: &impl ChainBackend,
+    ) -> Result<Transaction, CommitmentError> {
+        let output = &self.delayed_output;
+        if owner.public_key() != output.owner {
+            return Err(CommitmentError::UnknownParticipant);
+        }
+        let remaining = self.remaining_blocks(backend)?;
+        if remaining > 0 {
+            return Err(CommitmentError::NotMatured { remaining });
+        }
+        let fee = fee_rate.saturating_mul(TX_OVERHEAD_BYTES + INPUT_BYTES + OUTPUT_BYTES);
+        if output.value <= fee {
+            return Err(CommitmentError::FeeTooHigh { balance: output.value, fee });
+        }
+
+        let input = Input {
+            previous_output: output.txid,
+            index: output.index,
+            signature: vec![],
+            sequence: RelativeLock::Blocks(output.to_self_delay).to_sequence(),
+        };
+        let payout = Output {
+            value: output.value - fee,
+            public_key_hash: owner.public_key().public_key_hash().to_vec(),
+            lock_script: vec![],
+        };
+        let mut tx = funding::new_transaction(vec![input], vec![payout]);
+        let signature = script::sign(&tx, 0, owner, SighashType::All)?;
+        tx.inputs[0].signature = Script::new().push_data(&signature).push_int(0).into_bytes();
+
+        backend.broadcast(&tx)?;
+        channel.resolve()?;
+        Ok(tx)
+    }
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::commitment::{CommitmentError, CommitmentParams, CommitmentSignature, CommitmentTransaction, DEFAULT_TO_SELF_DELAY};
+use super::state::ChannelState;
+use super::transitions::ChannelError;
+use crate::crypto::{self, KeyPair, PublicKey, Signature};
+use crate::utxo::cache::{CacheError, UtxoCache};
+use crate::utxo::models::{Input, Output, SighashType, Transaction};
+use crate::utxo::script::{Script, ScriptError};
+use crate::utxo::selection::{self, SelectionError, SelectionParams, SelectionStrategy};
+use crate::utxo::timelock::SEQUENCE_FINAL;
+use crate::utxo::validation::{self, ValidationError};
+
+/// Index of the channel output in the funding transaction
+pub const FUNDING_OUTPUT_INDEX: u32 = 0;
+/// Version of funding and commitment transactions, so relative locks can apply
+const CHANNEL_TX_VERSION: u32 = 2;
+
+#[derive(Error, Debug)]
+pub enum FundingError {
+    #[error("A channel needs at least two distinct participants")]
+    TooFewParticipants,
+    #[error("Funding threshold {threshold} is invalid for {participants} participants")]
+    InvalidThreshold { threshold: usize, participants: usize },
+    #[error("Funder is not a channel participant")]
+    FunderNotParticipant,
+    #[error("Funding must be signed with the funder's key")]
+    NotFunder,
+    #[error("Initial balance for a key that is not a channel participant")]
+    UnknownParticipant,
+    #[error("Initial balances must not be negative")]
+    NegativeBalance,
+    #[error("Initial balances sum to {balances}, but the funding output holds {funding}")]
+    BalanceMismatch { balances: i64, funding: u64 },
+    #[error("Funding transaction or revocation hashes don't match the proposed channel")]
+    InvalidProposal,
+    /// Carries the participant's key bytes
+    #[error("Missing first commitment signature from {}", hex::encode(.0))]
+    MissingCommitmentSignature([u8; 32]),
+    #[error("Invalid first commitment signature from {}", hex::encode(.0))]
+    InvalidCommitmentSignature([u8; 32]),
+    #[error("Channel has no funding output")]
+    NotFunded,
+    #[error("Funding output is unknown or doesn't have enough confirmations")]
+    FundingNotConfirmed,
+    #[error("Coin selection failed: {0}")]
+    Selection(#[from] SelectionError),
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+    #[error("Signing failed: {0}")]
+    Validation(#[from] ValidationError),
+    #[error("Script error: {0}")]
+    Script(#[from] ScriptError),
+    #[error("Channel error: {0}")]
+    Channel(#[from] ChannelError),
+    #[error("Commitment error: {0}")]
+    Commitment(#[from] CommitmentError),
+}
+
+/// The on-chain output holding a channel's funds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FundingOutput {
+    /// Txid of the funding transaction
+    pub txid: H256,
+    /// Index of the channel output in the funding transaction
+    pub index: u32,
+    pub value: u64,
+    /// Signatures needed to spend the output
+    pub threshold: usize,
+    /// Multisig script over the participants' keys
+    #[serde(with = "hex")]
+    pub lock_script: Vec<u8>,
+}
+
+/// How the funding transaction is built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FundingParams {
+    /// Fee per estimated byte, for both the funding and commitment transactions
+    pub fee_rate: u64,
+    /// Blocks the holder of a commitment waits before sweeping their output
+    pub to_self_delay: u16,
+    /// Signatures needed to spend the funding output, `None` for every
+    /// participant
+    pub threshold: Option<usize>,
+    /// Minimum confirmations of the funder's inputs
+    pub min_confirmations: u32,
+    pub strategy: SelectionStrategy,
+}
+
+impl FundingParams {
+    /// n-of-n funding at `fee_rate`, spending confirmed outputs largest first
+    pub fn new(fee_rate: u64) -> Self {
+        Self {
+            fee_rate,
+            to_self_delay: DEFAULT_TO_SELF_DELAY,
+            threshold: None,
+            min_confirmations: 1,
+            strategy: SelectionStrategy::LargestFirst,
+        }
+    }
+
+    /// Let any `threshold` participants spend the funding output
+    ///
+    /// Those participants can then take every balance in the channel without
+    /// the others, so only lower the threshold if they are trusted to.
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_to_self_delay(mut self, to_self_delay: u16) -> Self {
+        self.to_self_delay = to_self_delay;
+        self
+    }
+
+    pub fn with_min_confirmations(mut self, min_confirmations: u32) -> Self {
+        self.min_confirmations = min_confirmations;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: SelectionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+}
+
+/// Proposal the funder sends to every other participant
+///
+/// The funding transaction is unsigned. Txids don't cover signatures, so
+/// its txid is already final and the first commitments can spend it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FundingProposal {
+    /// Participants, sorted by key, in the order of the funding multisig
+    pub participants: Vec<PublicKey>,
+    pub initial_balances: HashMap<PublicKey, i64>,
+    pub funder: PublicKey,
+    pub threshold: usize,
+    pub funding_tx: Transaction,
+    /// Every participant's revocation hashes of states 0 and 1
+    pub revocation_hashes: HashMap<PublicKey, [[u8; 32]; 2]>,
+    pub commitment_params: CommitmentParams,
+}
+
+/// A participant's signatures on every first commitment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstCommitmentSignatures {
+    pub public_key: PublicKey,
+    /// Script signature elements, keyed by commitment holder
+    pub signatures: HashMap<PublicKey, Vec<u8>>,
+}
+
+/// A fully signed funding transaction and the commitments it is backed by
+#[derive(Debug, Clone)]
+pub struct OpenedChannel {
+    /// Channel in `Opening` status, until the funding confirms
+    pub channel: ChannelState,
+    /// Signed and ready to broadcast
+    pub funding_tx: Transaction,
+    /// Every participant's commitment of state 0, signed by everyone
+    pub commitments: Vec<CommitmentTransaction>,
+}
+
+/// Funder's side of the opening protocol
+///
+/// 1. [`propose`](Self::propose) selects the funder's coins and builds the
+///    unsigned funding transaction and everyone's commitment of state 0.
+/// 2. Every participant checks the [`FundingProposal`] and returns
+///    [`FirstCommitmentSignatures`] ([`FundingProposal::sign_commitments`]).
+/// 3. Once every commitment is fully signed, [`release_funding`](Self::release_funding)
+///    signs the funding transaction. Until then nobody can lock the funder's
+///    coins into the channel without a way to get them back.
+/// 4. [`confirm_funding`] activates the channel once the funding output
+///    is confirmed.
+///
+/// The first commitments pay the initial balances like any later
+/// commitment, with the holder's output behind `to_self_delay` and the
+/// revocation hash of state 0. The first update revokes them, so they can't
+/// be used to take back the initial balances afterwards.
+#[derive(Debug, Clone)]
+pub struct ChannelOpening {
+    proposal: FundingProposal,
+    commitments: Vec<CommitmentTransaction>,
+}
+
+impl ChannelOpening {
+    /// Build the funding transaction and first commitments for a new channel
+    ///
+    /// `revocation_hashes` are every participant's revocation hashes of
+    /// states 0 and 1, collected from them beforehand.
+    ///
+    /// # Errors
+    /// * `BalanceMismatch` if `initial_balances` don't sum to `funding_value`
+    /// * `InvalidProposal` if a participant's revocation hashes are missing
+    /// * `Selection` if the funder can't afford `funding_value` plus fees
+    pub fn propose(
+        cache: &UtxoCache,
+        funder: &PublicKey,
+        participants: Vec<PublicKey>,
+        initial_balances: HashMap<PublicKey, i64>,
+        funding_value: u64,
+        revocation_hashes: HashMap<PublicKey, [[u8; 32]; 2]>,
+        params: &FundingParams,
+    ) -> Result<Self, FundingError> {
+        let mut participants = participants;
+        participants.sort_by_key(|p| p.as_bytes());
+        participants.dedup();
+        let threshold = params.threshold.unwrap_or(participants.len());
+        check_terms(&participants, &initial_balances, funder, funding_value, threshold)?;
+
+        let selection = selection::select_coins(
+            cache,
+            &funder.public_key_hash(),
+            &SelectionParams::new(funding_value, params.fee_rate).with_min_confirmations(params.min_confirmations),
+            params.strategy,
+        )?;
+
+        let lock_script = Script::multisig(threshold, &participants).into_bytes();
+        let mut outputs = vec![Output {
+            value: funding_value,
+            public_key_hash: crypto::hash160(&lock_script).to_vec(),
+            lock_script,
+        }];
+        if selection.change > 0 {
+            outputs.push(Output {
+                value: selection.change,
+                public_key_hash: funder.public_key_hash().to_vec(),
+                lock_script: vec![],
+            });
+        }
+        let inputs = selection.inputs.iter()
+            .map(|utxo| Input {
+                previous_output: utxo.tx_hash,
+                index: utxo.output_index,
+                signature: vec![],
+                sequence: SEQUENCE_FINAL,
+            })
+            .collect();
+        let funding_tx = new_transaction(inputs, outputs);
+
+        let proposal = FundingProposal {
+            participants,
+            initial_balances,
+            funder: funder.clone(),
+            threshold,
+            funding_tx,
+            revocation_hashes,
+            commitment_params: CommitmentParams::new(params.fee_rate).with_to_self_delay(params.to_self_delay),
+        };
+        let commitments = proposal.first_commitments()?;
+
+        Ok(Self { proposal, commitments })
+    }
+
+    /// The proposal to send to the other participants
+    pub fn proposal(&self) -> &FundingProposal {
+        &self.proposal
+    }
+
+    /// Accept a participant's signatures on the first commitments
+    ///
+    /// Every signature is verified before any is accepted.
+    ///
+    /// # Errors
+    /// * `MissingCommitmentSignature` if a commitment isn't signed
+    /// * `InvalidCommitmentSignature` if a signature doesn't verify
+    pub fn add_commitment_signatures(&mut self, signatures: FirstCommitmentSignatures) -> Result<(), FundingError> {
+        let FirstCommitmentSignatures { public_key, signatures } = signatures;
+        if !self.proposal.participants.contains(&public_key) {
+            return Err(FundingError::UnknownParticipant);
+        }
+
+        let mut commitments = self.commitments.clone();
+        for commitment in &mut commitments {
+            let signature = signatures.get(&commitment.holder)
+                .ok_or(FundingError::MissingCommitmentSignature(public_key.as_bytes()))?;
+            commitment
+                .add_signature(CommitmentSignature { public_key: public_key.clone(), signature: signature.clone() })
+                .map_err(|e| match e {
+                    CommitmentError::InvalidSignature(key) => FundingError::InvalidCommitmentSignature(key),
+                    e => e.into(),
+                })?;
+        }
+        self.commitments = commitments;
+        Ok(())
+    }
+
+    /// Sign the funding transaction, now that every first commitment is fully signed
+    ///
+    /// # Errors
+    /// * `MissingCommitmentSignature` naming the first participant that hasn't signed
+    pub fn release_funding(self, funder: &KeyPair) -> Result<OpenedChannel, FundingError> {
+        let proposal = self.proposal;
+        if funder.public_key() != proposal.funder {
+            return Err(FundingError::NotFunder);
+        }
+        for commitment in &self.commitments {
+            commitment.finalize().map_err(|e| match e {
+                CommitmentError::MissingSignature(key) => FundingError::MissingCommitmentSignature(key),
+                e => e.into(),
+            })?;
+        }
+
+        let channel = proposal.channel()?;
+        let mut funding_tx = proposal.funding_tx;
+        for index in 0..funding_tx.inputs.len() {
+            validation::sign_input(&mut funding_tx, index, funder, SighashType::All)?;
+        }
+
+        Ok(OpenedChannel { channel, funding_tx, commitments: self.commitments })
+    }
+}
+
+impl FundingProposal {
+    /// The channel output the funding transaction creates
+    pub fn funding_output(&self) -> FundingOutput {
+        let output = &self.funding_tx.outputs[FUNDING_OUTPUT_INDEX as usize];
+        FundingOutput {
+            txid: self.funding_tx.txid(),
+            index: FUNDING_OUTPUT_INDEX,
+            value: output.value,
+            threshold: self.threshold,
+            lock_script: output.lock_script.clone(),
+        }
+    }
+
+    /// The channel at state 0, as opened by the proposal
+    ///
+    /// Participants without an initial balance start at 0, and every
+    /// participant's revocation hashes are tracked.
+    ///
+    /// # Errors
+    /// * `InvalidProposal` if a participant's revocation hashes are missing
+    pub fn channel(&self) -> Result<ChannelState, FundingError> {
+        let mut balances = self.initial_balances.clone();
+        for participant in &self.participants {
+            balances.entry(participant.clone()).or_insert(0);
+        }
+        let mut channel = ChannelState::opening(self.participants.clone(), balances)
+            .with_funding(self.funding_output());
+        for participant in &self.participants {
+            let hashes = self.revocation_hashes.get(participant).ok_or(FundingError::InvalidProposal)?;
+            channel.add_revocation_hashes(participant, *hashes)?;
+        }
+        Ok(channel)
+    }
+
+    /// Every participant's commitment of state 0, unsigned
+    ///
+    /// Participants whose initial balance can't pay the fee get none, except
+    /// for the funder, who must always be able to close.
+    pub fn first_commitments(&self) -> Result<Vec<CommitmentTransaction>, FundingError> {
+        let channel = self.channel()?;
+        let mut commitments = Vec::with_capacity(self.participants.len());
+        for participant in &self.participants {
+            let revocation_hash = self.revocation_hashes[participant][0];
+            match CommitmentTransaction::build(&channel, participant, revocation_hash, &self.commitment_params) {
+                Ok(commitment) => commitments.push(commitment),
+                Err(CommitmentError::FeeTooHigh { .. }) if *participant != self.funder => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(commitments)
+    }
+
+    /// Check the proposal from a non-funding participant's point of view
+    ///
+    /// The funding output must be the agreed multisig holding exactly the
+    /// initial balances, and every participant's revocation hashes must be
+    /// included so the first commitments can be built and later revoked.
+    pub fn verify(&self) -> Result<(), FundingError> {
+        let mut sorted = self.participants.clone();
+        sorted.sort_by_key(|p| p.as_bytes());
+        sorted.dedup();
+        if sorted != self.participants {
+            return Err(FundingError::InvalidProposal);
+        }
+        let funding = self.funding_tx.outputs.get(FUNDING_OUTPUT_INDEX as usize)
+            .ok_or(FundingError::InvalidProposal)?;
+        check_terms(&self.participants, &self.initial_balances, &self.funder, funding.value, self.threshold)?;
+
+        if self.funding_tx.hash != self.funding_tx.txid()
+            || funding.lock_script != Script::multisig(self.threshold, &self.participants).into_bytes()
+        {
+            return Err(FundingError::InvalidProposal);
+        }
+        if self.revocation_hashes.len() != self.participants.len()
+            || self.participants.iter().any(|p| !self.revocation_hashes.contains_key(p))
+        {
+            return Err(FundingError::InvalidProposal);
+        }
+        self.first_commitments()?;
+        Ok(())
+    }
+
+    /// Verify the proposal and sign every first commitment
+    pub fn sign_commitments(&self, keypair: &KeyPair) -> Result<FirstCommitmentSignatures, FundingError> {
+        self.verify()?;
+        if !self.participants.contains(&keypair.public_key()) {
+            return Err(FundingError::UnknownParticipant);
+        }
+        let signatures = self.first_commitments()?
+            .iter()
+            .map(|commitment| Ok((commitment.holder.clone(), commitment.sign(keypair)?.signature)))
+            .collect::<Result<_, FundingError>>()?;
+        Ok(FirstCommitmentSignatures { public_key: keypair.public_key(), signatures })
+    }
+}
+
+/// Activate `channel` once its funding output has `min_confirmations`
+///
+/// # Errors
+/// * `FundingNotConfirmed` if the output is unknown, already spent, differs
+///   from the recorded one or isn't confirmed deeply enough
+pub fn confirm_funding(channel: &mut ChannelState, cache: &UtxoCache, min_confirmations: u32) -> Result<(), FundingError> {
+    let funding = channel.funding.as_ref().ok_or(FundingError::NotFunded)?;
+    let utxo = cache.get_utxo(funding.txid, funding.index)?
+        .filter(|utxo| utxo.output.value == funding.value && utxo.output.lock_script == funding.lock_script)
+        .ok_or(FundingError::FundingNotConfirmed)?;
+    if !utxo.is_confirmed || selection::confirmations(&utxo, cache.chain_tip()?) < min_confirmations {
+        return Err(FundingError::FundingNotConfirmed);
+    }
+    channel.activate()?;
+    Ok(())
+}
+
+/// Checks shared by the funder and the other participants
+fn check_terms(
+    participants: &[PublicKey],
+    initial_balances: &HashMap<PublicKey, i64>,
+    funder: &PublicKey,
+    funding_value: u64,
+    threshold: usize,
+) -> Result<(), FundingError> {
+    if participants.len() < 2 {
+        return Err(FundingError::TooFewParticipants);
+    }
+    if threshold == 0 || threshold > participants.len() {
+        return Err(FundingError::InvalidThreshold { threshold, participants: participants.len() });
+    }
+    if !participants.contains(funder) {
+        return Err(FundingError::FunderNotParticipant);
+    }
+    if initial_balances.keys().any(|p| !participants.contains(p)) {
+        return Err(FundingError::UnknownParticipant);
+    }
+    if initial_balances.values().any(|balance| *balance < 0) {
+        return Err(FundingError::NegativeBalance);
+    }
+
+    let balances = initial_balances.values().try_fold(0i64, |sum, balance| sum.checked_add(*balance));
+    match balances {
+        Some(balances) if u64::try_from(balances) == Ok(funding_value) => Ok(()),
+        _ => Err(FundingError::BalanceMismatch { balances: balances.unwrap_or(i64::MAX), funding: funding_value }),
+    }
+}
+
+/// Unlock script for the funding multisig
+///
+/// The multisig takes the first `threshold` signatures in key order, so
+/// `participants` must be sorted and every one of them must have signed.
+pub(super) fn multisig_unlock(
+    participants: &[PublicKey],
+    threshold: usize,
+    signatures: &HashMap<PublicKey, Vec<u8>>,
+) -> Vec<u8> {
+    participants.iter()
+        .take(threshold)
+        .fold(Script::new(), |script, p| script.push_data(&signatures[p]))
+        .into_bytes()
+}
+
+/// Whether `element` is `public_key`'s SIGHASH_ALL signature of input 0 of `tx`
+pub(super) fn verify_input_signature(tx: &Transaction, public_key: &PublicKey, element: &[u8]) -> bool {
+    let Some((&sighash_byte, signature)) = element.split_last() else {
+        return false;
+    };
+    let Some(message) = SighashType::from_u8(sighash_byte)
+        .filter(|sighash_type| *sighash_type == SighashType::All)
+        .and_then(|sighash_type| tx.sighash(0, sighash_type))
+    else {
+        return false;
+    };
+    Signature::from_bytes(signature).is_ok_and(|signature| public_key.verify_strict(&signature, message.as_bytes()))
+}
+
+pub(super) fn new_transaction(inputs: Vec<Input>, outputs: Vec<Output>) -> Transaction {
+    let mut tx = Transaction {
+        version: CHANNEL_TX_VERSION,
+        inputs,
+        outputs,
+        lock_time: 0,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+    tx
+}
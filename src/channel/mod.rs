@@ -1,8 +1,15 @@
 use std::collections::HashMap;
 use crate::crypto::{PublicKey, Signature};
 
+pub mod chain;
+pub mod close;
+pub mod commitment;
+pub mod funding;
+pub mod policy;
+pub mod revocation;
 pub mod state;
 pub mod transitions;
+pub mod wal;
 
 #[derive(Debug, Clone)]
 pub struct StateUpdate {
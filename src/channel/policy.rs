@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::transitions::ChannelError;
+use crate::crypto::PublicKey;
+
+/// Which participants must sign a channel update
+///
+/// Signers of an update are its `affected_participants`, each of which
+/// provides a signature. Policies combine with [`SignaturePolicy::All`]; "any
+/// 3 of 5 operators plus the payer" is `All` of a 3-of-5 `Threshold` over the
+/// operators and `AllDebited`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignaturePolicy {
+    /// Every participant with an entry in the balance changes must sign
+    #[default]
+    AllAffected,
+    /// Every participant whose balance decreases must sign; credited
+    /// participants may sign but don't have to
+    AllDebited,
+    /// At least `threshold` of `signers` must sign
+    Threshold {
+        threshold: usize,
+        signers: Vec<PublicKey>,
+    },
+    /// Every one of these participants must sign
+    Required(Vec<PublicKey>),
+    /// Every sub-policy must be satisfied
+    All(Vec<SignaturePolicy>),
+}
+
+impl SignaturePolicy {
+    /// Check that `signers` satisfy the policy for `balance_changes`
+    ///
+    /// # Errors
+    /// * `MissingSigners` naming the participants that are required but didn't sign
+    /// * `ThresholdNotMet` naming the eligible participants that didn't sign
+    pub fn check(
+        &self,
+        balance_changes: &HashMap<PublicKey, i64>,
+        signers: &[PublicKey],
+    ) -> Result<(), ChannelError> {
+        match self {
+            SignaturePolicy::AllAffected => require_all(balance_changes.keys(), signers),
+            SignaturePolicy::AllDebited => {
+                require_all(balance_changes.iter().filter(|(_, change)| **change < 0).map(|(p, _)| p), signers)
+            }
+            SignaturePolicy::Threshold { threshold, signers: eligible } => {
+                let (signed, missing): (Vec<&PublicKey>, Vec<&PublicKey>) =
+                    eligible.iter().partition(|p| signers.contains(p));
+                if signed.len() >= *threshold {
+                    return Ok(());
+                }
+                Err(ChannelError::ThresholdNotMet {
+                    threshold: *threshold,
+                    signed: signed.len(),
+                    missing: sorted(missing),
+                })
+            }
+            SignaturePolicy::Required(required) => require_all(required.iter(), signers),
+            SignaturePolicy::All(policies) => policies
+                .iter()
+                .try_for_each(|policy| policy.check(balance_changes, signers)),
+        }
+    }
+}
+
+fn require_all<'a>(required: impl Iterator<Item = &'a PublicKey>, signers: &[PublicKey]) -> Result<(), ChannelError> {
+    let missing: Vec<&PublicKey> = required.filter(|p| !signers.contains(p)).collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(ChannelError::MissingSigners(sorted(missing)))
+    }
+}
+
+fn sorted(keys: Vec<&PublicKey>) -> Vec<PublicKey> {
+    let mut keys: Vec<PublicKey> = keys.into_iter().cloned().collect();
+    keys.sort_by_key(|key| key.as_bytes());
+    keys.dedup();
+    keys
+}
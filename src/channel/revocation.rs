@@ -0,0 +1,323 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::chain::{ChainBackend, ChainError};
+use super::commitment::{self, CommitmentParams};
+use super::funding;
+use super::state::{ChannelState, ChannelStatus};
+use super::transitions::ChannelError;
+use crate::crypto::{KeyPair, PublicKey};
+use crate::utxo::models::{Input, Output, SighashType, Transaction};
+use crate::utxo::script::{self, Script, ScriptError};
+use crate::utxo::selection::{INPUT_BYTES, OUTPUT_BYTES, TX_OVERHEAD_BYTES};
+use crate::utxo::timelock::SEQUENCE_FINAL;
+
+/// Secret indices have 48 bits; the secret of sequence number `n` has index
+/// `FIRST_SECRET_INDEX - n`, so indices count down as in BOLT 3
+pub const FIRST_SECRET_INDEX: u64 = (1 << 48) - 1;
+/// Secrets a [`RevocationStore`] keeps to derive every revealed one
+const STORE_SLOTS: usize = 49;
+
+#[derive(Error, Debug)]
+pub enum RevocationError {
+    #[error("Sequence number {0} has no per-commitment secret")]
+    SequenceOutOfRange(u64),
+    #[error("Expected the secret of state {expected}, got {got}")]
+    OutOfOrder { expected: u64, got: u64 },
+    #[error("Secret of state {0} doesn't match the ones revealed before")]
+    InconsistentSecret(u64),
+    #[error("Secret of state {0} doesn't match its revocation hash")]
+    HashMismatch(u64),
+    #[error("State {0} has not been revoked")]
+    NotRevoked(u64),
+    #[error("No revocation hash known for state {0}")]
+    UnknownRevocationHash(u64),
+    #[error("Transaction is not a commitment of this participant")]
+    NotACommitment,
+    #[error("Key can't claim revoked outputs of this commitment")]
+    NotACounterparty,
+    #[error("Revoked output of {value} can't pay the fee {fee}")]
+    FeeTooHigh { value: u64, fee: u64 },
+    #[error("Chain error: {0}")]
+    Chain(#[from] ChainError),
+    #[error("Script error: {0}")]
+    Script(#[from] ScriptError),
+    #[error("Channel error: {0}")]
+    Channel(#[from] ChannelError),
+}
+
+/// A participant's per-commitment secrets, derived from one seed
+///
+/// The secret of each state revokes that state's commitment once revealed;
+/// its SHA-256 is the commitment's revocation hash. The seed is zeroized on
+/// drop.
+pub struct RevocationSecrets {
+    seed: [u8; 32],
+}
+
+impl RevocationSecrets {
+    pub fn generate() -> Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        Self { seed }
+    }
+
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self { seed }
+    }
+
+    /// Secret of the commitments at `sequence_number`
+    pub fn secret(&self, sequence_number: u64) -> Result<[u8; 32], RevocationError> {
+        Ok(derive_secret(self.seed, 48, secret_index(sequence_number)?))
+    }
+
+    /// Revocation hash of the commitments at `sequence_number`
+    pub fn revocation_hash(&self, sequence_number: u64) -> Result<[u8; 32], RevocationError> {
+        Ok(Sha256::digest(self.secret(sequence_number)?).into())
+    }
+
+    /// Revoke `sequence_number` once the commitments of the next state are signed
+    ///
+    /// The message also carries the revocation hash of `sequence_number + 2`,
+    /// which the other participants need for the state after the next one.
+    /// The hashes of states 0 and 1 are exchanged when the channel opens.
+    pub fn revoke(
+        &self,
+        channel_id: [u8; 32],
+        public_key: &PublicKey,
+        sequence_number: u64,
+    ) -> Result<Revocation, RevocationError> {
+        Ok(Revocation {
+            channel_id,
+            public_key: public_key.clone(),
+            sequence_number,
+            secret: self.secret(sequence_number)?,
+            next_revocation_hash: self.revocation_hash(sequence_number + 2)?,
+        })
+    }
+}
+
+impl Drop for RevocationSecrets {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+/// Revocation of a participant's commitment, sent with each `StateUpdate`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revocation {
+    pub channel_id: [u8; 32],
+    /// Holder of the revoked commitment
+    pub public_key: PublicKey,
+    /// State whose commitment is revoked
+    pub sequence_number: u64,
+    #[serde(with = "hex")]
+    pub secret: [u8; 32],
+    /// Revocation hash of the holder's commitment at `sequence_number + 2`
+    #[serde(with = "hex")]
+    pub next_revocation_hash: [u8; 32],
+}
+
+/// Compact storage of one participant's revealed secrets
+///
+/// Keeps at most 49 secrets, from which every secret revealed so far can be
+/// derived (the shachain scheme of BOLT 3). Secrets must be added in order,
+/// and each is checked against the ones before it. A store created with
+/// [`from_hashes`](Self::from_hashes) also tracks the revocation hashes the
+/// next secrets must match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevocationStore {
+    /// Slot `i` holds the latest secret whose index has `i` trailing zeros
+    known: Vec<Option<([u8; 32], u64)>>,
+    /// Sequence number of the next secret to add
+    next_sequence: u64,
+    /// Revocation hashes of the states from `next_sequence` on, if tracked
+    #[serde(default)]
+    upcoming: Vec<[u8; 32]>,
+}
+
+impl Default for RevocationStore {
+    fn default() -> Self {
+        Self {
+            known: vec![None; STORE_SLOTS],
+            next_sequence: 0,
+            upcoming: Vec::new(),
+        }
+    }
+}
+
+impl RevocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store tracking the revocation hashes of states 0 and 1, as
+    /// exchanged when the channel opens
+    pub fn from_hashes(hashes: [[u8; 32]; 2]) -> Self {
+        Self {
+            upcoming: hashes.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    /// Add the next secret, checked against its tracked revocation hash
+    ///
+    /// The hash `revocation` announces for two states later is tracked in
+    /// turn.
+    ///
+    /// # Errors
+    /// * `UnknownRevocationHash` if no hash is tracked for the revoked state
+    /// * Any error of [`add`](Self::add)
+    pub fn revoke(&mut self, revocation: &Revocation) -> Result<(), RevocationError> {
+        let expected = *self.upcoming.first()
+            .ok_or(RevocationError::UnknownRevocationHash(revocation.sequence_number))?;
+        self.add(revocation, &expected)?;
+        self.upcoming.remove(0);
+        self.upcoming.push(revocation.next_revocation_hash);
+        Ok(())
+    }
+
+    /// Tracked revocation hash of a state that hasn't been revoked yet
+    pub fn revocation_hash(&self, sequence_number: u64) -> Option<[u8; 32]> {
+        let offset = sequence_number.checked_sub(self.next_sequence)?;
+        self.upcoming.get(usize::try_from(offset).ok()?).copied()
+    }
+
+    /// Add a revealed secret after checking it against `revocation_hash`
+    ///
+    /// # Errors
+    /// * `OutOfOrder` unless `revocation` is for the next state
+    /// * `HashMismatch` if the secret doesn't hash to `revocation_hash`
+    /// * `InconsistentSecret` if earlier secrets can't be derived from it
+    pub fn add(&mut self, revocation: &Revocation, revocation_hash: &[u8; 32]) -> Result<(), RevocationError> {
+        let sequence_number = revocation.sequence_number;
+        if sequence_number != self.next_sequence {
+            return Err(RevocationError::OutOfOrder { expected: self.next_sequence, got: sequence_number });
+        }
+        if Sha256::digest(revocation.secret).as_slice() != revocation_hash {
+            return Err(RevocationError::HashMismatch(sequence_number));
+        }
+
+        let index = secret_index(sequence_number)?;
+        let position = index.trailing_zeros().min(48) as usize;
+        for (secret, known_index) in self.known.iter().take(position).flatten() {
+            if derive_secret(revocation.secret, position, *known_index) != *secret {
+                return Err(RevocationError::InconsistentSecret(sequence_number));
+            }
+        }
+        self.known[position] = Some((revocation.secret, index));
+        self.next_sequence += 1;
+        Ok(())
+    }
+
+    /// Secret revealed for `sequence_number`, if it has been revoked
+    pub fn secret(&self, sequence_number: u64) -> Option<[u8; 32]> {
+        if sequence_number >= self.next_sequence {
+            return None;
+        }
+        let index = secret_index(sequence_number).ok()?;
+        self.known.iter().enumerate().find_map(|(bits, slot)| {
+            let (secret, known_index) = slot.as_ref()?;
+            let mask = FIRST_SECRET_INDEX & !((1u64 << bits) - 1);
+            (index & mask == *known_index).then(|| derive_secret(*secret, bits, index))
+        })
+    }
+
+    /// Every state before this one has been revoked
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+}
+
+/// Build a justice transaction for a revoked commitment seen on chain
+///
+/// `revoked` is a commitment of `cheater` for an old state of `channel`. Its
+/// sequence number is read from `lock_time`, and the matching secret from
+/// `store`. The transaction spends the cheater's entire output to
+/// `claimer`, who must be one of the other participants.
+///
+/// # Errors
+/// * `NotRevoked` if the commitment's state hasn't been revoked
+/// * `NotACommitment` if `revoked` doesn't pay `cheater` through a revocable output
+pub fn build_justice(
+    channel: &ChannelState,
+    cheater: &PublicKey,
+    revoked: &Transaction,
+    store: &RevocationStore,
+    claimer: &KeyPair,
+    params: &CommitmentParams,
+) -> Result<Transaction, RevocationError> {
+    let sequence_number = revoked.lock_time;
+    let secret = store.secret(sequence_number).ok_or(RevocationError::NotRevoked(sequence_number))?;
+    let revocation_hash: [u8; 32] = Sha256::digest(secret).into();
+
+    let mut counterparties: Vec<PublicKey> = channel.participants.iter().filter(|p| *p != cheater).cloned().collect();
+    counterparties.sort_by_key(|p| p.as_bytes());
+    if !counterparties.contains(&claimer.public_key()) {
+        return Err(RevocationError::NotACounterparty);
+    }
+    let lock_script = commitment::to_self_script(cheater, &counterparties, &revocation_hash, params.to_self_delay)
+        .into_bytes();
+    let (index, output) = revoked.outputs.iter()
+        .enumerate()
+        .find(|(_, output)| output.lock_script == lock_script)
+        .ok_or(RevocationError::NotACommitment)?;
+
+    let fee = params.fee_rate.saturating_mul(TX_OVERHEAD_BYTES + INPUT_BYTES + OUTPUT_BYTES);
+    if output.value <= fee {
+        return Err(RevocationError::FeeTooHigh { value: output.value, fee });
+    }
+    let input = Input {
+        previous_output: revoked.hash,
+        index: index as u32,
+        signature: vec![],
+        sequence: SEQUENCE_FINAL,
+    };
+    let payout = Output {
+        value: output.value - fee,
+        public_key_hash: claimer.public_key().public_key_hash().to_vec(),
+        lock_script: vec![],
+    };
+    let mut tx = funding::new_transaction(vec![input], vec![payout]);
+
+    let signature = script::sign(&tx, 0, claimer, SighashType::All)?;
+    tx.inputs[0].signature = Script::new().push_data(&signature).push_data(&secret).push_int(1).into_bytes();
+    script::verify_script(&tx.inputs[0].signature, &lock_script, &tx, 0)?;
+    Ok(tx)
+}
+
+/// Broadcast a justice transaction and mark `channel` as `Penalized`
+pub fn penalize(
+    channel: &mut ChannelState,
+    justice_tx: &Transaction,
+    backend: &impl ChainBackend,
+) -> Result<(), RevocationError> {
+    backend.broadcast(justice_tx)?;
+    if matches!(channel.status, ChannelStatus::Active | ChannelStatus::Closing) {
+        channel.dispute()?;
+    }
+    channel.penalize()?;
+    Ok(())
+}
+
+fn secret_index(sequence_number: u64) -> Result<u64, RevocationError> {
+    FIRST_SECRET_INDEX
+        .checked_sub(sequence_number)
+        .ok_or(RevocationError::SequenceOutOfRange(sequence_number))
+}
+
+/// Derive the secret at `index` from `base`, flipping and hashing the low `bits` bits
+fn derive_secret(base: [u8; 32], bits: usize, index: u64) -> [u8; 32] {
+    let mut secret = base;
+    for bit in (0..bits).rev() {
+        if index & (1 << bit) != 0 {
+            secret[bit / 8] ^= 1 << (bit % 8);
+            secret = Sha256::digest(secret).into();
+        }
+    }
+    secret
+}
@@ -1,15 +1,69 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
-use crate::crypto::{PublicKey, Signature, verify_partial_multisig, CryptoError};
-use crate::channel::transitions::StateUpdateForSigning;
+use crate::crypto::{PublicKey, SchemePublicKey, SchemeSignature, Signature, SignatureScheme, verify_partial_multisig};
+use crate::crypto::musig::KeyAggContext;
+use crate::channel::funding::FundingOutput;
+use crate::channel::policy::SignaturePolicy;
+use crate::channel::revocation::{Revocation, RevocationStore};
+use crate::channel::transitions::{ChannelError, StateUpdateForSigning};
 use sha2::{Sha256, Digest};
 use bincode;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Lifecycle of a channel
+///
+/// `Opening → Active → Closing → Settled` for a cooperative lifecycle, with
+/// `Active`/`Closing → Disputed → Resolved | Penalized` when a participant
+/// contests a state published on chain. Only `Active` channels accept updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ChannelStatus {
-    Open,
-    Closed,
+    /// Waiting for the funding transaction to confirm
+    Opening,
+    /// Funded; off-chain updates are accepted
+    Active,
+    /// A close was initiated; the balances are frozen
+    Closing,
+    /// The closing transaction confirmed
+    Settled,
+    /// A state published on chain is being contested
     Disputed,
+    /// The dispute ended with the latest state being settled
+    Resolved,
+    /// The dispute ended with a cheating participant's funds forfeited
+    Penalized,
+}
+
+impl ChannelStatus {
+    /// Whether the state machine allows moving from `self` to `next`
+    pub fn can_transition_to(self, next: ChannelStatus) -> bool {
+        use ChannelStatus::*;
+        matches!(
+            (self, next),
+            (Opening, Active)
+                | (Active, Closing)
+                | (Active, Disputed)
+                | (Closing, Settled)
+                | (Closing, Disputed)
+                | (Disputed, Resolved)
+                | (Disputed, Penalized)
+        )
+    }
+
+    /// Whether the channel's lifecycle is over
+    pub fn is_final(self) -> bool {
+        matches!(self, ChannelStatus::Settled | ChannelStatus::Resolved | ChannelStatus::Penalized)
+    }
+}
+
+/// A recorded change of a channel's status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub from: ChannelStatus,
+    pub to: ChannelStatus,
+    /// Sequence number of the channel state when the transition happened
+    pub sequence_number: u64,
+    /// UNIX timestamp of the transition
+    pub timestamp: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -20,13 +74,36 @@ pub struct ChannelState {
     pub sequence_number: u64,
     pub status: ChannelStatus,
     pub latest_update: Option<StateUpdate>,
+    /// Scheme used to sign the channel's on-chain (funding and settlement)
+    /// transactions; off-chain updates are always signed with Ed25519
+    #[serde(default)]
+    pub signature_scheme: SignatureScheme,
+    /// MuSig2 aggregate of the participants' Schnorr keys, if the channel
+    /// accepts updates authorized by a single aggregate signature
+    #[serde(default)]
+    pub aggregate_key: Option<SchemePublicKey>,
+    /// Which participants must sign an update
+    #[serde(default)]
+    pub signature_policy: SignaturePolicy,
+    /// Every status change so far, oldest first, for auditing
+    #[serde(default)]
+    pub status_history: Vec<StatusTransition>,
+    /// On-chain output holding the channel's funds, for channels opened
+    /// through [`ChannelOpening`](crate::channel::funding::ChannelOpening)
+    #[serde(default)]
+    pub funding: Option<FundingOutput>,
+    /// Secrets each participant revealed for its revoked commitments, and
+    /// the revocation hashes of its upcoming ones
+    #[serde(default)]
+    pub revocations: HashMap<PublicKey, RevocationStore>,
 }
 
 impl ChannelState {
+    /// An active channel, e.g. one whose funding is already confirmed
     pub fn new(participants: Vec<PublicKey>, initial_balances: HashMap<PublicKey, i64>) -> Self {
         // Create a unique channel ID by hashing the sorted participants
         let mut sorted_participants = participants.clone();
-        sorted_participants.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
+        sorted_participants.sort_by_key(|a| a.as_bytes());
         
         let mut hasher = Sha256::new();
         for participant in &sorted_participants {
@@ -39,12 +116,118 @@ impl ChannelState {
             participants,
             balances: initial_balances,
             sequence_number: 0,
-            status: ChannelStatus::Open,
+            status: ChannelStatus::Active,
             latest_update: None,
+            signature_scheme: SignatureScheme::default(),
+            aggregate_key: None,
+            signature_policy: SignaturePolicy::default(),
+            status_history: Vec::new(),
+            funding: None,
+            revocations: HashMap::new(),
         }
     }
+
+    /// A channel waiting for its funding transaction; see [`activate`](Self::activate)
+    pub fn opening(participants: Vec<PublicKey>, initial_balances: HashMap<PublicKey, i64>) -> Self {
+        Self {
+            status: ChannelStatus::Opening,
+            ..Self::new(participants, initial_balances)
+        }
+    }
+
+    /// Declare the scheme settlement transactions are signed with
+    pub fn with_signature_scheme(mut self, scheme: SignatureScheme) -> Self {
+        self.signature_scheme = scheme;
+        self
+    }
+    
+    /// Require updates to be signed according to `policy`
+    pub fn with_signature_policy(mut self, policy: SignaturePolicy) -> Self {
+        self.signature_policy = policy;
+        self
+    }
+    
+    /// Accept updates signed by a single MuSig2 signature of all participants
+    ///
+    /// Each participant contributes its Schnorr key to `key_agg`; the
+    /// Ed25519 participant keys still identify balances.
+    pub fn with_aggregate_key(mut self, key_agg: &KeyAggContext) -> Self {
+        self.aggregate_key = Some(SchemePublicKey::Schnorr(key_agg.aggregate_key()));
+        self
+    }
+    
+    /// Record the on-chain output that funds the channel
+    pub fn with_funding(mut self, funding: FundingOutput) -> Self {
+        self.funding = Some(funding);
+        self
+    }
+    
+    /// Funding confirmed: `Opening → Active`
+    pub fn activate(&mut self) -> Result<(), ChannelError> {
+        self.transition_to(ChannelStatus::Active)
+    }
+
+    /// Start closing the channel: `Active → Closing`
+    pub fn begin_close(&mut self) -> Result<(), ChannelError> {
+        self.transition_to(ChannelStatus::Closing)
+    }
+
+    /// Closing transaction confirmed: `Closing → Settled`
+    pub fn settle(&mut self) -> Result<(), ChannelError> {
+        self.transition_to(ChannelStatus::Settled)
+    }
+
+    /// Contest a state published on chain: `Active | Closing → Disputed`
+    pub fn dispute(&mut self) -> Result<(), ChannelError> {
+        self.transition_to(ChannelStatus::Disputed)
+    }
+
+    /// Dispute ended with the latest state: `Disputed → Resolved`
+    pub fn resolve(&mut self) -> Result<(), ChannelError> {
+        self.transition_to(ChannelStatus::Resolved)
+    }
+
+    /// Dispute ended with a cheater punished: `Disputed → Penalized`
+    pub fn penalize(&mut self) -> Result<(), ChannelError> {
+        self.transition_to(ChannelStatus::Penalized)
+    }
+
+    /// Move to `next`, recording the transition in `status_history`
+    pub fn transition_to(&mut self, next: ChannelStatus) -> Result<(), ChannelError> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        self.record_transition(StatusTransition {
+            from: self.status,
+            to: next,
+            sequence_number: self.sequence_number,
+            timestamp,
+        })
+    }
+
+    /// Apply a transition recorded earlier, e.g. when replaying a log
+    ///
+    /// # Errors
+    /// * `InvalidStatusTransition` if the channel isn't in `transition.from`
+    ///   or the state machine doesn't allow the transition
+    pub fn record_transition(&mut self, transition: StatusTransition) -> Result<(), ChannelError> {
+        if transition.from != self.status || !self.status.can_transition_to(transition.to) {
+            return Err(ChannelError::InvalidStatusTransition {
+                from: self.status,
+                to: transition.to,
+            });
+        }
+        self.status = transition.to;
+        self.status_history.push(transition);
+        Ok(())
+    }
     
     pub fn apply_update(&mut self, update: &StateUpdate) -> Result<(), &'static str> {
+        self.ensure_active()?;
+        self.signature_policy
+            .check(&update.balance_changes, &update.affected_participants)
+            .map_err(|_| "Signature policy not satisfied")?;
+        let revocations = self.check_revocations(&update.revocations)
+            .map_err(|_| "Invalid revocations")?;
+        
         // Sort participants for consistent message construction
         let mut sorted_participants = update.affected_participants.clone();
         sorted_participants.sort_by_key(|k| k.as_bytes());
@@ -53,7 +236,7 @@ impl ChannelState {
         let mut sorted_changes: Vec<_> = update.balance_changes.iter()
             .map(|(k, v)| (k.clone(), *v))
             .collect();
-        sorted_changes.sort_by(|a, b| a.0.as_bytes().cmp(&b.0.as_bytes()));
+        sorted_changes.sort_by_key(|a| a.0.as_bytes());
         
         // Construct message for verification using StateUpdateForSigning
         let message = StateUpdateForSigning {
@@ -75,23 +258,131 @@ impl ChannelState {
             &message_bytes
         ).map_err(|_| "Invalid signatures")?;
         
-        // Update sequence number
-        if update.sequence_number != self.sequence_number + 1 {
+        self.apply_balance_changes(update.sequence_number, &update.balance_changes)?;
+        self.revocations = revocations;
+        
+        // Store latest update
+        self.latest_update = Some(update.clone());
+        Ok(())
+    }
+
+    /// Apply an update authorized by one aggregate signature over the same
+    /// message individual signers would sign
+    ///
+    /// `latest_update` only tracks individually signed updates and is left
+    /// unchanged.
+    pub fn apply_aggregate_update(&mut self, update: &AggregateStateUpdate) -> Result<(), &'static str> {
+        self.ensure_active()?;
+        let aggregate_key = self.aggregate_key.as_ref().ok_or("Channel has no aggregate key")?;
+        if update.affected_participants.iter().any(|p| !self.participants.contains(p)) {
+            return Err("Unknown participant");
+        }
+        // The channel's total value can't change
+        let sum = update.balance_changes.values().try_fold(0i64, |sum, change| sum.checked_add(*change));
+        if sum != Some(0) {
+            return Err("Non-zero balance change");
+        }
+        
+        let mut message = StateUpdateForSigning::new(
+            update.sequence_number,
+            self.channel_id,
+            &update.balance_changes,
+            &update.affected_participants,
+        );
+        message.timestamp = update.timestamp;
+        let message_bytes = bincode::serialize(&message).map_err(|_| "Serialization failed")?;
+        
+        if !aggregate_key.verify(&update.signature, &message_bytes) {
+            return Err("Invalid signatures");
+        }
+        let revocations = self.check_revocations(&update.revocations)
+            .map_err(|_| "Invalid revocations")?;
+        
+        self.apply_balance_changes(update.sequence_number, &update.balance_changes)?;
+        self.revocations = revocations;
+        Ok(())
+    }
+
+    /// Track the revocations of `public_key`'s commitments
+    ///
+    /// `hashes` are the revocation hashes of its commitments at states 0 and
+    /// 1, exchanged when the channel opens. Every later update must carry
+    /// the participant's revocation of the state it replaces, as must every
+    /// update of a funded channel for all participants.
+    ///
+    /// # Errors
+    /// * `UnknownParticipant` if `public_key` isn't a participant
+    /// * `InvalidSequence` once the channel has moved past state 0
+    pub fn add_revocation_hashes(&mut self, public_key: &PublicKey, hashes: [[u8; 32]; 2]) -> Result<(), ChannelError> {
+        if !self.participants.contains(public_key) {
+            return Err(ChannelError::UnknownParticipant);
+        }
+        if self.sequence_number != 0 {
+            return Err(ChannelError::InvalidSequence);
+        }
+        self.revocations.insert(public_key.clone(), RevocationStore::from_hashes(hashes));
+        Ok(())
+    }
+
+    /// The revocation stores after adding `revocations` of the current state
+    ///
+    /// Funded channels need a revocation from every participant, others
+    /// from the participants whose revocation hashes are tracked.
+    ///
+    /// # Errors
+    /// * `InvalidRevocation` if a revocation is for another channel or
+    ///   state, is repeated, or doesn't match the tracked revocation hash
+    /// * `MissingRevocation` naming the first participant without one
+    pub(super) fn check_revocations(&self, revocations: &[Revocation]) -> Result<HashMap<PublicKey, RevocationStore>, ChannelError> {
+        let mut stores = self.revocations.clone();
+        let mut revoked = HashSet::with_capacity(revocations.len());
+        for revocation in revocations {
+            let valid = revocation.channel_id == self.channel_id
+                && revocation.sequence_number == self.sequence_number
+                && revoked.insert(&revocation.public_key)
+                && stores.get_mut(&revocation.public_key).is_some_and(|store| store.revoke(revocation).is_ok());
+            if !valid {
+                return Err(ChannelError::InvalidRevocation(revocation.public_key.as_bytes()));
+            }
+        }
+        
+        let missing = self.participants.iter()
+            .filter(|p| self.funding.is_some() || self.revocations.contains_key(*p))
+            .find(|p| !revoked.contains(p));
+        if let Some(missing) = missing {
+            return Err(ChannelError::MissingRevocation(missing.as_bytes()));
+        }
+        Ok(stores)
+    }
+    
+    fn ensure_active(&self) -> Result<(), &'static str> {
+        if self.status != ChannelStatus::Active {
+            return Err("Channel is not active");
+        }
+        Ok(())
+    }
+    
+    /// Move to `sequence_number` with `balance_changes` applied
+    ///
+    /// New balances are computed before anything is written, so a rejected
+    /// update leaves the state untouched.
+    fn apply_balance_changes(&mut self, sequence_number: u64, balance_changes: &HashMap<PublicKey, i64>) -> Result<(), &'static str> {
+        if sequence_number != self.sequence_number + 1 {
             return Err("Invalid sequence number");
         }
-        self.sequence_number = update.sequence_number;
         
-        // Apply balance changes
-        for (participant, change) in &update.balance_changes {
-            let balance = self.balances.get_mut(participant).ok_or("Unknown participant")?;
-            *balance = balance.checked_add(*change).ok_or("Balance overflow")?;
-            if *balance < 0 {
+        let mut new_balances = HashMap::with_capacity(balance_changes.len());
+        for (participant, change) in balance_changes {
+            let balance = self.balances.get(participant).ok_or("Unknown participant")?;
+            let balance = balance.checked_add(*change).ok_or("Balance overflow")?;
+            if balance < 0 {
                 return Err("Negative balance");
             }
+            new_balances.insert(participant.clone(), balance);
         }
         
-        // Store latest update
-        self.latest_update = Some(update.clone());
+        self.sequence_number = sequence_number;
+        self.balances.extend(new_balances);
         Ok(())
     }
 }
@@ -103,4 +394,22 @@ pub struct StateUpdate {
     pub signatures: Vec<Signature>,
     pub affected_participants: Vec<PublicKey>,
     pub timestamp: u64,
+    /// Revocations of the state this update replaces
+    #[serde(default)]
+    pub revocations: Vec<Revocation>,
+}
+
+/// A state update authorized by one MuSig2 signature of all channel
+/// participants instead of a signature per affected participant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateStateUpdate {
+    pub sequence_number: u64,
+    pub balance_changes: HashMap<PublicKey, i64>,
+    pub affected_participants: Vec<PublicKey>,
+    pub timestamp: u64,
+    /// BIP340 signature under the channel's aggregate key
+    pub signature: SchemeSignature,
+    /// Revocations of the state this update replaces
+    #[serde(default)]
+    pub revocations: Vec<Revocation>,
 }
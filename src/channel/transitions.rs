@@ -1,8 +1,11 @@
-use super::state::{ChannelState, StateUpdate};
+use super::state::{ChannelState, ChannelStatus, StateUpdate};
 use crate::crypto;
+use crate::crypto::batch::{self, BatchError};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::thread;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ChannelError {
@@ -10,8 +13,8 @@ pub enum ChannelError {
     InvalidSequence,
     #[error("Insufficient balance")]
     InsufficientBalance,
-    #[error("Invalid signature")]
-    InvalidSignature,
+    #[error("Invalid signature from signer {0}")]
+    InvalidSignature(usize),
     #[error("Stale update")]
     StaleUpdate,
     #[error("Unknown participant")]
@@ -24,6 +27,38 @@ pub enum ChannelError {
     InvalidSignatureCount,
     #[error("Serialization error")]
     SerializationError,
+    #[error("Cannot move channel from {from:?} to {to:?}")]
+    InvalidStatusTransition {
+        from: ChannelStatus,
+        to: ChannelStatus,
+    },
+    #[error("Channel is {0:?}, updates need an active channel")]
+    ChannelNotActive(ChannelStatus),
+    #[error("Missing signatures from {}", format_keys(.0))]
+    MissingSigners(Vec<crypto::PublicKey>),
+    #[error("{signed} of {threshold} required signatures; missing {}", format_keys(.missing))]
+    ThresholdNotMet {
+        threshold: usize,
+        signed: usize,
+        missing: Vec<crypto::PublicKey>,
+    },
+    #[error("Update doesn't revoke the current state of {}", hex::encode(.0))]
+    MissingRevocation([u8; 32]),
+    #[error("Invalid revocation from {}", hex::encode(.0))]
+    InvalidRevocation([u8; 32]),
+}
+
+impl From<BatchError> for ChannelError {
+    fn from(e: BatchError) -> Self {
+        match e {
+            BatchError::LengthMismatch { .. } => ChannelError::InvalidSignatureCount,
+            BatchError::InvalidSignature(index) => ChannelError::InvalidSignature(index),
+        }
+    }
+}
+
+fn format_keys(keys: &[crypto::PublicKey]) -> String {
+    keys.iter().map(|key| hex::encode(key.as_bytes())).collect::<Vec<_>>().join(", ")
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -44,13 +79,13 @@ impl StateUpdateForSigning {
     ) -> Self {
         // Sort affected participants by public key for deterministic ordering
         let mut sorted_participants = affected_participants.to_vec();
-        sorted_participants.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
+        sorted_participants.sort_by_key(|a| a.as_bytes());
         
         // Create a sorted Vec of balance changes
         let mut sorted_changes: Vec<_> = balance_changes.iter()
             .map(|(k, v)| (k.clone(), *v))
             .collect();
-        sorted_changes.sort_by(|a, b| a.0.as_bytes().cmp(&b.0.as_bytes()));
+        sorted_changes.sort_by_key(|a| a.0.as_bytes());
         
         Self {
             sequence_number,
@@ -61,6 +96,18 @@ impl StateUpdateForSigning {
         }
     }
     
+    /// Message signed for `update` to `channel`
+    pub fn for_update(channel: &ChannelState, update: &StateUpdate) -> Self {
+        let mut message = Self::new(
+            update.sequence_number,
+            channel.channel_id,
+            &update.balance_changes,
+            &update.affected_participants,
+        );
+        message.timestamp = update.timestamp;
+        message
+    }
+
     // Helper function to verify signatures in deterministic order
     pub fn verify_signatures(&self, signatures: &[crypto::Signature]) -> Result<(), ChannelError> {
         if signatures.len() != self.affected_participants.len() {
@@ -69,15 +116,10 @@ impl StateUpdateForSigning {
         
         let message = bincode::serialize(&self).map_err(|_| ChannelError::SerializationError)?;
         
-        // Verify signatures in the same order as affected_participants
-        for (i, participant) in self.affected_participants.iter().enumerate() {
-            // Verify that the signature at index i corresponds to the participant at index i
-            if !participant.verify_signature(&signatures[i], &message) {
-                println!("Signature verification failed for participant at index {}", i);
-                return Err(ChannelError::InvalidSignature);
-            }
-        }
-        
+        // The signature at index i must correspond to the participant at index i
+        let messages = vec![message.as_slice(); signatures.len()];
+        batch::verify_batch(&messages, signatures, &self.affected_participants)?;
+
         Ok(())
     }
 }
@@ -94,6 +136,11 @@ pub fn validate_state_transition(
     println!("Signatures: {} provided", update.signatures.len());
     println!("Affected participants: {:?}", update.affected_participants);
 
+    // Only active channels take updates; closing and disputed balances are frozen
+    if channel.status != ChannelStatus::Active {
+        return Err(ChannelError::ChannelNotActive(channel.status));
+    }
+
     // Validate sequence number - must be exactly one more than current
     if update.sequence_number != channel.sequence_number + 1 {
         println!("Invalid sequence number: expected {}, got {}", 
@@ -117,7 +164,7 @@ pub fn validate_state_transition(
 
     // Get a sorted list of affected participants for consistent ordering
     let mut sorted_affected = update.affected_participants.clone();
-    sorted_affected.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
+    sorted_affected.sort_by_key(|a| a.as_bytes());
 
     // Verify affected_participants list is properly sorted
     if sorted_affected != update.affected_participants {
@@ -140,13 +187,11 @@ pub fn validate_state_transition(
         }
     }
 
-    // Verify all participants with balance changes are in affected_participants
-    for participant in update.balance_changes.keys() {
-        if !update.affected_participants.contains(participant) {
-            println!("Participant with balance change not in affected_participants list: {:?}", participant);
-            return Err(ChannelError::InvalidSignatureCount);
-        }
+    // Signers must be channel participants, and together satisfy the channel's policy
+    if update.affected_participants.iter().any(|p| !channel.participants.contains(p)) {
+        return Err(ChannelError::UnknownParticipant);
     }
+    channel.signature_policy.check(&update.balance_changes, &update.affected_participants)?;
 
     // Validate that all affected participants have provided signatures
     if update.signatures.len() != update.affected_participants.len() {
@@ -155,17 +200,97 @@ pub fn validate_state_transition(
         return Err(ChannelError::InvalidSignatureCount);
     }
 
-    // Create message for signature verification
-    let mut message_for_signing = StateUpdateForSigning::new(
-        update.sequence_number,
-        channel.channel_id,
-        &update.balance_changes,
-        &update.affected_participants, // Use the original order of affected participants
-    );
-    message_for_signing.timestamp = update.timestamp;
-    
-    // Verify signatures using the helper function
-    message_for_signing.verify_signatures(&update.signatures)?;
+    // Create message for signature verification and verify using the helper function
+    StateUpdateForSigning::for_update(channel, update).verify_signatures(&update.signatures)?;
+
+    // Every commitment the update replaces must be revoked
+    channel.check_revocations(&update.revocations)?;
 
     Ok(())
 }
+
+/// Verify the signatures of many updates to `channel` in parallel
+///
+/// Only signatures are checked, not sequence numbers or balances, so the
+/// updates can be verified before they are applied in order, e.g. when
+/// replaying a channel's history. Updates are split across the available
+/// CPUs and each thread verifies all signatures of its share in one batch,
+/// falling back to per-update checks if that batch fails.
+///
+/// Returns one result per update, in order.
+pub fn verify_update_signatures(channel: &ChannelState, updates: &[StateUpdate]) -> Vec<Result<(), ChannelError>> {
+    if updates.is_empty() {
+        return Vec::new();
+    }
+    let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = updates.len().div_ceil(threads);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = updates
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || verify_chunk(channel, chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("signature verification thread panicked"))
+            .collect()
+    })
+}
+
+/// Batch-verify the signatures of `updates` together
+fn verify_chunk(channel: &ChannelState, updates: &[StateUpdate]) -> Vec<Result<(), ChannelError>> {
+    let prepared: Vec<_> = updates
+        .iter()
+        .map(|update| prepare_update(channel, update))
+        .collect();
+
+    let mut messages = Vec::new();
+    let mut signatures = Vec::new();
+    let mut public_keys = Vec::new();
+    for (prepared, update) in prepared.iter().zip(updates) {
+        if let Ok((message, participants)) = prepared {
+            messages.extend(std::iter::repeat_n(message.as_slice(), participants.len()));
+            signatures.extend_from_slice(&update.signatures);
+            public_keys.extend_from_slice(participants);
+        }
+    }
+    let all_valid = prepared.iter().all(Result::is_ok)
+        && batch::verify_batch(&messages, &signatures, &public_keys).is_ok();
+
+    prepared
+        .into_iter()
+        .zip(updates)
+        .map(|(prepared, update)| {
+            let (message, participants) = prepared?;
+            if all_valid {
+                return Ok(());
+            }
+            let messages = vec![message.as_slice(); participants.len()];
+            batch::verify_batch(&messages, &update.signatures, &participants)
+                .map_err(ChannelError::from)
+        })
+        .collect()
+}
+
+/// Signed message and signers, in signature order, of an update
+///
+/// Signatures are paired with the sorted signer list, so an update whose
+/// `affected_participants` aren't sorted is rejected like in
+/// [`validate_state_transition`].
+fn prepare_update(
+    channel: &ChannelState,
+    update: &StateUpdate,
+) -> Result<(Vec<u8>, Vec<crypto::PublicKey>), ChannelError> {
+    if update.affected_participants.iter().any(|p| !channel.participants.contains(p)) {
+        return Err(ChannelError::UnknownParticipant);
+    }
+    if !update.affected_participants.is_sorted_by_key(|p| p.as_bytes()) {
+        return Err(ChannelError::InvalidSignatureCount);
+    }
+    if update.signatures.len() != update.affected_participants.len() {
+        return Err(ChannelError::InvalidSignatureCount);
+    }
+    let message_for_signing = StateUpdateForSigning::for_update(channel, update);
+    let message = bincode::serialize(&message_for_signing).map_err(|_| ChannelError::SerializationError)?;
+    Ok((message, message_for_signing.affected_participants))
+}
@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::state::{AggregateStateUpdate, ChannelState, ChannelStatus, StateUpdate, StatusTransition};
+use super::transitions::{validate_state_transition, ChannelError};
+
+/// Size of a record header: payload length (u32 LE) followed by a 4-byte checksum
+const HEADER_LEN: usize = 8;
+
+#[derive(Error, Debug)]
+pub enum WalError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+    #[error("Update rejected: {0}")]
+    Rejected(#[from] ChannelError),
+    #[error("Update could not be applied: {0}")]
+    ApplyFailed(&'static str),
+    #[error("Update for unknown channel {}", hex::encode(.0))]
+    UnknownChannel([u8; 32]),
+}
+
+/// A single entry in the channel write-ahead log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)] // Records only live while being written or replayed
+pub enum WalRecord {
+    /// Full channel state; replay restarts from the latest snapshot of a channel
+    Snapshot(ChannelState),
+    /// An accepted state update, logged before it was applied
+    Update {
+        channel_id: [u8; 32],
+        update: StateUpdate,
+    },
+    /// An accepted update authorized by an aggregate signature, logged before
+    /// it was applied
+    AggregateUpdate {
+        channel_id: [u8; 32],
+        update: AggregateStateUpdate,
+    },
+    /// A change of a channel's lifecycle status
+    StatusChange {
+        channel_id: [u8; 32],
+        transition: StatusTransition,
+    },
+}
+
+/// Append-only, crash-safe log of channel state updates
+///
+/// Each record is framed as `len (u32 LE) | checksum (4) | bincode payload`,
+/// where the checksum is the first four bytes of SHA-256 over the payload.
+/// Records are fsynced before the corresponding update becomes visible in
+/// memory, so every applied update can be replayed after a crash.
+#[derive(Debug)]
+pub struct ChannelWal {
+    path: PathBuf,
+    file: File,
+}
+
+impl ChannelWal {
+    /// Open (or create) a log at `path`
+    ///
+    /// Any torn or partially written record at the tail, e.g. from a crash in
+    /// the middle of an append, is detected and truncated away.
+    pub fn open(path: &Path) -> Result<Self, WalError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let (_, valid_len) = scan_records(&bytes);
+        if valid_len < bytes.len() {
+            file.set_len(valid_len as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self { path: path.to_path_buf(), file })
+    }
+
+    /// Durably record the full state of a channel
+    pub fn log_snapshot(&mut self, state: &ChannelState) -> Result<(), WalError> {
+        self.append(&WalRecord::Snapshot(state.clone()))
+    }
+
+    /// Validate an update, durably log it, then apply it to `state`
+    ///
+    /// The update is applied to a copy first so that only updates that will
+    /// actually succeed are logged. `state` is left untouched on any error.
+    pub fn apply_update(&mut self, state: &mut ChannelState, update: &StateUpdate) -> Result<(), WalError> {
+        let candidate = accept_update(state, update)?;
+        self.append(&WalRecord::Update {
+            channel_id: state.channel_id,
+            update: update.clone(),
+        })?;
+        *state = candidate;
+        Ok(())
+    }
+
+    /// Validate an aggregate-signed update, durably log it, then apply it to `state`
+    ///
+    /// Like [`ChannelWal::apply_update`], `state` is left untouched on any error.
+    pub fn apply_aggregate_update(&mut self, state: &mut ChannelState, update: &AggregateStateUpdate) -> Result<(), WalError> {
+        let candidate = accept_aggregate_update(state, update)?;
+        self.append(&WalRecord::AggregateUpdate {
+            channel_id: state.channel_id,
+            update: update.clone(),
+        })?;
+        *state = candidate;
+        Ok(())
+    }
+
+    /// Move `state` to `status`, durably logging the transition first
+    ///
+    /// `state` is left untouched if the transition isn't allowed.
+    pub fn transition(&mut self, state: &mut ChannelState, status: ChannelStatus) -> Result<(), WalError> {
+        let mut candidate = state.clone();
+        candidate.transition_to(status)?;
+        let transition = candidate.status_history.last().cloned()
+            .expect("a successful transition is recorded");
+        self.append(&WalRecord::StatusChange {
+            channel_id: state.channel_id,
+            transition,
+        })?;
+        *state = candidate;
+        Ok(())
+    }
+
+    /// Rebuild every channel by replaying the log
+    ///
+    /// Each channel starts from its most recent snapshot and has the updates
+    /// and status changes logged after it re-validated and re-applied in order.
+    pub fn recover(&self) -> Result<HashMap<[u8; 32], ChannelState>, WalError> {
+        let bytes = fs::read(&self.path)?;
+        let (records, _) = scan_records(&bytes);
+
+        let mut channels = HashMap::new();
+        for record in records {
+            match record? {
+                WalRecord::Snapshot(state) => {
+                    channels.insert(state.channel_id, state);
+                }
+                WalRecord::Update { channel_id, update } => {
+                    let state = channels.get_mut(&channel_id)
+                        .ok_or(WalError::UnknownChannel(channel_id))?;
+                    *state = accept_update(state, &update)?;
+                }
+                WalRecord::AggregateUpdate { channel_id, update } => {
+                    let state = channels.get_mut(&channel_id)
+                        .ok_or(WalError::UnknownChannel(channel_id))?;
+                    *state = accept_aggregate_update(state, &update)?;
+                }
+                WalRecord::StatusChange { channel_id, transition } => {
+                    channels.get_mut(&channel_id)
+                        .ok_or(WalError::UnknownChannel(channel_id))?
+                        .record_transition(transition)?;
+                }
+            }
+        }
+
+        Ok(channels)
+    }
+
+    /// Rewrite the log so it holds only a snapshot of each given channel
+    ///
+    /// The new log is written to a temporary file and atomically renamed over
+    /// the old one.
+    pub fn compact<'a>(&mut self, states: impl IntoIterator<Item = &'a ChannelState>) -> Result<(), WalError> {
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp = File::create(&tmp_path)?;
+        for state in states {
+            tmp.write_all(&encode_record(&WalRecord::Snapshot(state.clone()))?)?;
+        }
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        self.file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        Ok(())
+    }
+
+    fn append(&mut self, record: &WalRecord) -> Result<(), WalError> {
+        self.file.write_all(&encode_record(record)?)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Run transition validation and apply the update to a copy of `state`
+fn accept_update(state: &ChannelState, update: &StateUpdate) -> Result<ChannelState, WalError> {
+    validate_state_transition(state, update)?;
+    let mut candidate = state.clone();
+    candidate.apply_update(update).map_err(WalError::ApplyFailed)?;
+    Ok(candidate)
+}
+
+/// Apply an aggregate-signed update to a copy of `state`
+fn accept_aggregate_update(state: &ChannelState, update: &AggregateStateUpdate) -> Result<ChannelState, WalError> {
+    let mut candidate = state.clone();
+    candidate.apply_aggregate_update(update).map_err(WalError::ApplyFailed)?;
+    Ok(candidate)
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(payload);
+    let mut out = [0u8; 4];
+    out.copy_from_slice(&digest[..4]);
+    out
+}
+
+fn encode_record(record: &WalRecord) -> Result<Vec<u8>, WalError> {
+    let payload = bincode::serialize(record).map_err(|e| WalError::Serialization(e.to_string()))?;
+    let len = u32::try_from(payload.len())
+        .map_err(|_| WalError::Serialization("record too large".to_string()))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload));
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Split a log into records, stopping at the first torn or corrupt frame
+///
+/// Returns the decoded records and the length of the valid prefix.
+fn scan_records(bytes: &[u8]) -> (Vec<Result<WalRecord, WalError>>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while bytes.len() - offset >= HEADER_LEN {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&bytes[offset..offset + 4]);
+        let len = u32::from_le_bytes(len) as usize;
+
+        let start = offset + HEADER_LEN;
+        let Some(end) = start.checked_add(len).filter(|end| *end <= bytes.len()) else {
+            break;
+        };
+        let payload = &bytes[start..end];
+        if checksum(payload) != bytes[offset + 4..start] {
+            break;
+        }
+
+        records.push(bincode::deserialize(payload).map_err(|e| WalError::Serialization(e.to_string())));
+        offset = end;
+    }
+
+    (records, offset)
+}
@@ -0,0 +1,105 @@
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{IsIdentity, VartimeMultiscalarMul};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha512};
+use thiserror::Error;
+
+use super::{PublicKey, Signature};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    #[error("Got {messages} messages, {signatures} signatures and {public_keys} public keys")]
+    LengthMismatch {
+        messages: usize,
+        signatures: usize,
+        public_keys: usize,
+    },
+    #[error("Signature {0} failed verification")]
+    InvalidSignature(usize),
+}
+
+/// Verify many Ed25519 signatures at once
+///
+/// Checks a random linear combination of all verification equations with one
+/// multiscalar multiplication, which is roughly twice as fast as verifying
+/// each signature. If the batch fails, every signature is verified on its
+/// own with [`PublicKey::verify_signature`] to find the first invalid one.
+///
+/// The batch equation is the cofactored one, so a batch may accept
+/// signatures with small-order components that the single-signature check
+/// rejects. Such signatures can only be made by the owner of the key.
+///
+/// # Errors
+/// * `LengthMismatch` if the inputs don't have the same length
+/// * `InvalidSignature` with the index of the first invalid signature
+pub fn verify_batch(
+    messages: &[&[u8]],
+    signatures: &[Signature],
+    public_keys: &[PublicKey],
+) -> Result<(), BatchError> {
+    if messages.len() != signatures.len() || signatures.len() != public_keys.len() {
+        return Err(BatchError::LengthMismatch {
+            messages: messages.len(),
+            signatures: signatures.len(),
+            public_keys: public_keys.len(),
+        });
+    }
+
+    if signatures.len() > 1 && batch_equation_holds(messages, signatures, public_keys) {
+        return Ok(());
+    }
+
+    match (0..signatures.len()).find(|&i| !public_keys[i].verify_signature(&signatures[i], messages[i])) {
+        Some(index) => Err(BatchError::InvalidSignature(index)),
+        None => Ok(()),
+    }
+}
+
+/// `[8](-(Σ zᵢsᵢ)B + Σ zᵢRᵢ + Σ zᵢkᵢAᵢ) == 0` for random 128-bit `zᵢ`
+///
+/// Returns false if any `R`, `A` or `s` can't be decoded, leaving it to the
+/// single-signature fallback to reject.
+fn batch_equation_holds(messages: &[&[u8]], signatures: &[Signature], public_keys: &[PublicKey]) -> bool {
+    let n = signatures.len();
+    let mut scalars = Vec::with_capacity(2 * n + 1);
+    let mut points = Vec::with_capacity(2 * n + 1);
+    let mut base_scalar = Scalar::ZERO;
+
+    for ((message, signature), public_key) in messages.iter().zip(signatures).zip(public_keys) {
+        let r_bytes = signature.0.r_bytes();
+        let a_bytes = public_key.as_bytes();
+        let s = Option::<Scalar>::from(Scalar::from_canonical_bytes(*signature.0.s_bytes()));
+        let r = CompressedEdwardsY(*r_bytes).decompress();
+        let a = CompressedEdwardsY(a_bytes).decompress();
+        let (Some(s), Some(r), Some(a)) = (s, r, a) else {
+            return false;
+        };
+
+        let k = Scalar::from_bytes_mod_order_wide(
+            &Sha512::new().chain_update(r_bytes).chain_update(a_bytes).chain_update(message).finalize().into(),
+        );
+        let z = random_coefficient();
+
+        base_scalar -= z * s;
+        scalars.push(z);
+        points.push(r);
+        scalars.push(z * k);
+        points.push(a);
+    }
+
+    scalars.push(base_scalar);
+    points.push(ED25519_BASEPOINT_POINT);
+
+    EdwardsPoint::vartime_multiscalar_mul(scalars, points)
+        .mul_by_cofactor()
+        .is_identity()
+}
+
+fn random_coefficient() -> Scalar {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes[..16]);
+    Scalar::from_bytes_mod_order(bytes)
+}
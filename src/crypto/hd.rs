@@ -0,0 +1,533 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ed25519_dalek::SigningKey;
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::{ecdsa, schnorr, ProjectivePoint, Scalar};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha512;
+use thiserror::Error;
+use zeroize::Zeroize;
+
+use super::{hash160, KeyPair, PublicKey, SchemeKeyPair, SchemePublicKey, SignatureScheme};
+
+/// Indices at or above this are hardened
+pub const HARDENED_OFFSET: u32 = 1 << 31;
+/// Minimum seed length accepted by BIP32
+pub const MIN_SEED_LEN: usize = 16;
+/// Maximum seed length accepted by BIP32
+pub const MAX_SEED_LEN: usize = 64;
+/// Length of seeds produced by [`Seed::generate`]
+pub const DEFAULT_SEED_LEN: usize = 32;
+
+/// First path element of channel key derivation, as used by Lightning nodes
+pub const CHANNEL_KEY_PURPOSE: u32 = 1017;
+
+type HmacSha512 = Hmac<Sha512>;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HdError {
+    #[error("Seed must be between 16 and 64 bytes, got {0}")]
+    InvalidSeedLength(usize),
+    #[error("Invalid derivation path: {0}")]
+    InvalidPath(String),
+    #[error("Ed25519 only supports hardened derivation")]
+    NonHardenedEd25519,
+    #[error("Hardened keys can't be derived from a public key")]
+    HardenedFromPublic,
+    #[error("Derived key is invalid; use the next index")]
+    InvalidChildKey,
+    #[error("{0:?} keys can't be derived on this curve")]
+    SchemeMismatch(SignatureScheme),
+}
+
+/// Curve a key tree lives on
+///
+/// secp256k1 trees follow BIP32; Ed25519 trees follow SLIP-10, which only
+/// defines hardened derivation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Curve {
+    Secp256k1,
+    Ed25519,
+}
+
+impl Curve {
+    /// Curve whose keys sign with `scheme`
+    pub fn for_scheme(scheme: SignatureScheme) -> Self {
+        match scheme {
+            SignatureScheme::Ed25519 => Curve::Ed25519,
+            SignatureScheme::EcdsaSecp256k1 | SignatureScheme::SchnorrSecp256k1 => Curve::Secp256k1,
+        }
+    }
+
+    /// HMAC key used to derive the master key from a seed
+    fn seed_key(self) -> &'static [u8] {
+        match self {
+            Curve::Secp256k1 => b"Bitcoin seed",
+            Curve::Ed25519 => b"ed25519 seed",
+        }
+    }
+}
+
+/// Master secret from which every key of a node is derived
+///
+/// This is the one value that needs backing up. Zeroized on drop.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Seed(Vec<u8>);
+
+impl Seed {
+    /// Random 32-byte seed
+    pub fn generate() -> Self {
+        let mut bytes = vec![0u8; DEFAULT_SEED_LEN];
+        OsRng.fill_bytes(&mut bytes);
+        Seed(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, HdError> {
+        if !(MIN_SEED_LEN..=MAX_SEED_LEN).contains(&bytes.len()) {
+            return Err(HdError::InvalidSeedLength(bytes.len()));
+        }
+        Ok(Seed(bytes.to_vec()))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Seed(..)")
+    }
+}
+
+/// One step of a derivation path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChildNumber {
+    Normal(u32),
+    Hardened(u32),
+}
+
+impl ChildNumber {
+    /// Index as used in the derivation, with the hardened bit set if hardened
+    pub fn to_index(self) -> u32 {
+        match self {
+            ChildNumber::Normal(index) => index,
+            ChildNumber::Hardened(index) => index | HARDENED_OFFSET,
+        }
+    }
+
+    pub fn from_index(index: u32) -> Self {
+        if index & HARDENED_OFFSET != 0 {
+            ChildNumber::Hardened(index & !HARDENED_OFFSET)
+        } else {
+            ChildNumber::Normal(index)
+        }
+    }
+
+    pub fn is_hardened(self) -> bool {
+        matches!(self, ChildNumber::Hardened(_))
+    }
+}
+
+impl fmt::Display for ChildNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChildNumber::Normal(index) => write!(f, "{index}"),
+            ChildNumber::Hardened(index) => write!(f, "{index}'"),
+        }
+    }
+}
+
+/// Derivation path such as `m/1017'/0'/1'/0/5`
+///
+/// Hardened steps are written with a trailing `'` or `h`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<ChildNumber>);
+
+impl DerivationPath {
+    pub fn master() -> Self {
+        Self::default()
+    }
+
+    /// Path extended by `child`
+    pub fn child(&self, child: ChildNumber) -> Self {
+        let mut path = self.clone();
+        path.0.push(child);
+        path
+    }
+
+    pub fn as_slice(&self) -> &[ChildNumber] {
+        &self.0
+    }
+}
+
+impl From<Vec<ChildNumber>> for DerivationPath {
+    fn from(children: Vec<ChildNumber>) -> Self {
+        DerivationPath(children)
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = HdError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let invalid = || HdError::InvalidPath(path.to_string());
+        let mut parts = path.split('/');
+        if parts.next() != Some("m") {
+            return Err(invalid());
+        }
+
+        parts
+            .map(|part| {
+                let (digits, hardened) = match part.strip_suffix(['\'', 'h']) {
+                    Some(digits) => (digits, true),
+                    None => (part, false),
+                };
+                let index: u32 = digits.parse().map_err(|_| invalid())?;
+                if index >= HARDENED_OFFSET {
+                    return Err(invalid());
+                }
+                Ok(if hardened { ChildNumber::Hardened(index) } else { ChildNumber::Normal(index) })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(DerivationPath)
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("m")?;
+        for child in &self.0 {
+            write!(f, "/{child}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Private key plus chain code, from which child keys are derived
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExtendedPrivateKey {
+    curve: Curve,
+    secret: [u8; 32],
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: ChildNumber,
+}
+
+impl ExtendedPrivateKey {
+    /// Master key of the tree on `curve` for `seed`
+    pub fn from_seed(curve: Curve, seed: &Seed) -> Result<Self, HdError> {
+        let (secret, chain_code) = hmac_sha512(curve.seed_key(), &[seed.as_bytes()]);
+        if curve == Curve::Secp256k1 && parse_scalar(&secret).is_none() {
+            return Err(HdError::InvalidChildKey);
+        }
+        Ok(Self {
+            curve,
+            secret,
+            chain_code,
+            depth: 0,
+            parent_fingerprint: [0; 4],
+            child_number: ChildNumber::Normal(0),
+        })
+    }
+
+    /// Derive the key at `path` below this key
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, HdError> {
+        path.as_slice().iter().try_fold(self.clone(), |key, &child| key.derive_child(child))
+    }
+
+    /// Derive a single child key
+    ///
+    /// # Errors
+    /// * `NonHardenedEd25519` for a normal child of an Ed25519 key
+    /// * `InvalidChildKey` in the (astronomically unlikely) case the derived
+    ///   secp256k1 key is invalid, in which case BIP32 says to skip the index
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, HdError> {
+        let index = child.to_index().to_be_bytes();
+        let (tweak, chain_code) = match (self.curve, child.is_hardened()) {
+            (Curve::Ed25519, false) => return Err(HdError::NonHardenedEd25519),
+            (_, true) => hmac_sha512(&self.chain_code, &[&[0], &self.secret, &index]),
+            (Curve::Secp256k1, false) => hmac_sha512(&self.chain_code, &[&self.compressed_public_key(), &index]),
+        };
+
+        let secret = match self.curve {
+            Curve::Ed25519 => tweak,
+            Curve::Secp256k1 => {
+                let tweak = parse_scalar(&tweak).ok_or(HdError::InvalidChildKey)?;
+                let parent = parse_scalar(&self.secret).ok_or(HdError::InvalidChildKey)?;
+                let child_key = tweak + parent;
+                if bool::from(child_key.is_zero()) {
+                    return Err(HdError::InvalidChildKey);
+                }
+                child_key.to_bytes().into()
+            }
+        };
+
+        Ok(Self {
+            curve: self.curve,
+            secret,
+            chain_code,
+            depth: self.depth.saturating_add(1),
+            parent_fingerprint: self.fingerprint(),
+            child_number: child,
+        })
+    }
+
+    /// Public counterpart, for deriving non-hardened public keys (secp256k1 only)
+    pub fn to_extended_public_key(&self) -> Option<ExtendedPublicKey> {
+        match self.curve {
+            Curve::Secp256k1 => Some(ExtendedPublicKey {
+                point: ProjectivePoint::GENERATOR * parse_scalar(&self.secret)?,
+                chain_code: self.chain_code,
+                depth: self.depth,
+                parent_fingerprint: self.parent_fingerprint,
+                child_number: self.child_number,
+            }),
+            Curve::Ed25519 => None,
+        }
+    }
+
+    /// Key pair for `scheme`, which must match the tree's curve
+    pub fn keypair(&self, scheme: SignatureScheme) -> Result<SchemeKeyPair, HdError> {
+        if Curve::for_scheme(scheme) != self.curve {
+            return Err(HdError::SchemeMismatch(scheme));
+        }
+        Ok(match scheme {
+            SignatureScheme::Ed25519 => {
+                let signing_key = SigningKey::from_bytes(&self.secret);
+                let verifying_key = PublicKey(signing_key.verifying_key());
+                SchemeKeyPair::Ed25519(KeyPair { signing_key, verifying_key })
+            }
+            SignatureScheme::EcdsaSecp256k1 => SchemeKeyPair::Ecdsa(
+                ecdsa::SigningKey::from_bytes(&self.secret.into()).map_err(|_| HdError::InvalidChildKey)?,
+            ),
+            SignatureScheme::SchnorrSecp256k1 => SchemeKeyPair::Schnorr(
+                schnorr::SigningKey::from_bytes(&self.secret).map_err(|_| HdError::InvalidChildKey)?,
+            ),
+        })
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    /// Raw 32-byte private key
+    pub fn secret_bytes(&self) -> &[u8; 32] {
+        &self.secret
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn parent_fingerprint(&self) -> [u8; 4] {
+        self.parent_fingerprint
+    }
+
+    pub fn child_number(&self) -> ChildNumber {
+        self.child_number
+    }
+
+    /// First four bytes of the hash160 of the public key
+    ///
+    /// Ed25519 keys are prefixed with a zero byte, as in SLIP-10.
+    pub fn fingerprint(&self) -> [u8; 4] {
+        let public_key = match self.curve {
+            Curve::Secp256k1 => self.compressed_public_key().to_vec(),
+            Curve::Ed25519 => {
+                let mut bytes = vec![0];
+                bytes.extend_from_slice(SigningKey::from_bytes(&self.secret).verifying_key().as_bytes());
+                bytes
+            }
+        };
+        fingerprint(&public_key)
+    }
+
+    fn compressed_public_key(&self) -> [u8; 33] {
+        let point = ProjectivePoint::GENERATOR * parse_scalar(&self.secret).unwrap_or(Scalar::ZERO);
+        compress(&point)
+    }
+}
+
+impl Drop for ExtendedPrivateKey {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+        self.chain_code.zeroize();
+    }
+}
+
+impl fmt::Debug for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtendedPrivateKey")
+            .field("curve", &self.curve)
+            .field("depth", &self.depth)
+            .field("child_number", &self.child_number)
+            .finish_non_exhaustive()
+    }
+}
+
+/// secp256k1 public key plus chain code, for watch-only derivation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    point: ProjectivePoint,
+    chain_code: [u8; 32],
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: ChildNumber,
+}
+
+impl ExtendedPublicKey {
+    /// Derive a non-hardened child, matching the private derivation
+    pub fn derive_child(&self, child: ChildNumber) -> Result<Self, HdError> {
+        if child.is_hardened() {
+            return Err(HdError::HardenedFromPublic);
+        }
+        let public_key = self.public_key_bytes();
+        let (tweak, chain_code) = hmac_sha512(&self.chain_code, &[&public_key, &child.to_index().to_be_bytes()]);
+        let tweak = parse_scalar(&tweak).ok_or(HdError::InvalidChildKey)?;
+        let point = ProjectivePoint::GENERATOR * tweak + self.point;
+        if point == ProjectivePoint::IDENTITY {
+            return Err(HdError::InvalidChildKey);
+        }
+
+        Ok(Self {
+            point,
+            chain_code,
+            depth: self.depth.saturating_add(1),
+            parent_fingerprint: fingerprint(&public_key),
+            child_number: child,
+        })
+    }
+
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<Self, HdError> {
+        path.as_slice().iter().try_fold(self.clone(), |key, &child| key.derive_child(child))
+    }
+
+    /// Compressed 33-byte public key
+    pub fn public_key_bytes(&self) -> [u8; 33] {
+        compress(&self.point)
+    }
+
+    /// Public key for `scheme` (ECDSA or Schnorr)
+    pub fn public_key(&self, scheme: SignatureScheme) -> Result<SchemePublicKey, HdError> {
+        let bytes = self.public_key_bytes();
+        match scheme {
+            SignatureScheme::EcdsaSecp256k1 => SchemePublicKey::from_bytes(scheme, &bytes),
+            SignatureScheme::SchnorrSecp256k1 => SchemePublicKey::from_bytes(scheme, &bytes[1..]),
+            SignatureScheme::Ed25519 => return Err(HdError::SchemeMismatch(scheme)),
+        }
+        .map_err(|_| HdError::InvalidChildKey)
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+}
+
+/// Purpose of a per-channel key, used as a path element
+///
+/// Numbering follows the key families of Lightning implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyFamily {
+    /// Key in the 2-of-2 funding output
+    Funding = 0,
+    /// Basepoint for revocation keys of the counterparty's commitments
+    RevocationBase = 1,
+    /// Basepoint for the key paying our balance on the counterparty's commitment
+    PaymentBase = 3,
+    /// Basepoint for the key paying our time-delayed balance on our commitment
+    DelayedPaymentBase = 4,
+}
+
+/// Keys of one channel, all derived from the node's seed
+#[derive(Clone)]
+pub struct ChannelKeys {
+    pub channel_index: u32,
+    pub funding: SchemeKeyPair,
+    pub revocation_basepoint: SchemeKeyPair,
+    pub payment_basepoint: SchemeKeyPair,
+    pub delayed_payment_basepoint: SchemeKeyPair,
+}
+
+impl ChannelKeys {
+    /// Derive the keys of channel `channel_index` signing with `scheme`
+    ///
+    /// Keys live at `m/1017'/0'/family'/0/channel_index`. Ed25519 trees only
+    /// support hardened steps, so the last two are hardened there.
+    pub fn derive(seed: &Seed, channel_index: u32, scheme: SignatureScheme) -> Result<Self, HdError> {
+        let curve = Curve::for_scheme(scheme);
+        let master = ExtendedPrivateKey::from_seed(curve, seed)?;
+        let key = |family| {
+            master.derive_path(&Self::path(curve, family, channel_index)?)?.keypair(scheme)
+        };
+
+        Ok(Self {
+            channel_index,
+            funding: key(KeyFamily::Funding)?,
+            revocation_basepoint: key(KeyFamily::RevocationBase)?,
+            payment_basepoint: key(KeyFamily::PaymentBase)?,
+            delayed_payment_basepoint: key(KeyFamily::DelayedPaymentBase)?,
+        })
+    }
+
+    /// Derivation path of a channel key
+    pub fn path(curve: Curve, family: KeyFamily, channel_index: u32) -> Result<DerivationPath, HdError> {
+        if channel_index >= HARDENED_OFFSET {
+            return Err(HdError::InvalidPath(format!("channel index {channel_index} out of range")));
+        }
+        let tail = match curve {
+            Curve::Secp256k1 => [ChildNumber::Normal(0), ChildNumber::Normal(channel_index)],
+            Curve::Ed25519 => [ChildNumber::Hardened(0), ChildNumber::Hardened(channel_index)],
+        };
+        let mut path = vec![
+            ChildNumber::Hardened(CHANNEL_KEY_PURPOSE),
+            ChildNumber::Hardened(0),
+            ChildNumber::Hardened(family as u32),
+        ];
+        path.extend(tail);
+        Ok(DerivationPath(path))
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = HmacSha512::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in data {
+        mac.update(part);
+    }
+    let output = mac.finalize().into_bytes();
+    let (left, right) = output.split_at(32);
+    (left.try_into().unwrap(), right.try_into().unwrap())
+}
+
+/// Parse a non-zero scalar below the curve order
+fn parse_scalar(bytes: &[u8; 32]) -> Option<Scalar> {
+    let scalar: Option<Scalar> = Scalar::from_repr((*bytes).into()).into();
+    scalar.filter(|scalar| !bool::from(scalar.is_zero()))
+}
+
+fn compress(point: &ProjectivePoint) -> [u8; 33] {
+    point.to_affine().to_encoded_point(true).as_bytes().try_into().unwrap()
+}
+
+fn fingerprint(public_key: &[u8]) -> [u8; 4] {
+    hash160(public_key)[..4].try_into().unwrap()
+}
@@ -0,0 +1,289 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ed25519_dalek::SigningKey;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use super::{KeyPair, PublicKey};
+
+/// Version of the key file format
+pub const KEYSTORE_VERSION: u32 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+const KEY_FILE_EXTENSION: &str = "json";
+
+/// Upper bounds on the KDF parameters accepted from a key file, so a crafted
+/// file can't make `load` allocate or spin without limit
+const MAX_KDF_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 64;
+const MAX_KDF_PARALLELISM: u32 = 16;
+
+#[derive(Error, Debug)]
+pub enum KeystoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+    #[error("No key stored for {}", hex::encode(.0))]
+    NotFound([u8; 32]),
+    #[error("Key {} is already stored", hex::encode(.0))]
+    AlreadyExists([u8; 32]),
+    #[error("Wrong password or corrupted key file")]
+    DecryptionFailed,
+    #[error("Unsupported key file version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+    #[error("Key derivation parameters exceed the allowed maximum: {0:?}")]
+    KdfParamsTooHigh(KdfParams),
+    #[error("Verifying key doesn't match the signing key")]
+    KeyMismatch,
+}
+
+/// Argon2id cost parameters used to derive the encryption key from a password
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of passes
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// OWASP's recommended minimum for Argon2id: 19 MiB, 2 passes
+    fn default() -> Self {
+        Self { memory_kib: 19_456, iterations: 2, parallelism: 1 }
+    }
+}
+
+/// Public information about a stored key, available without the password
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub public_key: PublicKey,
+    pub label: String,
+    /// UNIX timestamp of when the key was stored
+    pub created_at: u64,
+}
+
+/// On-disk format of a single key
+///
+/// The secret key is encrypted with XChaCha20-Poly1305 under a key derived
+/// from the password with Argon2id. The public key is bound as associated
+/// data, so a ciphertext can't be moved to another key's file. The label is
+/// not authenticated, so it can be changed without the password.
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    #[serde(with = "hex")]
+    public_key: Vec<u8>,
+    label: String,
+    created_at: u64,
+    kdf: KdfParams,
+    #[serde(with = "hex")]
+    salt: Vec<u8>,
+    #[serde(with = "hex")]
+    nonce: Vec<u8>,
+    #[serde(with = "hex")]
+    ciphertext: Vec<u8>,
+}
+
+/// Directory of password-encrypted signing keys, one file per key
+///
+/// Keys are looked up by public key. Secret material only exists in memory
+/// while a key is being stored or loaded and is zeroized afterwards; the
+/// returned [`KeyPair`] zeroizes its signing key when dropped.
+#[derive(Debug)]
+pub struct Keystore {
+    dir: PathBuf,
+    kdf: KdfParams,
+}
+
+impl Keystore {
+    /// Open (or create) a keystore in `dir`
+    pub fn open(dir: &Path) -> Result<Self, KeystoreError> {
+        fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf(), kdf: KdfParams::default() })
+    }
+
+    /// Use `kdf` for keys stored from now on; existing keys keep their own
+    pub fn with_kdf_params(mut self, kdf: KdfParams) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Encrypt and store `keypair` under `label`
+    ///
+    /// # Errors
+    /// * `KeyMismatch` if `keypair.verifying_key` isn't derived from its signing key
+    /// * `AlreadyExists` if the key is already stored
+    pub fn store(&self, keypair: &KeyPair, label: &str, password: &str) -> Result<(), KeystoreError> {
+        if keypair.signing_key.verifying_key() != keypair.verifying_key.0 {
+            return Err(KeystoreError::KeyMismatch);
+        }
+        let public_key = keypair.public_key().as_bytes();
+        let path = self.key_path(&public_key);
+        if path.exists() {
+            return Err(KeystoreError::AlreadyExists(public_key));
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+
+        let key = derive_key(password, &salt, &self.kdf)?;
+        let secret = Zeroizing::new(keypair.signing_key.to_bytes());
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: secret.as_ref(), aad: &public_key })
+            .expect("encrypting a 32-byte key can't exceed the AEAD length limit");
+
+        let file = KeyFile {
+            version: KEYSTORE_VERSION,
+            public_key: public_key.to_vec(),
+            label: label.to_string(),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+            kdf: self.kdf,
+            salt: salt.to_vec(),
+            nonce: nonce.to_vec(),
+            ciphertext,
+        };
+        self.write(&path, &file)
+    }
+
+    /// Decrypt the key pair for `public_key`
+    ///
+    /// # Errors
+    /// * `NotFound` if no such key is stored
+    /// * `KdfParamsTooHigh` if the key file asks for an excessive KDF cost
+    /// * `DecryptionFailed` if the password is wrong or the file was tampered with
+    pub fn load(&self, public_key: &PublicKey, password: &str) -> Result<KeyPair, KeystoreError> {
+        let file = self.read(&public_key.as_bytes())?;
+        let key = derive_key(password, &file.salt, &file.kdf)?;
+        if file.nonce.len() != NONCE_LEN {
+            return Err(KeystoreError::DecryptionFailed);
+        }
+
+        let secret = Zeroizing::new(
+            XChaCha20Poly1305::new(key.as_ref().into())
+                .decrypt(XNonce::from_slice(&file.nonce), Payload { msg: &file.ciphertext, aad: &file.public_key })
+                .map_err(|_| KeystoreError::DecryptionFailed)?,
+        );
+        let secret: &[u8; KEY_LEN] = secret.as_slice().try_into()
+            .map_err(|_| KeystoreError::DecryptionFailed)?;
+
+        let signing_key = SigningKey::from_bytes(secret);
+        let verifying_key = PublicKey(signing_key.verifying_key());
+        if verifying_key != *public_key {
+            return Err(KeystoreError::DecryptionFailed);
+        }
+        Ok(KeyPair { signing_key, verifying_key })
+    }
+
+    /// All stored keys, ordered by public key
+    pub fn list(&self) -> Result<Vec<KeyInfo>, KeystoreError> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == KEY_FILE_EXTENSION) {
+                keys.push(Self::info(&Self::parse(&path)?)?);
+            }
+        }
+        keys.sort_by_key(|info| info.public_key.as_bytes());
+        Ok(keys)
+    }
+
+    /// Public information of a single key
+    pub fn get(&self, public_key: &PublicKey) -> Result<KeyInfo, KeystoreError> {
+        Self::info(&self.read(&public_key.as_bytes())?)
+    }
+
+    pub fn contains(&self, public_key: &PublicKey) -> bool {
+        self.key_path(&public_key.as_bytes()).exists()
+    }
+
+    /// Change the label of a stored key
+    pub fn set_label(&self, public_key: &PublicKey, label: &str) -> Result<(), KeystoreError> {
+        let mut file = self.read(&public_key.as_bytes())?;
+        file.label = label.to_string();
+        self.write(&self.key_path(&public_key.as_bytes()), &file)
+    }
+
+    /// Delete a stored key
+    pub fn remove(&self, public_key: &PublicKey) -> Result<(), KeystoreError> {
+        let bytes = public_key.as_bytes();
+        let path = self.key_path(&bytes);
+        if !path.exists() {
+            return Err(KeystoreError::NotFound(bytes));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    fn key_path(&self, public_key: &[u8; 32]) -> PathBuf {
+        self.dir.join(format!("{}.{KEY_FILE_EXTENSION}", hex::encode(public_key)))
+    }
+
+    fn read(&self, public_key: &[u8; 32]) -> Result<KeyFile, KeystoreError> {
+        let path = self.key_path(public_key);
+        if !path.exists() {
+            return Err(KeystoreError::NotFound(*public_key));
+        }
+        Self::parse(&path)
+    }
+
+    fn parse(path: &Path) -> Result<KeyFile, KeystoreError> {
+        let file: KeyFile = serde_json::from_slice(&fs::read(path)?)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        if file.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(file.version));
+        }
+        Ok(file)
+    }
+
+    fn info(file: &KeyFile) -> Result<KeyInfo, KeystoreError> {
+        let public_key = PublicKey::from_bytes(&file.public_key)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        Ok(KeyInfo { public_key, label: file.label.clone(), created_at: file.created_at })
+    }
+
+    /// Write via a temporary file and rename, so a crash never leaves a torn key file
+    fn write(&self, path: &Path, file: &KeyFile) -> Result<(), KeystoreError> {
+        let json = serde_json::to_vec_pretty(file)
+            .map_err(|e| KeystoreError::Serialization(e.to_string()))?;
+        let tmp_path = path.with_extension("tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(&json)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+fn derive_key(password: &str, salt: &[u8], kdf: &KdfParams) -> Result<Zeroizing<[u8; KEY_LEN]>, KeystoreError> {
+    if kdf.memory_kib > MAX_KDF_MEMORY_KIB
+        || kdf.iterations > MAX_KDF_ITERATIONS
+        || kdf.parallelism > MAX_KDF_PARALLELISM
+    {
+        return Err(KeystoreError::KdfParamsTooHigh(*kdf));
+    }
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(KEY_LEN))
+        .map_err(|e| KeystoreError::Kdf(e.to_string()))?;
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, key.as_mut())
+        .map_err(|e| KeystoreError::Kdf(e.to_string()))?;
+    Ok(key)
+}
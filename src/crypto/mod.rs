@@ -1,9 +1,22 @@
-use ed25519_dalek::{SigningKey, VerifyingKey, Signature as EdSignature, Verifier};
+use ed25519_dalek::{SigningKey, VerifyingKey, Signature as EdSignature, Signer, Verifier};
 use rand::rngs::OsRng;
 use rand::RngCore;
+use ripemd::Ripemd160;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+pub mod batch;
+pub mod hd;
+pub mod keystore;
+pub mod musig;
+pub mod scheme;
+
+pub use scheme::{SchemeKeyPair, SchemePublicKey, SchemeSignature, SignatureScheme};
+
+/// Length of a public key hash (RIPEMD-160 of SHA-256)
+pub const PUBLIC_KEY_HASH_LEN: usize = 20;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PublicKey(pub VerifyingKey);
 
@@ -15,11 +28,40 @@ impl PublicKey {
     pub fn verify_signature(&self, signature: &Signature, message: &[u8]) -> bool {
         self.0.verify(message, &signature.0).is_ok()
     }
+
+    /// Verify rejecting malleable signatures and weak keys
+    pub fn verify_strict(&self, signature: &Signature, message: &[u8]) -> bool {
+        self.0.verify_strict(message, &signature.0).is_ok()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| CryptoError::KeyParseError)?;
+        VerifyingKey::from_bytes(&bytes)
+            .map(PublicKey)
+            .map_err(|_| CryptoError::KeyParseError)
+    }
+
+    /// Hash committed to by outputs locked to this key
+    pub fn public_key_hash(&self) -> [u8; PUBLIC_KEY_HASH_LEN] {
+        hash160(&self.as_bytes())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Signature(pub EdSignature);
 
+impl Signature {
+    pub fn to_bytes(&self) -> [u8; 64] {
+        self.0.to_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        EdSignature::from_slice(bytes)
+            .map(Signature)
+            .map_err(|_| CryptoError::InvalidSignature)
+    }
+}
+
 impl Serialize for PublicKey {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -73,6 +115,8 @@ pub enum CryptoError {
     KeyParseError,
     #[error("Signature verification failed")]
     VerificationFailed,
+    #[error("Batch verification failed: {0}")]
+    Batch(#[from] batch::BatchError),
 }
 
 #[derive(Clone)]
@@ -85,6 +129,16 @@ impl KeyPair {
     pub fn public_key(&self) -> PublicKey {
         self.verifying_key.clone()
     }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        Signature(self.signing_key.sign(message))
+    }
+}
+
+/// RIPEMD-160 of SHA-256, as used for public key hashes
+pub fn hash160(data: &[u8]) -> [u8; PUBLIC_KEY_HASH_LEN] {
+    let sha = Sha256::digest(data);
+    Ripemd160::digest(sha).into()
 }
 
 pub fn generate_keypair() -> KeyPair {
@@ -108,10 +162,8 @@ pub fn verify_multisig(
         return Err(CryptoError::VerificationFailed);
     }
 
-    for (pk, sig) in participants.iter().zip(signatures) {
-        pk.0.verify(message, &sig.0)
-            .map_err(|_| CryptoError::VerificationFailed)?;
-    }
+    let messages = vec![message; signatures.len()];
+    batch::verify_batch(&messages, signatures, participants)?;
     
     Ok(())
 }
@@ -125,39 +177,21 @@ pub fn verify_partial_multisig(
     affected_participants: &[PublicKey],
     message: &[u8],
 ) -> Result<(), CryptoError> {
-    println!("Verifying {} signatures for {} affected participants", 
-        signatures.len(), affected_participants.len());
-
     // Verify we have the right number of signatures
     if signatures.len() != affected_participants.len() {
-        println!("Signature count mismatch: {} signatures != {} affected participants",
-            signatures.len(), affected_participants.len());
         return Err(CryptoError::VerificationFailed);
     }
 
     // Verify that all affected participants are in the main participants list
     for affected in affected_participants {
         if !all_participants.contains(affected) {
-            println!("Affected participant not found in participants list");
             return Err(CryptoError::VerificationFailed);
         }
     }
 
-    // Create pairs of signatures and affected participants
-    let mut sig_pairs: Vec<_> = signatures.iter()
-        .zip(affected_participants.iter())
-        .collect();
-
-    // Sort pairs by public key bytes for consistent ordering
-    sig_pairs.sort_by(|(_, pk1), (_, pk2)| pk1.0.to_bytes().cmp(&pk2.0.to_bytes()));
-
-    // Verify each signature against its corresponding participant
-    for (sig, pk) in sig_pairs {
-        if pk.0.verify(message, &sig.0).is_err() {
-            println!("Signature verification failed for participant");
-            return Err(CryptoError::VerificationFailed);
-        }
-    }
+    // Verify each signature against its corresponding participant, all in one batch
+    let messages = vec![message; signatures.len()];
+    batch::verify_batch(&messages, signatures, affected_participants)?;
 
     Ok(())
 }
@@ -176,12 +210,12 @@ mod tests {
         let sig1 = Signature(kp1.signing_key.try_sign(msg).unwrap());
         let sig2 = Signature(kp2.signing_key.try_sign(msg).unwrap());
 
-        let participants = vec![
+        let participants = [
             kp1.public_key(),
             kp2.public_key(),
         ];
 
-        let signatures = vec![sig1, sig2];
+        let signatures = [sig1, sig2];
 
         assert!(verify_multisig(&signatures[..], &participants[..], msg).is_ok());
     }
@@ -0,0 +1,369 @@
+use k256::elliptic_curve::group::GroupEncoding;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::point::AffineCoordinates;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::schnorr;
+use k256::{AffinePoint, FieldBytes, ProjectivePoint, PublicKey as Secp256k1PublicKey, Scalar, U256};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use zeroize::Zeroize;
+
+/// Length of an encoded public nonce: two compressed points
+pub const PUBLIC_NONCE_LEN: usize = 66;
+/// Length of an encoded partial signature
+pub const PARTIAL_SIGNATURE_LEN: usize = 32;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MusigError {
+    #[error("A signing session needs at least one signer")]
+    NoSigners,
+    #[error("Aggregate key is the point at infinity")]
+    InvalidAggregateKey,
+    #[error("Key is not one of the session's signers")]
+    UnknownSigner,
+    #[error("Expected {expected} public nonces, got {got}")]
+    NonceCountMismatch { expected: usize, got: usize },
+    #[error("Invalid public nonce encoding")]
+    InvalidNonce,
+    #[error("Secret nonce doesn't match the signer's public nonce")]
+    NonceMismatch,
+    #[error("Invalid partial signature encoding")]
+    InvalidPartialSignature,
+    #[error("Expected {expected} partial signatures, got {got}")]
+    PartialSignatureCountMismatch { expected: usize, got: usize },
+    #[error("Partial signature of signer {0} is invalid")]
+    PartialSignatureVerificationFailed(usize),
+}
+
+/// Aggregated public key of a fixed set of signers
+///
+/// Keys are sorted by their encoding, so every signer derives the same
+/// aggregate key and signer indices regardless of the order they were given
+/// in. Each key is weighted by a hash of the whole key set, which prevents
+/// rogue-key attacks. The aggregate key is an ordinary BIP340 key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    public_keys: Vec<schnorr::VerifyingKey>,
+    coefficients: Vec<Scalar>,
+    aggregate: AffinePoint,
+    /// Whether the sum of the weighted keys had an odd Y coordinate and was
+    /// negated to get the x-only aggregate key
+    negated: bool,
+}
+
+impl KeyAggContext {
+    pub fn new(public_keys: &[schnorr::VerifyingKey]) -> Result<Self, MusigError> {
+        if public_keys.is_empty() {
+            return Err(MusigError::NoSigners);
+        }
+        let mut public_keys = public_keys.to_vec();
+        public_keys.sort_by_key(|key| key.to_bytes());
+
+        let mut list_hash = tagged_hash(b"KeyAgg list");
+        for key in &public_keys {
+            list_hash.update(key.to_bytes());
+        }
+        let list_hash = list_hash.finalize();
+
+        // The second distinct key gets coefficient 1, saving a multiplication
+        let second = public_keys.iter().find(|key| **key != public_keys[0]).cloned();
+        let coefficients: Vec<Scalar> = public_keys
+            .iter()
+            .map(|key| {
+                if Some(key) == second.as_ref() {
+                    Scalar::ONE
+                } else {
+                    hash_to_scalar(tagged_hash(b"KeyAgg coefficient").chain_update(list_hash).chain_update(key.to_bytes()))
+                }
+            })
+            .collect();
+
+        let sum = public_keys
+            .iter()
+            .zip(&coefficients)
+            .fold(ProjectivePoint::IDENTITY, |sum, (key, coefficient)| {
+                sum + ProjectivePoint::from(*key.as_affine()) * coefficient
+            });
+        if sum == ProjectivePoint::IDENTITY {
+            return Err(MusigError::InvalidAggregateKey);
+        }
+        let sum = sum.to_affine();
+        let negated = bool::from(sum.y_is_odd());
+        let aggregate = if negated { -sum } else { sum };
+
+        Ok(Self { public_keys, coefficients, aggregate, negated })
+    }
+
+    /// The x-only aggregate key final signatures verify against
+    pub fn aggregate_key(&self) -> schnorr::VerifyingKey {
+        schnorr::VerifyingKey::try_from(Secp256k1PublicKey::from_affine(self.aggregate).expect("aggregate key isn't infinity"))
+            .expect("aggregate key has an even Y coordinate")
+    }
+
+    /// Signer keys, in signer index order
+    pub fn public_keys(&self) -> &[schnorr::VerifyingKey] {
+        &self.public_keys
+    }
+
+    /// Index of `public_key` among the signers
+    pub fn signer_index(&self, public_key: &schnorr::VerifyingKey) -> Option<usize> {
+        self.public_keys.iter().position(|key| key == public_key)
+    }
+}
+
+/// First-round message of a signer: commitments to two secret nonces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicNonce {
+    r1: AffinePoint,
+    r2: AffinePoint,
+}
+
+impl PublicNonce {
+    pub fn to_bytes(&self) -> [u8; PUBLIC_NONCE_LEN] {
+        let mut bytes = [0u8; PUBLIC_NONCE_LEN];
+        bytes[..33].copy_from_slice(self.r1.to_encoded_point(true).as_bytes());
+        bytes[33..].copy_from_slice(self.r2.to_encoded_point(true).as_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MusigError> {
+        if bytes.len() != PUBLIC_NONCE_LEN {
+            return Err(MusigError::InvalidNonce);
+        }
+        let point = |bytes: &[u8]| {
+            Secp256k1PublicKey::from_sec1_bytes(bytes)
+                .map(|key| *key.as_affine())
+                .map_err(|_| MusigError::InvalidNonce)
+        };
+        Ok(Self { r1: point(&bytes[..33])?, r2: point(&bytes[33..])? })
+    }
+}
+
+/// Secret nonces of one signing session
+///
+/// Deliberately neither `Clone` nor serializable: signing consumes it, and
+/// reusing a nonce for two different sessions leaks the secret key. The
+/// nonces are zeroized on drop.
+pub struct SecretNonce {
+    k1: Scalar,
+    k2: Scalar,
+    public_nonce: PublicNonce,
+}
+
+impl SecretNonce {
+    /// Generate fresh nonces for signing `message` with `signing_key`
+    ///
+    /// Besides fresh randomness, the nonces commit to the key, the aggregate
+    /// key and the message, so a weak RNG alone doesn't lead to nonce reuse.
+    pub fn generate(signing_key: &schnorr::SigningKey, key_agg: &KeyAggContext, message: &[u8]) -> Self {
+        let mut rand = [0u8; 32];
+        OsRng.fill_bytes(&mut rand);
+        let mut secret = signing_key.to_bytes();
+
+        let [k1, k2] = [0u8, 1u8].map(|i| {
+            hash_to_scalar(
+                tagged_hash(b"MuSig/nonce")
+                    .chain_update(rand)
+                    .chain_update(secret)
+                    .chain_update(key_agg.aggregate_key().to_bytes())
+                    .chain_update((message.len() as u64).to_be_bytes())
+                    .chain_update(message)
+                    .chain_update([i]),
+            )
+        });
+        rand.zeroize();
+        secret.zeroize();
+
+        let public_nonce = PublicNonce {
+            r1: (ProjectivePoint::GENERATOR * k1).to_affine(),
+            r2: (ProjectivePoint::GENERATOR * k2).to_affine(),
+        };
+        Self { k1, k2, public_nonce }
+    }
+
+    pub fn public_nonce(&self) -> PublicNonce {
+        self.public_nonce
+    }
+}
+
+impl Drop for SecretNonce {
+    fn drop(&mut self) {
+        self.k1.zeroize();
+        self.k2.zeroize();
+    }
+}
+
+/// Second-round message of a signer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSignature(Scalar);
+
+impl PartialSignature {
+    pub fn to_bytes(&self) -> [u8; PARTIAL_SIGNATURE_LEN] {
+        self.0.to_bytes().into()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MusigError> {
+        let bytes: [u8; PARTIAL_SIGNATURE_LEN] = bytes.try_into().map_err(|_| MusigError::InvalidPartialSignature)?;
+        Option::from(Scalar::from_repr(bytes.into()))
+            .map(PartialSignature)
+            .ok_or(MusigError::InvalidPartialSignature)
+    }
+}
+
+/// MuSig2 signing session for one message, once all public nonces are known
+///
+/// The protocol has two rounds:
+/// 1. Every signer generates a [`SecretNonce`] and shares its [`PublicNonce`].
+/// 2. With all public nonces, every signer opens a session, signs with its
+///    secret nonce and shares the [`PartialSignature`].
+///
+/// Any party can then [`aggregate`](Self::aggregate) the partial signatures
+/// into one BIP340 signature valid under [`KeyAggContext::aggregate_key`].
+#[derive(Debug, Clone)]
+pub struct SigningSession {
+    key_agg: KeyAggContext,
+    public_nonces: Vec<PublicNonce>,
+    /// Nonce coefficient `b`
+    nonce_coefficient: Scalar,
+    /// Final nonce `R`, with an even Y coordinate
+    nonce: AffinePoint,
+    /// Whether `R1 + b*R2` was negated to get `R`
+    nonce_negated: bool,
+    /// BIP340 challenge `e`
+    challenge: Scalar,
+}
+
+impl SigningSession {
+    /// Open a session; `public_nonces` are in signer index order
+    pub fn new(key_agg: &KeyAggContext, public_nonces: &[PublicNonce], message: &[u8]) -> Result<Self, MusigError> {
+        if public_nonces.len() != key_agg.public_keys.len() {
+            return Err(MusigError::NonceCountMismatch {
+                expected: key_agg.public_keys.len(),
+                got: public_nonces.len(),
+            });
+        }
+
+        let (r1, r2) = public_nonces.iter().fold(
+            (ProjectivePoint::IDENTITY, ProjectivePoint::IDENTITY),
+            |(r1, r2), nonce| (r1 + ProjectivePoint::from(nonce.r1), r2 + ProjectivePoint::from(nonce.r2)),
+        );
+        let aggregate_key = key_agg.aggregate_key().to_bytes();
+        let nonce_coefficient = hash_to_scalar(
+            tagged_hash(b"MuSig/noncecoef")
+                .chain_update(r1.to_bytes())
+                .chain_update(r2.to_bytes())
+                .chain_update(aggregate_key)
+                .chain_update(message),
+        );
+
+        // An infinite final nonce can only be the result of a malicious
+        // signer; falling back to the generator keeps the session going
+        let mut nonce = r1 + r2 * nonce_coefficient;
+        if nonce == ProjectivePoint::IDENTITY {
+            nonce = ProjectivePoint::GENERATOR;
+        }
+        let nonce = nonce.to_affine();
+        let nonce_negated = bool::from(nonce.y_is_odd());
+        let nonce = if nonce_negated { -nonce } else { nonce };
+
+        let challenge = hash_to_scalar(
+            tagged_hash(b"BIP0340/challenge")
+                .chain_update(nonce.x())
+                .chain_update(aggregate_key)
+                .chain_update(message),
+        );
+
+        Ok(Self {
+            key_agg: key_agg.clone(),
+            public_nonces: public_nonces.to_vec(),
+            nonce_coefficient,
+            nonce,
+            nonce_negated,
+            challenge,
+        })
+    }
+
+    /// Produce this signer's partial signature, consuming its secret nonce
+    ///
+    /// # Errors
+    /// * `UnknownSigner` if `signing_key` isn't one of the session's signers
+    /// * `NonceMismatch` if `secret_nonce` isn't the one shared for this signer
+    pub fn sign(&self, signing_key: &schnorr::SigningKey, secret_nonce: SecretNonce) -> Result<PartialSignature, MusigError> {
+        let index = self.key_agg.signer_index(signing_key.verifying_key()).ok_or(MusigError::UnknownSigner)?;
+        if self.public_nonces[index] != secret_nonce.public_nonce {
+            return Err(MusigError::NonceMismatch);
+        }
+
+        let (mut k1, mut k2) = (secret_nonce.k1, secret_nonce.k2);
+        if self.nonce_negated {
+            k1 = -k1;
+            k2 = -k2;
+        }
+        let mut secret = **signing_key.as_nonzero_scalar();
+        if self.key_agg.negated {
+            secret = -secret;
+        }
+
+        let s = k1 + self.nonce_coefficient * k2 + self.challenge * self.key_agg.coefficients[index] * secret;
+        k1.zeroize();
+        k2.zeroize();
+        secret.zeroize();
+
+        let partial = PartialSignature(s);
+        debug_assert!(self.verify_partial(index, &partial));
+        Ok(partial)
+    }
+
+    /// Check the partial signature of the signer at `index`
+    pub fn verify_partial(&self, index: usize, partial: &PartialSignature) -> bool {
+        let (Some(key), Some(nonce)) = (self.key_agg.public_keys.get(index), self.public_nonces.get(index)) else {
+            return false;
+        };
+
+        let mut expected_nonce = ProjectivePoint::from(nonce.r1) + ProjectivePoint::from(nonce.r2) * self.nonce_coefficient;
+        if self.nonce_negated {
+            expected_nonce = -expected_nonce;
+        }
+        let mut key = ProjectivePoint::from(*key.as_affine());
+        if self.key_agg.negated {
+            key = -key;
+        }
+
+        ProjectivePoint::GENERATOR * partial.0 == expected_nonce + key * (self.challenge * self.key_agg.coefficients[index])
+    }
+
+    /// Combine all partial signatures, in signer index order, into a BIP340 signature
+    ///
+    /// # Errors
+    /// * `PartialSignatureVerificationFailed` with the index of the first invalid partial signature
+    pub fn aggregate(&self, partials: &[PartialSignature]) -> Result<schnorr::Signature, MusigError> {
+        if partials.len() != self.public_nonces.len() {
+            return Err(MusigError::PartialSignatureCountMismatch {
+                expected: self.public_nonces.len(),
+                got: partials.len(),
+            });
+        }
+        if let Some(index) = (0..partials.len()).find(|&i| !self.verify_partial(i, &partials[i])) {
+            return Err(MusigError::PartialSignatureVerificationFailed(index));
+        }
+
+        let s = partials.iter().fold(Scalar::ZERO, |sum, partial| sum + partial.0);
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.nonce.x());
+        bytes[32..].copy_from_slice(&s.to_bytes());
+        schnorr::Signature::try_from(&bytes[..]).map_err(|_| MusigError::InvalidPartialSignature)
+    }
+}
+
+/// BIP340-style tagged hash: `SHA256(SHA256(tag) || SHA256(tag) || ...)`
+fn tagged_hash(tag: &[u8]) -> Sha256 {
+    let tag_hash = Sha256::digest(tag);
+    Sha256::new().chain_update(tag_hash).chain_update(tag_hash)
+}
+
+fn hash_to_scalar(hash: Sha256) -> Scalar {
+    <Scalar as Reduce<U256>>::reduce_bytes(&FieldBytes::from(hash.finalize()))
+}
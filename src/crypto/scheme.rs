@@ -0,0 +1,260 @@
+use k256::ecdsa;
+use k256::schnorr;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use signature::{Signer, Verifier};
+
+use super::{generate_keypair, hash160, CryptoError, KeyPair, PublicKey, Signature, PUBLIC_KEY_HASH_LEN};
+
+/// Signature algorithm used by a key or channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SignatureScheme {
+    /// Ed25519 over the raw message
+    #[default]
+    Ed25519,
+    /// secp256k1 ECDSA over the SHA-256 of the message, low-S normalized,
+    /// with compressed 33-byte public keys
+    EcdsaSecp256k1,
+    /// BIP340 Schnorr over the raw message, with x-only 32-byte public keys
+    SchnorrSecp256k1,
+}
+
+/// Public key of any supported [`SignatureScheme`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemePublicKey {
+    Ed25519(PublicKey),
+    Ecdsa(ecdsa::VerifyingKey),
+    Schnorr(schnorr::VerifyingKey),
+}
+
+/// Signature of any supported [`SignatureScheme`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemeSignature {
+    Ed25519(Signature),
+    Ecdsa(ecdsa::Signature),
+    Schnorr(schnorr::Signature),
+}
+
+/// Signing key of any supported [`SignatureScheme`]
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)] // Few key pairs are alive at once
+pub enum SchemeKeyPair {
+    Ed25519(KeyPair),
+    Ecdsa(ecdsa::SigningKey),
+    Schnorr(schnorr::SigningKey),
+}
+
+impl SchemePublicKey {
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            SchemePublicKey::Ed25519(_) => SignatureScheme::Ed25519,
+            SchemePublicKey::Ecdsa(_) => SignatureScheme::EcdsaSecp256k1,
+            SchemePublicKey::Schnorr(_) => SignatureScheme::SchnorrSecp256k1,
+        }
+    }
+
+    /// Serialized key: 32 bytes for Ed25519, 33-byte compressed SEC1 for
+    /// ECDSA, 32-byte x-only for Schnorr
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            SchemePublicKey::Ed25519(key) => key.as_bytes().to_vec(),
+            SchemePublicKey::Ecdsa(key) => key.to_encoded_point(true).as_bytes().to_vec(),
+            SchemePublicKey::Schnorr(key) => key.to_bytes().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(scheme: SignatureScheme, bytes: &[u8]) -> Result<Self, CryptoError> {
+        match scheme {
+            SignatureScheme::Ed25519 => PublicKey::from_bytes(bytes).map(SchemePublicKey::Ed25519),
+            SignatureScheme::EcdsaSecp256k1 => ecdsa::VerifyingKey::from_sec1_bytes(bytes)
+                .map(SchemePublicKey::Ecdsa)
+                .map_err(|_| CryptoError::KeyParseError),
+            SignatureScheme::SchnorrSecp256k1 => schnorr::VerifyingKey::from_bytes(bytes)
+                .map(SchemePublicKey::Schnorr)
+                .map_err(|_| CryptoError::KeyParseError),
+        }
+    }
+
+    /// Hash committed to by outputs locked to this key
+    ///
+    /// For ECDSA keys this is the standard Bitcoin P2PKH hash of the
+    /// compressed key.
+    pub fn public_key_hash(&self) -> [u8; PUBLIC_KEY_HASH_LEN] {
+        hash160(&self.to_bytes())
+    }
+
+    /// Verify `signature` over `message`
+    ///
+    /// Fails for signatures of another scheme, and for high-S ECDSA
+    /// signatures, which are malleable.
+    pub fn verify(&self, signature: &SchemeSignature, message: &[u8]) -> bool {
+        match (self, signature) {
+            (SchemePublicKey::Ed25519(key), SchemeSignature::Ed25519(signature)) => {
+                key.verify_strict(signature, message)
+            }
+            (SchemePublicKey::Ecdsa(key), SchemeSignature::Ecdsa(signature)) => {
+                signature.normalize_s().is_none() && key.verify(message, signature).is_ok()
+            }
+            (SchemePublicKey::Schnorr(key), SchemeSignature::Schnorr(signature)) => {
+                key.verify_raw(message, signature).is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<PublicKey> for SchemePublicKey {
+    fn from(key: PublicKey) -> Self {
+        SchemePublicKey::Ed25519(key)
+    }
+}
+
+impl SchemeSignature {
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            SchemeSignature::Ed25519(_) => SignatureScheme::Ed25519,
+            SchemeSignature::Ecdsa(_) => SignatureScheme::EcdsaSecp256k1,
+            SchemeSignature::Schnorr(_) => SignatureScheme::SchnorrSecp256k1,
+        }
+    }
+
+    /// Fixed-size 64-byte encoding (`r || s` for the secp256k1 schemes)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            SchemeSignature::Ed25519(signature) => signature.to_bytes().to_vec(),
+            SchemeSignature::Ecdsa(signature) => signature.to_bytes().to_vec(),
+            SchemeSignature::Schnorr(signature) => signature.to_bytes().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(scheme: SignatureScheme, bytes: &[u8]) -> Result<Self, CryptoError> {
+        match scheme {
+            SignatureScheme::Ed25519 => Signature::from_bytes(bytes).map(SchemeSignature::Ed25519),
+            SignatureScheme::EcdsaSecp256k1 => ecdsa::Signature::from_slice(bytes)
+                .map(SchemeSignature::Ecdsa)
+                .map_err(|_| CryptoError::InvalidSignature),
+            SignatureScheme::SchnorrSecp256k1 => schnorr::Signature::try_from(bytes)
+                .map(SchemeSignature::Schnorr)
+                .map_err(|_| CryptoError::InvalidSignature),
+        }
+    }
+
+    /// DER encoding as used in Bitcoin scripts, ECDSA only
+    pub fn to_der(&self) -> Option<Vec<u8>> {
+        match self {
+            SchemeSignature::Ecdsa(signature) => Some(signature.to_der().as_bytes().to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Parse a DER-encoded ECDSA signature
+    pub fn from_der(bytes: &[u8]) -> Result<Self, CryptoError> {
+        ecdsa::Signature::from_der(bytes)
+            .map(SchemeSignature::Ecdsa)
+            .map_err(|_| CryptoError::InvalidSignature)
+    }
+}
+
+impl From<Signature> for SchemeSignature {
+    fn from(signature: Signature) -> Self {
+        SchemeSignature::Ed25519(signature)
+    }
+}
+
+impl SchemeKeyPair {
+    /// Generate a random key pair for `scheme`
+    pub fn generate(scheme: SignatureScheme) -> Self {
+        match scheme {
+            SignatureScheme::Ed25519 => SchemeKeyPair::Ed25519(generate_keypair()),
+            SignatureScheme::EcdsaSecp256k1 => SchemeKeyPair::Ecdsa(ecdsa::SigningKey::random(&mut OsRng)),
+            SignatureScheme::SchnorrSecp256k1 => SchemeKeyPair::Schnorr(schnorr::SigningKey::random(&mut OsRng)),
+        }
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            SchemeKeyPair::Ed25519(_) => SignatureScheme::Ed25519,
+            SchemeKeyPair::Ecdsa(_) => SignatureScheme::EcdsaSecp256k1,
+            SchemeKeyPair::Schnorr(_) => SignatureScheme::SchnorrSecp256k1,
+        }
+    }
+
+    pub fn public_key(&self) -> SchemePublicKey {
+        match self {
+            SchemeKeyPair::Ed25519(keypair) => SchemePublicKey::Ed25519(keypair.public_key()),
+            SchemeKeyPair::Ecdsa(key) => SchemePublicKey::Ecdsa(*key.verifying_key()),
+            SchemeKeyPair::Schnorr(key) => SchemePublicKey::Schnorr(*key.verifying_key()),
+        }
+    }
+
+    /// Sign `message`; ECDSA signatures are always low-S
+    pub fn sign(&self, message: &[u8]) -> SchemeSignature {
+        match self {
+            SchemeKeyPair::Ed25519(keypair) => SchemeSignature::Ed25519(keypair.sign(message)),
+            SchemeKeyPair::Ecdsa(key) => {
+                let signature: ecdsa::Signature = key.sign(message);
+                SchemeSignature::Ecdsa(signature.normalize_s().unwrap_or(signature))
+            }
+            SchemeKeyPair::Schnorr(key) => {
+                let mut aux_rand = [0u8; 32];
+                OsRng.fill_bytes(&mut aux_rand);
+                let signature = key.sign_raw(message, &aux_rand)
+                    .expect("BIP340 signing only fails with negligible probability");
+                SchemeSignature::Schnorr(signature)
+            }
+        }
+    }
+}
+
+impl From<KeyPair> for SchemeKeyPair {
+    fn from(keypair: KeyPair) -> Self {
+        SchemeKeyPair::Ed25519(keypair)
+    }
+}
+
+/// Serialized form of scheme keys and signatures: the scheme plus its bytes
+#[derive(Serialize, Deserialize)]
+struct Tagged {
+    scheme: SignatureScheme,
+    #[serde(with = "hex")]
+    bytes: Vec<u8>,
+}
+
+impl Serialize for SchemePublicKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Tagged { scheme: self.scheme(), bytes: self.to_bytes() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemePublicKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let tagged = Tagged::deserialize(deserializer)?;
+        SchemePublicKey::from_bytes(tagged.scheme, &tagged.bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for SchemeSignature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        Tagged { scheme: self.scheme(), bytes: self.to_bytes() }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemeSignature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let tagged = Tagged::deserialize(deserializer)?;
+        SchemeSignature::from_bytes(tagged.scheme, &tagged.bytes).map_err(serde::de::Error::custom)
+    }
+}
@@ -2,6 +2,12 @@ pub mod utxo {
     pub mod models;
     pub mod store;
     pub mod cache;
+    pub mod snapshot;
+    pub mod selection;
+    pub mod validation;
+    pub mod timelock;
+    pub mod script;
+    pub mod wire;
 }
 
 pub mod channel;
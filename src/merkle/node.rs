@@ -32,7 +32,7 @@ impl MerkleNode {
     pub fn hash_leaf(data: &[u8]) -> H256 {
         let mut hasher = Keccak256::new();
         // Prefix with 0x00 to distinguish from internal nodes
-        hasher.update(&[0x00]);
+        hasher.update([0x00]);
         hasher.update(data);
         H256::from(hasher.finalize().as_ref())
     }
@@ -41,7 +41,7 @@ impl MerkleNode {
     pub fn hash_internal(left: &H256, right: &H256) -> H256 {
         let mut hasher = Keccak256::new();
         // Prefix with 0x01 to distinguish from leaf nodes
-        hasher.update(&[0x01]);
+        hasher.update([0x01]);
         hasher.update(left.as_bytes());
         hasher.update(right.as_bytes());
         H256::from(hasher.finalize().as_ref())
@@ -45,7 +45,7 @@ impl MerkleProof {
             println!("\n  Level {} verification:", i);
             println!("    Current index: {}", current_index);
             println!("    Level size: {}", level_size);
-            let is_left = current_index % 2 == 0;
+            let is_left = current_index.is_multiple_of(2);
             println!("    Is left child: {}", is_left);
             println!("    Current hash: {:#x}", current_hash);
             println!("    Sibling hash: {:#x}", sibling_hash);
@@ -67,7 +67,7 @@ impl MerkleProof {
             println!("    Combined hash: {:#x}", current_hash);
 
             current_index /= 2;
-            level_size = (level_size + 1) / 2;
+            level_size = level_size.div_ceil(2);
         }
 
         println!("\nFinal verification:");
@@ -44,7 +44,7 @@ impl MerkleTree {
 
         let mut level = 0;
         let mut levels = Vec::new();
-        levels.push(nodes.iter().map(|n| n.hash.clone()).collect::<Vec<_>>());
+        levels.push(nodes.iter().map(|n| n.hash).collect::<Vec<_>>());
         while nodes.len() > 1 {
             println!("\nProcessing level {}", level);
             let mut new_nodes = Vec::new();
@@ -60,7 +60,7 @@ impl MerkleTree {
                 }
             }
 
-            levels.push(new_nodes.iter().map(|n| n.hash.clone()).collect::<Vec<_>>());
+            levels.push(new_nodes.iter().map(|n| n.hash).collect::<Vec<_>>());
             nodes = new_nodes;
             level += 1;
         }
@@ -98,10 +98,10 @@ impl MerkleTree {
         let mut level_size = initial_level_size;
 
         while level_size > 1 {
-            println!("  Level size: {}, Index: {}, Is left: {}", level_size, current_index, current_index % 2 == 0);
+            println!("  Level size: {}, Index: {}, Is left: {}", level_size, current_index, current_index.is_multiple_of(2));
             
             // Get the sibling hash
-            let sibling_index = if current_index % 2 == 0 {
+            let sibling_index = if current_index.is_multiple_of(2) {
                 current_index + 1
             } else {
                 current_index - 1
@@ -110,27 +110,27 @@ impl MerkleTree {
             println!("  Collecting proof at level:");
             println!("    Level size: {}", level_size);
             println!("    Current index: {}", current_index);
-            println!("    Is left: {}", current_index % 2 == 0);
+            println!("    Is left: {}", current_index.is_multiple_of(2));
 
             // Get the current and sibling hashes from the correct level
             let current_hash = if current_index < self.levels[current_level].len() {
-                self.levels[current_level][current_index].clone()
+                self.levels[current_level][current_index]
             } else {
                 // If we're beyond the level size, duplicate the last hash
-                self.levels[current_level].last().unwrap().clone()
+                *self.levels[current_level].last().unwrap()
             };
 
             let sibling_hash = if sibling_index < self.levels[current_level].len() {
-                self.levels[current_level][sibling_index].clone()
+                self.levels[current_level][sibling_index]
             } else {
                 // If the sibling is beyond the level size, duplicate the current hash
-                current_hash.clone()
+                current_hash
             };
 
             println!("    Left hash: {:#x}", current_hash);
             println!("    Right hash: {:#x}", sibling_hash);
 
-            if current_index % 2 == 0 {
+            if current_index.is_multiple_of(2) {
                 println!("    Adding right sibling: {:#x}", sibling_hash);
             } else {
                 println!("    Adding left sibling: {:#x}", sibling_hash);
@@ -140,7 +140,7 @@ impl MerkleTree {
 
             current_level += 1;
             current_index /= 2;
-            level_size = (level_size + 1) / 2;
+            level_size = level_size.div_ceil(2);
         }
 
         println!("\nProof summary:");
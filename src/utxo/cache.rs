@@ -1,11 +1,15 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+use std::sync::{Arc, Mutex, RwLock};
 use primitive_types::H256;
 use thiserror::Error;
 
-use super::models::{Transaction, Utxo};
+use super::models::{BlockUndo, Transaction, Utxo};
 use super::store::{SdbStore, StoreError};
 
+/// Default maximum number of cached UTXOs
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
 #[derive(Error, Debug)]
 pub enum CacheError {
     #[error("UTXO not found: {0}")]
@@ -14,57 +18,234 @@ pub enum CacheError {
     StorageError(#[from] StoreError),
     #[error("Lock acquisition failed")]
     LockError,
+    #[error("Input {0:?}:{1} not found or already spent")]
+    MissingInput(H256, u32),
+    #[error("Input {0:?}:{1} spent more than once in the same transaction")]
+    DuplicateInput(H256, u32),
+}
+
+/// When cached changes reach persistent storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WritePolicy {
+    /// Every change is written to storage before the call returns
+    #[default]
+    WriteThrough,
+    /// Outputs added with `add_transaction` are kept in memory as dirty
+    /// entries and written to storage when evicted, on `flush`, or before any
+    /// operation that needs storage to see them
+    WriteBack,
+}
+
+/// Size limits and write policy for a `UtxoCache`
+///
+/// When both limits are set the cache evicts until both are satisfied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Maximum number of cached UTXOs, `None` for no limit
+    pub max_entries: Option<usize>,
+    /// Maximum estimated memory used by cached UTXOs, `None` for no limit
+    pub max_bytes: Option<usize>,
+    /// When changes are persisted
+    pub write_policy: WritePolicy,
 }
 
-/// Thread-safe cache for UTXOs with persistence layer
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: Some(DEFAULT_MAX_ENTRIES),
+            max_bytes: None,
+            write_policy: WritePolicy::WriteThrough,
+        }
+    }
+}
+
+/// Counters for sizing the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Lookups served from memory
+    pub hits: u64,
+    /// Lookups that had to go to storage
+    pub misses: u64,
+    /// Entries dropped to stay within the configured limits
+    pub evictions: u64,
+    /// Dirty entries written to storage, on eviction or flush
+    pub write_backs: u64,
+    /// Entries currently cached
+    pub entries: usize,
+    /// Estimated memory used by the cached entries
+    pub bytes: usize,
+}
+
+struct CacheEntry {
+    utxo: Utxo,
+    /// Not yet written to storage
+    dirty: bool,
+    /// Logical time of the last access, key into `LruMap::recency`
+    last_used: u64,
+}
+
+/// UTXO map with least-recently-used ordering and size accounting
+struct LruMap {
+    entries: HashMap<(H256, u32), CacheEntry>,
+    /// Access time to key, oldest first
+    recency: BTreeMap<u64, (H256, u32)>,
+    clock: u64,
+    bytes: usize,
+    stats: CacheStats,
+}
+
+impl LruMap {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            bytes: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Look up an entry and mark it as most recently used
+    fn get(&mut self, key: &(H256, u32)) -> Option<&Utxo> {
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        self.clock += 1;
+        entry.last_used = self.clock;
+        self.recency.insert(self.clock, *key);
+        Some(&entry.utxo)
+    }
+
+    fn contains_key(&self, key: &(H256, u32)) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Insert or replace an entry as the most recently used
+    ///
+    /// Replacing a dirty entry keeps it dirty so the newer value is still
+    /// written back.
+    fn insert(&mut self, utxo: Utxo, dirty: bool) {
+        let key = (utxo.tx_hash, utxo.output_index);
+        let dirty = dirty || self.remove(&key).is_some_and(|old| old.dirty);
+
+        self.clock += 1;
+        self.bytes += estimated_size(&utxo);
+        self.recency.insert(self.clock, key);
+        self.entries.insert(key, CacheEntry { utxo, dirty, last_used: self.clock });
+    }
+
+    fn remove(&mut self, key: &(H256, u32)) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        self.bytes -= estimated_size(&entry.utxo);
+        Some(entry)
+    }
+
+    /// Evict least recently used entries until within `config`'s limits,
+    /// returning the evicted entries that still need to be written back
+    fn evict(&mut self, config: &CacheConfig) -> Vec<Utxo> {
+        let mut dirty = Vec::new();
+        while self.over_limit(config) {
+            let Some((_, key)) = self.recency.pop_first() else { break };
+            let Some(entry) = self.entries.remove(&key) else { continue };
+            self.bytes -= estimated_size(&entry.utxo);
+            self.stats.evictions += 1;
+            if entry.dirty {
+                dirty.push(entry.utxo);
+            }
+        }
+        dirty
+    }
+
+    fn over_limit(&self, config: &CacheConfig) -> bool {
+        config.max_entries.is_some_and(|max| self.entries.len() > max)
+            || config.max_bytes.is_some_and(|max| self.bytes > max)
+    }
+
+    /// Mark every dirty entry clean, returning copies to be written back
+    fn take_dirty(&mut self) -> Vec<Utxo> {
+        self.entries.values_mut()
+            .filter(|entry| entry.dirty)
+            .map(|entry| {
+                entry.dirty = false;
+                entry.utxo.clone()
+            })
+            .collect()
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats { entries: self.entries.len(), bytes: self.bytes, ..self.stats }
+    }
+}
+
+/// Estimated heap and bookkeeping footprint of one cached UTXO
+fn estimated_size(utxo: &Utxo) -> usize {
+    mem::size_of::<((H256, u32), CacheEntry)>()
+        + mem::size_of::<(u64, (H256, u32))>()
+        + utxo.output.public_key_hash.len()
+        + utxo.output.lock_script.len()
+}
+
+/// Thread-safe, size-bounded LRU cache for UTXOs with persistence layer
+///
+/// Lookups that miss are read through from storage. When the configured
+/// entry or byte limit is exceeded the least recently used entries are
+/// evicted, writing back any that have not been persisted yet.
 pub struct UtxoCache {
     /// In-memory cache of UTXOs
-    cache: Arc<RwLock<HashMap<(H256, u32), Utxo>>>,
+    cache: Arc<Mutex<LruMap>>,
     /// Persistent storage
     store: Arc<RwLock<SdbStore>>,
+    /// Size limits and write policy
+    config: CacheConfig,
 }
 
 impl UtxoCache {
-    /// Create a new UTXO cache with persistent storage
+    /// Create a new UTXO cache with persistent storage and default limits
     pub fn new(store: SdbStore) -> Self {
+        Self::with_config(store, CacheConfig::default())
+    }
+
+    /// Create a new UTXO cache with the given limits and write policy
+    pub fn with_config(store: SdbStore, config: CacheConfig) -> Self {
         Self {
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(Mutex::new(LruMap::new())),
             store: Arc::new(RwLock::new(store)),
+            config,
         }
     }
 
     /// Add UTXOs from a transaction to the cache
+    ///
+    /// With `WritePolicy::WriteBack` the outputs are only persisted once they
+    /// are evicted or flushed.
     pub fn add_transaction(&self, tx: &Transaction, block_height: Option<u32>) -> Result<(), CacheError> {
-        let mut cache = self.cache.write().map_err(|_| CacheError::LockError)?;
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
         let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
-        
-        // Create UTXOs for each output
-        for (idx, output) in tx.outputs.iter().enumerate() {
-            let utxo = if let Some(height) = block_height {
-                Utxo::new(output.clone(), height, idx as u32, tx.hash)
-            } else {
-                Utxo::new_unconfirmed(output.clone(), idx as u32, tx.hash)
-            };
-            
-            // Add to cache
-            cache.insert((tx.hash, idx as u32), utxo.clone());
+
+        let write_back = self.config.write_policy == WritePolicy::WriteBack;
+        if !write_back {
+            // Persist to storage
+            store.add_outputs(tx, block_height)?;
         }
-        
-        // Persist to storage
-        store.add_outputs(tx)?;
-        
-        Ok(())
+
+        // Create UTXOs for each output and add them to the cache
+        for utxo in Utxo::from_transaction(tx, block_height) {
+            cache.insert(utxo, write_back);
+        }
+
+        self.evict(&mut cache, &mut store)
     }
 
     /// Remove spent UTXOs when inputs reference them
     pub fn remove_spent(&self, tx: &Transaction) -> Result<(), CacheError> {
-        let mut cache = self.cache.write().map_err(|_| CacheError::LockError)?;
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
         let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
-        
+        Self::write_back(&mut cache, &mut store)?;
+
         // Check each input's referenced UTXO exists and hasn't been spent
         for input in &tx.inputs {
             let key = (input.previous_output, input.index);
-            
+
             // Check if UTXO exists in cache
             if !cache.contains_key(&key) {
                 // Check persistent storage
@@ -77,53 +258,227 @@ impl UtxoCache {
                 }
             }
         }
-        
+
         // Remove each input's referenced UTXO
         for input in &tx.inputs {
             cache.remove(&(input.previous_output, input.index));
         }
-        
+
         // Update persistent storage
         store.remove_inputs(&tx.inputs)?;
-        
+
+        Ok(())
+    }
+
+    /// Atomically spend a transaction's inputs and add its outputs
+    ///
+    /// Inputs are checked, spent entries removed and new outputs inserted in a
+    /// single sled transaction while holding the cache lock, so a crash or a
+    /// concurrent reader can never observe a half-applied transaction.
+    /// Returns the UTXOs that were spent, in input order.
+    pub fn apply_transaction(&self, tx: &Transaction, block_height: Option<u32>) -> Result<Vec<Utxo>, CacheError> {
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)?;
+
+        let spent = store.apply_transaction(tx, block_height).map_err(|e| match e {
+            StoreError::InputNotFound(hash, index) => CacheError::MissingInput(hash, index),
+            StoreError::DuplicateInput(hash, index) => CacheError::DuplicateInput(hash, index),
+            e => CacheError::StorageError(e),
+        })?;
+
+        // Mirror the committed changes in memory
+        for input in &tx.inputs {
+            cache.remove(&(input.previous_output, input.index));
+        }
+        for utxo in Utxo::from_transaction(tx, block_height) {
+            cache.insert(utxo, false);
+        }
+        self.evict(&mut cache, &mut store)?;
+
+        Ok(spent)
+    }
+
+    /// Connect a block, spending and creating outputs and recording undo data
+    ///
+    /// Transactions already added as unconfirmed are confirmed at `height`.
+    /// Affected cache entries are invalidated and reloaded from storage on the
+    /// next lookup.
+    pub fn connect_block(&self, height: u32, txs: &[Transaction]) -> Result<(), CacheError> {
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)?;
+
+        let undo = store.connect_block(height, txs).map_err(|e| match e {
+            StoreError::InputNotFound(hash, index) => CacheError::MissingInput(hash, index),
+            StoreError::DuplicateInput(hash, index) => CacheError::DuplicateInput(hash, index),
+            e => CacheError::StorageError(e),
+        })?;
+        Self::invalidate_block(&mut cache, &undo);
+
         Ok(())
     }
 
+    /// Disconnect the block at the chain tip, restoring the UTXO set to its
+    /// state before the block was connected
+    ///
+    /// Spent outputs are restored and outputs created by the block removed;
+    /// transactions that were unconfirmed before the block revert to unconfirmed.
+    pub fn disconnect_block(&self, height: u32) -> Result<(), CacheError> {
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)?;
+
+        let undo = store.disconnect_block(height)?;
+        Self::invalidate_block(&mut cache, &undo);
+
+        Ok(())
+    }
+
+    /// Drop every cache entry touched by a connected or disconnected block
+    fn invalidate_block(cache: &mut LruMap, undo: &BlockUndo) {
+        for tx_undo in &undo.transactions {
+            for index in 0..tx_undo.output_count {
+                cache.remove(&(tx_undo.tx_hash, index));
+            }
+            for utxo in &tx_undo.spent {
+                cache.remove(&(utxo.tx_hash, utxo.output_index));
+            }
+        }
+    }
+
     /// Get a UTXO by its transaction hash and output index
     pub fn get_utxo(&self, tx_hash: H256, output_index: u32) -> Result<Option<Utxo>, CacheError> {
-        // Try cache first
-        if let Some(utxo) = self.cache
-            .read()
-            .map_err(|_| CacheError::LockError)?
-            .get(&(tx_hash, output_index))
-        {
-            return Ok(Some(utxo.clone()));
+        // Try cache first. The cache lock is held across the store read on a
+        // miss so a concurrent spend can't be undone by re-inserting a stale
+        // entry.
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        if let Some(utxo) = cache.get(&(tx_hash, output_index)) {
+            let utxo = utxo.clone();
+            cache.stats.hits += 1;
+            return Ok(Some(utxo));
         }
-        
-        // If not in cache, try persistent storage
-        // TODO: Implement get_utxo in SdbStore and load into cache
-        
-        Ok(None)
+        cache.stats.misses += 1;
+
+        // If not in cache, fall back to persistent storage
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        let utxo = store.get_utxo(tx_hash, output_index)?;
+
+        // Populate the cache so subsequent lookups are served from memory
+        if let Some(utxo) = &utxo {
+            cache.insert(utxo.clone(), false);
+            self.evict(&mut cache, &mut store)?;
+        }
+
+        Ok(utxo)
+    }
+
+    /// Get every unspent output locked to `public_key_hash`
+    ///
+    /// Answered from the store's owner index after writing back any dirty
+    /// entries, so outputs only held in memory are included.
+    pub fn utxos_for_owner(&self, public_key_hash: &[u8]) -> Result<Vec<Utxo>, CacheError> {
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)?;
+        Ok(store.utxos_for_owner(public_key_hash)?)
+    }
+
+    /// Total value of every unspent output locked to `public_key_hash`
+    pub fn balance_of(&self, public_key_hash: &[u8]) -> Result<u64, CacheError> {
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)?;
+        Ok(store.balance_of(public_key_hash)?)
+    }
+
+    /// Height of the most recently connected block, if any
+    pub fn chain_tip(&self) -> Result<Option<u32>, CacheError> {
+        let store = self.store.read().map_err(|_| CacheError::LockError)?;
+        Ok(store.chain_tip()?)
     }
 
     /// Confirm a transaction's UTXOs by updating their block height
+    ///
+    /// The confirmation is written through to persistent storage first; the
+    /// store's transaction index is then used to refresh the affected cache
+    /// entries without scanning the whole cache.
     pub fn confirm_transaction(&self, tx_hash: H256, block_height: u32) -> Result<(), CacheError> {
-        let mut cache = self.cache.write().map_err(|_| CacheError::LockError)?;
-        
-        // Update all UTXOs from this transaction
-        for (key, utxo) in cache.iter_mut() {
-            if key.0 == tx_hash {
-                utxo.block_height = block_height;
-                utxo.is_confirmed = true;
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)?;
+
+        // Persist the confirmation atomically
+        let confirmed = store.confirm_outputs(tx_hash, block_height)?;
+
+        // Update the cached copies of all UTXOs from this transaction
+        for utxo in confirmed {
+            cache.insert(utxo, false);
+        }
+
+        self.evict(&mut cache, &mut store)
+    }
+
+    /// Write every dirty entry to persistent storage
+    ///
+    /// A no-op under `WritePolicy::WriteThrough`.
+    pub fn flush(&self) -> Result<(), CacheError> {
+        let mut cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        let mut store = self.store.write().map_err(|_| CacheError::LockError)?;
+        Self::write_back(&mut cache, &mut store)
+    }
+
+    /// Current hit, miss and eviction counters and cache size
+    pub fn stats(&self) -> Result<CacheStats, CacheError> {
+        let cache = self.cache.lock().map_err(|_| CacheError::LockError)?;
+        Ok(cache.stats())
+    }
+
+    /// The limits and write policy this cache was created with
+    pub fn config(&self) -> &CacheConfig {
+        &self.config
+    }
+
+    /// Evict down to the configured limits, persisting dirty victims
+    fn evict(&self, cache: &mut LruMap, store: &mut SdbStore) -> Result<(), CacheError> {
+        let dirty = cache.evict(&self.config);
+        if !dirty.is_empty() {
+            if let Err(e) = store.put_utxos(&dirty) {
+                // Never drop unpersisted entries; retry on the next eviction
+                for utxo in dirty {
+                    cache.insert(utxo, true);
+                }
+                return Err(e.into());
             }
+            cache.stats.write_backs += dirty.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Persist every dirty entry and mark it clean
+    fn write_back(cache: &mut LruMap, store: &mut SdbStore) -> Result<(), CacheError> {
+        let dirty = cache.take_dirty();
+        if !dirty.is_empty() {
+            if let Err(e) = store.put_utxos(&dirty) {
+                // Keep the entries dirty so a later flush can retry
+                for utxo in dirty {
+                    cache.insert(utxo, true);
+                }
+                return Err(e.into());
+            }
+            cache.stats.write_backs += dirty.len() as u64;
         }
-        
-        // TODO: Update persistent storage with confirmation
-        
         Ok(())
     }
 }
 
+impl Drop for UtxoCache {
+    /// Best-effort write-back of entries that were never persisted
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -7,6 +7,7 @@ pub struct Transaction {
     pub inputs: Vec<Input>,
     pub outputs: Vec<Output>,
     pub lock_time: u64,
+    /// Transaction ID, see [`Transaction::txid`]
     pub hash: H256,
 }
 
@@ -46,30 +47,203 @@ pub struct Utxo {
     pub is_confirmed: bool,
 }
 
+/// Undo information for one transaction connected as part of a block
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TxUndo {
+    /// Hash of the connected transaction
+    pub tx_hash: H256,
+    /// Number of outputs the transaction creates
+    pub output_count: u32,
+    /// Whether the transaction had already been applied unconfirmed before the
+    /// block was connected; it reverts to unconfirmed on disconnect instead of
+    /// being removed
+    pub was_unconfirmed: bool,
+    /// Indices of outputs that were still unspent and unconfirmed before the
+    /// block was connected; these revert to unconfirmed on disconnect
+    pub previously_unconfirmed: Vec<u32>,
+    /// UTXOs spent by the transaction's inputs, in input order
+    pub spent: Vec<Utxo>,
+}
+
+/// Undo information needed to roll back a connected block exactly
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlockUndo {
+    /// Height of the connected block
+    pub height: u32,
+    /// Per-transaction undo records, in block order
+    pub transactions: Vec<TxUndo>,
+}
+
+/// Which parts of a transaction an input signature commits to
+/// 
+/// Mirrors Bitcoin's sighash flags. The base type selects the outputs that
+/// are covered; `AnyoneCanPay` variants cover only the signing input, so
+/// other inputs can be added later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SighashType {
+    /// Every input and every output
+    All,
+    /// Every input, no outputs
+    None,
+    /// Every input and the output with the same index as the signing input
+    Single,
+    /// Only the signing input, every output
+    AllAnyoneCanPay,
+    /// Only the signing input, no outputs
+    NoneAnyoneCanPay,
+    /// Only the signing input and the output with the same index
+    SingleAnyoneCanPay,
+}
+
+impl SighashType {
+    /// Flag bit marking the `AnyoneCanPay` variants
+    pub const ANYONECANPAY_FLAG: u8 = 0x80;
+
+    /// Serialized flag byte, as appended to a signature
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SighashType::All => 0x01,
+            SighashType::None => 0x02,
+            SighashType::Single => 0x03,
+            SighashType::AllAnyoneCanPay => 0x81,
+            SighashType::NoneAnyoneCanPay => 0x82,
+            SighashType::SingleAnyoneCanPay => 0x83,
+        }
+    }
+
+    /// Parse a flag byte, `None` if it isn't one of the defined types
+    pub fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(SighashType::All),
+            0x02 => Some(SighashType::None),
+            0x03 => Some(SighashType::Single),
+            0x81 => Some(SighashType::AllAnyoneCanPay),
+            0x82 => Some(SighashType::NoneAnyoneCanPay),
+            0x83 => Some(SighashType::SingleAnyoneCanPay),
+            _ => None,
+        }
+    }
+
+    pub fn anyone_can_pay(self) -> bool {
+        self.to_u8() & Self::ANYONECANPAY_FLAG != 0
+    }
+}
+
 impl Transaction {
+    /// Transaction ID, used as the `hash` field and as the key of its outputs
+    /// 
+    /// Identical to [`Transaction::txid`], so the ID doesn't change when
+    /// inputs are (re-)signed.
     pub fn calculate_hash(&self) -> H256 {
+        self.txid()
+    }
+
+    /// Hash of everything except input signatures
+    /// 
+    /// Stable under re-signing, so child transactions (e.g. commitment and
+    /// refund transactions) can be signed before their parent is.
+    pub fn txid(&self) -> H256 {
         use sha3::{Digest, Keccak256};
         let mut hasher = Keccak256::new();
         
         // Add transaction data to hasher
-        hasher.update(&self.version.to_le_bytes());
+        hasher.update(self.version.to_le_bytes());
         for input in &self.inputs {
             hasher.update(input.previous_output.as_bytes());
-            hasher.update(&input.index.to_le_bytes());
-            hasher.update(&input.signature);
-            hasher.update(&input.sequence.to_le_bytes());
+            hasher.update(input.index.to_le_bytes());
+            hasher.update(input.sequence.to_le_bytes());
         }
         for output in &self.outputs {
-            hasher.update(&output.value.to_le_bytes());
+            hasher.update(output.value.to_le_bytes());
             hasher.update(&output.public_key_hash);
             hasher.update(&output.lock_script);
         }
-        hasher.update(&self.lock_time.to_le_bytes());
+        hasher.update(self.lock_time.to_le_bytes());
         
         // Convert hash to H256
         let result = hasher.finalize();
         H256::from_slice(&result)
     }
+
+    /// Hash of the whole transaction including input signatures
+    pub fn wtxid(&self) -> H256 {
+        use sha3::{Digest, Keccak256};
+        let mut hasher = Keccak256::new();
+        
+        hasher.update(self.version.to_le_bytes());
+        for input in &self.inputs {
+            hasher.update(input.previous_output.as_bytes());
+            hasher.update(input.index.to_le_bytes());
+            hasher.update(&input.signature);
+            hasher.update(input.sequence.to_le_bytes());
+        }
+        for output in &self.outputs {
+            hasher.update(output.value.to_le_bytes());
+            hasher.update(&output.public_key_hash);
+            hasher.update(&output.lock_script);
+        }
+        hasher.update(self.lock_time.to_le_bytes());
+        
+        H256::from_slice(&hasher.finalize())
+    }
+
+    /// Message signed by input `input_index` under `sighash_type`
+    /// 
+    /// Signatures are never covered. For `None`/`Single` the sequence numbers
+    /// of other inputs are left out so they can be updated independently.
+    /// Returns `None` if `input_index` is out of range, or for `Single` if
+    /// there is no output with the same index.
+    pub fn sighash(&self, input_index: usize, sighash_type: SighashType) -> Option<H256> {
+        use sha3::{Digest, Keccak256};
+
+        let signing_input = self.inputs.get(input_index)?;
+        let base = sighash_type.to_u8() & !SighashType::ANYONECANPAY_FLAG;
+        let single_output = if base == SighashType::Single.to_u8() {
+            Some(self.outputs.get(input_index)?)
+        } else {
+            None
+        };
+
+        let mut hasher = Keccak256::new();
+        hasher.update(self.version.to_le_bytes());
+        hasher.update([sighash_type.to_u8()]);
+        hasher.update((input_index as u32).to_le_bytes());
+
+        if sighash_type.anyone_can_pay() {
+            hasher.update(signing_input.previous_output.as_bytes());
+            hasher.update(signing_input.index.to_le_bytes());
+            hasher.update(signing_input.sequence.to_le_bytes());
+        } else {
+            hasher.update((self.inputs.len() as u32).to_le_bytes());
+            for (index, input) in self.inputs.iter().enumerate() {
+                hasher.update(input.previous_output.as_bytes());
+                hasher.update(input.index.to_le_bytes());
+                if index == input_index || base == SighashType::All.to_u8() {
+                    hasher.update(input.sequence.to_le_bytes());
+                }
+            }
+        }
+
+        match single_output {
+            Some(output) => {
+                hasher.update(output.value.to_le_bytes());
+                hasher.update(&output.public_key_hash);
+                hasher.update(&output.lock_script);
+            }
+            None if base == SighashType::All.to_u8() => {
+                hasher.update((self.outputs.len() as u32).to_le_bytes());
+                for output in &self.outputs {
+                    hasher.update(output.value.to_le_bytes());
+                    hasher.update(&output.public_key_hash);
+                    hasher.update(&output.lock_script);
+                }
+            }
+            None => {}
+        }
+
+        hasher.update(self.lock_time.to_le_bytes());
+        Some(H256::from_slice(&hasher.finalize()))
+    }
 }
 
 impl Utxo {
@@ -84,6 +258,19 @@ impl Utxo {
         }
     }
 
+    /// Create UTXOs for every output of a transaction
+    /// 
+    /// Outputs are confirmed at `block_height` if given, unconfirmed otherwise.
+    pub fn from_transaction(tx: &Transaction, block_height: Option<u32>) -> Vec<Self> {
+        let txid = tx.txid();
+        tx.outputs.iter().enumerate()
+            .map(|(index, output)| match block_height {
+                Some(height) => Self::new(output.clone(), height, index as u32, txid),
+                None => Self::new_unconfirmed(output.clone(), index as u32, txid),
+            })
+            .collect()
+    }
+
     /// Create a new unconfirmed UTXO from a transaction output
     pub fn new_unconfirmed(output: Output, output_index: u32, tx_hash: H256) -> Self {
         Self {
@@ -0,0 +1,713 @@
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::models::{SighashType, Transaction};
+use super::timelock;
+use crate::crypto::{self, KeyPair, PublicKey, SchemeKeyPair, SchemePublicKey, SchemeSignature, SignatureScheme};
+
+/// Opcodes understood by the interpreter
+///
+/// Values follow Bitcoin script so scripts read familiarly in hex dumps;
+/// anything not listed here is rejected wherever it appears.
+pub mod opcodes {
+    /// Push an empty element (false / zero)
+    pub const OP_0: u8 = 0x00;
+    /// Next byte is the length of the data to push
+    pub const OP_PUSHDATA1: u8 = 0x4c;
+    /// Next two bytes (little-endian) are the length of the data to push
+    pub const OP_PUSHDATA2: u8 = 0x4d;
+    /// Push the number -1
+    pub const OP_1NEGATE: u8 = 0x4f;
+    /// Push the number 1; `OP_1 + n - 1` pushes n up to 16
+    pub const OP_1: u8 = 0x51;
+    /// Push the number 16
+    pub const OP_16: u8 = 0x60;
+    pub const OP_NOP: u8 = 0x61;
+    pub const OP_IF: u8 = 0x63;
+    pub const OP_NOTIF: u8 = 0x64;
+    pub const OP_ELSE: u8 = 0x67;
+    pub const OP_ENDIF: u8 = 0x68;
+    pub const OP_VERIFY: u8 = 0x69;
+    pub const OP_RETURN: u8 = 0x6a;
+    pub const OP_DROP: u8 = 0x75;
+    pub const OP_DUP: u8 = 0x76;
+    pub const OP_SWAP: u8 = 0x7c;
+    pub const OP_SIZE: u8 = 0x82;
+    pub const OP_EQUAL: u8 = 0x87;
+    pub const OP_EQUALVERIFY: u8 = 0x88;
+    pub const OP_SHA256: u8 = 0xa8;
+    pub const OP_HASH160: u8 = 0xa9;
+    pub const OP_CHECKSIG: u8 = 0xac;
+    pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+    pub const OP_CHECKMULTISIG: u8 = 0xae;
+    pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+    pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+    pub const OP_CHECKSEQUENCEVERIFY: u8 = 0xb2;
+}
+
+use opcodes::*;
+
+/// Maximum size of a lock or unlock script in bytes
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Maximum size of a single stack element
+pub const MAX_ELEMENT_SIZE: usize = 520;
+/// Maximum number of elements on the stack
+pub const MAX_STACK_SIZE: usize = 1_000;
+/// Maximum number of public keys in a CHECKMULTISIG
+pub const MAX_MULTISIG_KEYS: usize = 20;
+/// Default number of steps (opcodes plus multisig keys) a script pair may take
+pub const DEFAULT_MAX_STEPS: usize = 1_000;
+/// Length of an Ed25519 signature element: `signature || sighash type`
+pub const SCRIPT_SIGNATURE_LEN: usize = 64 + 1;
+/// Length of a compressed secp256k1 public key, which selects ECDSA in CHECKSIG
+pub const COMPRESSED_SECP256K1_KEY_LEN: usize = 33;
+
+/// Maximum byte length of numbers used in arithmetic
+const MAX_NUM_LEN: usize = 4;
+/// Lock times don't fit in 4 bytes, so CLTV/CSV accept 5-byte numbers
+const MAX_LOCK_NUM_LEN: usize = 5;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    #[error("Script size {0} exceeds the maximum")]
+    ScriptTooLarge(usize),
+    #[error("Push of {0} bytes exceeds the maximum element size")]
+    PushTooLarge(usize),
+    #[error("Push runs past the end of the script")]
+    TruncatedPush,
+    #[error("Unknown opcode {0:#04x}")]
+    UnknownOpcode(u8),
+    #[error("Unlock script may only push data")]
+    UnlockNotPushOnly,
+    #[error("Step limit exceeded")]
+    StepLimitExceeded,
+    #[error("Stack size limit exceeded")]
+    StackOverflow,
+    #[error("Operation needs more stack elements")]
+    StackUnderflow,
+    #[error("IF/ELSE/ENDIF not balanced")]
+    UnbalancedConditional,
+    #[error("IF condition must be empty or 0x01")]
+    NonMinimalCondition,
+    #[error("Invalid or non-minimally encoded number")]
+    InvalidNumber,
+    #[error("OP_RETURN executed")]
+    OpReturn,
+    #[error("VERIFY failed")]
+    VerifyFailed,
+    #[error("Invalid public key encoding")]
+    InvalidPublicKey,
+    #[error("Invalid signature encoding")]
+    InvalidSignatureEncoding,
+    #[error("Unknown sighash type or one that doesn't apply to the input")]
+    InvalidSighash,
+    #[error("{0:?} keys can't be used in scripts")]
+    UnsupportedScheme(SignatureScheme),
+    #[error("Non-empty signature failed verification")]
+    NullFail,
+    #[error("Invalid multisig key or signature count")]
+    InvalidMultisigCount,
+    #[error("Negative lock time")]
+    NegativeLockTime,
+    #[error("Transaction lock time doesn't satisfy CHECKLOCKTIMEVERIFY")]
+    LockTimeNotSatisfied,
+    #[error("Input sequence doesn't satisfy CHECKSEQUENCEVERIFY")]
+    SequenceNotSatisfied,
+    #[error("Script finished with a false or empty stack")]
+    EvalFalse,
+    #[error("Script finished with more than one stack element")]
+    CleanStack,
+}
+
+/// A parsed script element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    /// Data pushed by `OP_0` or a push opcode
+    Push(&'a [u8]),
+    /// Any other opcode
+    Op(u8),
+}
+
+/// A lock or unlock script
+///
+/// Doubles as a builder: `push_*` methods append and return the script, so
+/// templates read left to right.
+///
+/// ```
+/// use state_channel_node::utxo::script::{opcodes::*, Script};
+///
+/// let script = Script::new()
+///     .push_opcode(OP_SHA256)
+///     .push_data(&[0u8; 32])
+///     .push_opcode(OP_EQUAL);
+/// assert_eq!(script.len(), 35);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script(Vec<u8>);
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Script(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Append a bare opcode
+    pub fn push_opcode(mut self, opcode: u8) -> Self {
+        self.0.push(opcode);
+        self
+    }
+
+    /// Append a push of `data` using the shortest encoding
+    pub fn push_data(mut self, data: &[u8]) -> Self {
+        match data.len() {
+            0 => self.0.push(OP_0),
+            len @ 1..=75 => self.0.push(len as u8),
+            len @ 76..=255 => self.0.extend_from_slice(&[OP_PUSHDATA1, len as u8]),
+            len => {
+                self.0.push(OP_PUSHDATA2);
+                self.0.extend_from_slice(&(len as u16).to_le_bytes());
+            }
+        }
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    /// Append a push of a number, using `OP_0`..`OP_16` where possible
+    pub fn push_int(self, value: i64) -> Self {
+        match value {
+            0 => self.push_opcode(OP_0),
+            -1 => self.push_opcode(OP_1NEGATE),
+            1..=16 => self.push_opcode(OP_1 + value as u8 - 1),
+            _ => self.push_data(&encode_num(value)),
+        }
+    }
+
+    /// Pay to the owner of `public_key_hash`
+    ///
+    /// `OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG`, unlocked by
+    /// `<signature> <public key>`.
+    pub fn p2pkh(public_key_hash: &[u8]) -> Self {
+        Script::new()
+            .push_opcode(OP_DUP)
+            .push_opcode(OP_HASH160)
+            .push_data(public_key_hash)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_CHECKSIG)
+    }
+
+    /// `required`-of-`keys.len()` multisig
+    ///
+    /// Unlocked by `required` signatures in the same order as their keys.
+    pub fn multisig(required: usize, keys: &[PublicKey]) -> Self {
+        let script = keys.iter()
+            .fold(Script::new().push_int(required as i64), |script, key| script.push_data(&key.as_bytes()));
+        script.push_int(keys.len() as i64).push_opcode(OP_CHECKMULTISIG)
+    }
+
+    /// Spendable by revealing the SHA-256 preimage of `hash`
+    pub fn hashlock(hash: &[u8; 32]) -> Self {
+        Script::new()
+            .push_opcode(OP_SHA256)
+            .push_data(hash)
+            .push_opcode(OP_EQUAL)
+    }
+
+    /// Parse into instructions, stopping at the first malformed push
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions { script: &self.0 }
+    }
+
+    /// Whether the script consists of data pushes only
+    pub fn is_push_only(&self) -> bool {
+        is_push_only(&self.0)
+    }
+}
+
+fn is_push_only(script: &[u8]) -> bool {
+    (Instructions { script }).all(|instruction| match instruction {
+        Ok(Instruction::Push(_)) => true,
+        Ok(Instruction::Op(opcode)) => opcode == OP_1NEGATE || (OP_1..=OP_16).contains(&opcode),
+        Err(_) => false,
+    })
+}
+
+impl From<Script> for Vec<u8> {
+    fn from(script: Script) -> Self {
+        script.0
+    }
+}
+
+/// Iterator over the instructions of a script
+pub struct Instructions<'a> {
+    script: &'a [u8],
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, ScriptError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&opcode, rest) = self.script.split_first()?;
+        let (len, rest) = match opcode {
+            OP_0 => (0, rest),
+            1..=75 => (opcode as usize, rest),
+            OP_PUSHDATA1 => match rest.split_first() {
+                Some((&len, rest)) => (len as usize, rest),
+                None => return Some(self.fail()),
+            },
+            OP_PUSHDATA2 => match rest {
+                [lo, hi, rest @ ..] => (u16::from_le_bytes([*lo, *hi]) as usize, rest),
+                _ => return Some(self.fail()),
+            },
+            _ => {
+                self.script = rest;
+                return Some(Ok(Instruction::Op(opcode)));
+            }
+        };
+        if rest.len() < len {
+            return Some(self.fail());
+        }
+        let (data, rest) = rest.split_at(len);
+        self.script = rest;
+        Some(Ok(Instruction::Push(data)))
+    }
+}
+
+impl Instructions<'_> {
+    fn fail<T>(&mut self) -> Result<T, ScriptError> {
+        self.script = &[];
+        Err(ScriptError::TruncatedPush)
+    }
+}
+
+/// Signature element for input `input_index`, as consumed by `OP_CHECKSIG`
+///
+/// # Errors
+/// * `InvalidSighash` if the input is out of range or `sighash_type` is
+///   `Single` without a matching output
+pub fn sign(
+    tx: &Transaction,
+    input_index: usize,
+    keypair: &KeyPair,
+    sighash_type: SighashType,
+) -> Result<Vec<u8>, ScriptError> {
+    let message = tx.sighash(input_index, sighash_type).ok_or(ScriptError::InvalidSighash)?;
+    let mut element = keypair.sign(message.as_bytes()).to_bytes().to_vec();
+    element.push(sighash_type.to_u8());
+    Ok(element)
+}
+
+/// Like [`sign`], for a key of any scheme usable in scripts
+///
+/// ECDSA signatures are DER-encoded, as in Bitcoin scripts.
+///
+/// # Errors
+/// * `UnsupportedScheme` for Schnorr keys, whose x-only encoding can't be
+///   told apart from Ed25519 keys in a script
+/// * `InvalidSighash` as for [`sign`]
+pub fn sign_with_scheme(
+    tx: &Transaction,
+    input_index: usize,
+    keypair: &SchemeKeyPair,
+    sighash_type: SighashType,
+) -> Result<Vec<u8>, ScriptError> {
+    if keypair.scheme() == SignatureScheme::SchnorrSecp256k1 {
+        return Err(ScriptError::UnsupportedScheme(keypair.scheme()));
+    }
+    let message = tx.sighash(input_index, sighash_type).ok_or(ScriptError::InvalidSighash)?;
+    let signature = keypair.sign(message.as_bytes());
+    let mut element = signature.to_der().unwrap_or_else(|| signature.to_bytes());
+    element.push(sighash_type.to_u8());
+    Ok(element)
+}
+
+/// Check that `unlock` satisfies `lock` for input `input_index` of `tx`
+pub fn verify_script(
+    unlock: &[u8],
+    lock: &[u8],
+    tx: &Transaction,
+    input_index: usize,
+) -> Result<(), ScriptError> {
+    Interpreter::new(tx, input_index).verify(unlock, lock)
+}
+
+/// Evaluates scripts in the context of one transaction input
+///
+/// Signatures are checked against `Transaction::sighash` of that input, and
+/// CLTV/CSV against the transaction's lock time and the input's sequence.
+pub struct Interpreter<'a> {
+    tx: &'a Transaction,
+    input_index: usize,
+    max_steps: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(tx: &'a Transaction, input_index: usize) -> Self {
+        Self { tx, input_index, max_steps: DEFAULT_MAX_STEPS }
+    }
+
+    /// Limit the number of steps `verify` may take
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Run `unlock` followed by `lock`
+    ///
+    /// The unlock script must be push-only, and the lock script must leave
+    /// exactly one true element on the stack.
+    pub fn verify(&self, unlock: &[u8], lock: &[u8]) -> Result<(), ScriptError> {
+        if !is_push_only(unlock) {
+            return Err(ScriptError::UnlockNotPushOnly);
+        }
+
+        let mut stack = Vec::new();
+        let mut steps = 0;
+        self.execute(unlock, &mut stack, &mut steps)?;
+        self.execute(lock, &mut stack, &mut steps)?;
+
+        match stack.as_slice() {
+            [top] if cast_to_bool(top) => Ok(()),
+            [] | [_] => Err(ScriptError::EvalFalse),
+            _ => Err(ScriptError::CleanStack),
+        }
+    }
+
+    fn execute(&self, script: &[u8], stack: &mut Vec<Vec<u8>>, steps: &mut usize) -> Result<(), ScriptError> {
+        if script.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::ScriptTooLarge(script.len()));
+        }
+
+        // One entry per open IF, true when its current branch executes
+        let mut branches: Vec<bool> = Vec::new();
+
+        for instruction in (Instructions { script }) {
+            *steps += 1;
+            if *steps > self.max_steps {
+                return Err(ScriptError::StepLimitExceeded);
+            }
+            let executing = branches.iter().all(|&branch| branch);
+
+            let opcode = match instruction? {
+                Instruction::Push(data) => {
+                    if data.len() > MAX_ELEMENT_SIZE {
+                        return Err(ScriptError::PushTooLarge(data.len()));
+                    }
+                    if executing {
+                        stack.push(data.to_vec());
+                    }
+                    check_stack_size(stack)?;
+                    continue;
+                }
+                Instruction::Op(opcode) => opcode,
+            };
+
+            match opcode {
+                OP_IF | OP_NOTIF => {
+                    let mut branch = false;
+                    if executing {
+                        let condition = pop(stack)?;
+                        branch = match condition.as_slice() {
+                            [] => false,
+                            [1] => true,
+                            _ => return Err(ScriptError::NonMinimalCondition),
+                        };
+                        if opcode == OP_NOTIF {
+                            branch = !branch;
+                        }
+                    }
+                    branches.push(branch);
+                    continue;
+                }
+                OP_ELSE => {
+                    let branch = branches.last_mut().ok_or(ScriptError::UnbalancedConditional)?;
+                    *branch = !*branch;
+                    continue;
+                }
+                OP_ENDIF => {
+                    branches.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if !is_known(opcode) {
+                return Err(ScriptError::UnknownOpcode(opcode));
+            }
+            if !executing {
+                continue;
+            }
+
+            match opcode {
+                OP_1NEGATE => stack.push(encode_num(-1)),
+                OP_1..=OP_16 => stack.push(encode_num((opcode - OP_1 + 1) as i64)),
+                OP_NOP => {}
+                OP_VERIFY => verify(cast_to_bool(&pop(stack)?))?,
+                OP_RETURN => return Err(ScriptError::OpReturn),
+                OP_DROP => {
+                    pop(stack)?;
+                }
+                OP_DUP => {
+                    let top = stack.last().ok_or(ScriptError::StackUnderflow)?.clone();
+                    stack.push(top);
+                }
+                OP_SWAP => {
+                    let len = stack.len();
+                    if len < 2 {
+                        return Err(ScriptError::StackUnderflow);
+                    }
+                    stack.swap(len - 1, len - 2);
+                }
+                OP_SIZE => {
+                    let len = stack.last().ok_or(ScriptError::StackUnderflow)?.len();
+                    stack.push(encode_num(len as i64));
+                }
+                OP_EQUAL | OP_EQUALVERIFY => {
+                    let equal = pop(stack)? == pop(stack)?;
+                    if opcode == OP_EQUALVERIFY {
+                        verify(equal)?;
+                    } else {
+                        stack.push(encode_bool(equal));
+                    }
+                }
+                OP_SHA256 => {
+                    let data = pop(stack)?;
+                    stack.push(Sha256::digest(data).to_vec());
+                }
+                OP_HASH160 => {
+                    let data = pop(stack)?;
+                    stack.push(crypto::hash160(&data).to_vec());
+                }
+                OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                    let key = pop(stack)?;
+                    let signature = pop(stack)?;
+                    let valid = self.check_signature(&signature, &key)?;
+                    if !valid && !signature.is_empty() {
+                        return Err(ScriptError::NullFail);
+                    }
+                    if opcode == OP_CHECKSIGVERIFY {
+                        verify(valid)?;
+                    } else {
+                        stack.push(encode_bool(valid));
+                    }
+                }
+                OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                    let valid = self.check_multisig(stack, steps)?;
+                    if opcode == OP_CHECKMULTISIGVERIFY {
+                        verify(valid)?;
+                    } else {
+                        stack.push(encode_bool(valid));
+                    }
+                }
+                OP_CHECKLOCKTIMEVERIFY => {
+                    let lock_time = lock_number(stack)?;
+                    if !timelock::lock_time_satisfies(self.tx, self.input_index, lock_time) {
+                        return Err(ScriptError::LockTimeNotSatisfied);
+                    }
+                }
+                OP_CHECKSEQUENCEVERIFY => {
+                    let required = lock_number(stack)?;
+                    let satisfied = match u32::try_from(required) {
+                        Ok(sequence) => timelock::sequence_satisfies(self.tx, self.input_index, sequence),
+                        // Wider than a sequence number: only a disabled lock passes (BIP112)
+                        Err(_) => required & u64::from(timelock::SEQUENCE_LOCKTIME_DISABLE_FLAG) != 0,
+                    };
+                    if !satisfied {
+                        return Err(ScriptError::SequenceNotSatisfied);
+                    }
+                }
+                _ => return Err(ScriptError::UnknownOpcode(opcode)),
+            }
+            check_stack_size(stack)?;
+        }
+
+        if !branches.is_empty() {
+            return Err(ScriptError::UnbalancedConditional);
+        }
+        Ok(())
+    }
+
+    /// Pop `n <keys..> m <signatures..>` and match signatures to keys in order
+    fn check_multisig(&self, stack: &mut Vec<Vec<u8>>, steps: &mut usize) -> Result<bool, ScriptError> {
+        let key_count = decode_num(&pop(stack)?, MAX_NUM_LEN)?;
+        if !(0..=MAX_MULTISIG_KEYS as i64).contains(&key_count) {
+            return Err(ScriptError::InvalidMultisigCount);
+        }
+        *steps += key_count as usize;
+        if *steps > self.max_steps {
+            return Err(ScriptError::StepLimitExceeded);
+        }
+        let mut keys = (0..key_count).map(|_| pop(stack)).collect::<Result<Vec<_>, _>>()?;
+        keys.reverse();
+
+        let required = decode_num(&pop(stack)?, MAX_NUM_LEN)?;
+        if !(0..=key_count).contains(&required) {
+            return Err(ScriptError::InvalidMultisigCount);
+        }
+        let mut signatures = (0..required).map(|_| pop(stack)).collect::<Result<Vec<_>, _>>()?;
+        signatures.reverse();
+
+        let mut remaining_keys = keys.iter();
+        let mut valid = true;
+        for signature in &signatures {
+            let mut matched = false;
+            for key in remaining_keys.by_ref() {
+                if self.check_signature(signature, key)? {
+                    matched = true;
+                    break;
+                }
+            }
+            if !matched {
+                valid = false;
+                break;
+            }
+        }
+
+        if !valid && signatures.iter().any(|signature| !signature.is_empty()) {
+            return Err(ScriptError::NullFail);
+        }
+        Ok(valid)
+    }
+
+    /// Verify a `signature || sighash type` element; an empty one is just false
+    ///
+    /// 33-byte keys are compressed secp256k1 keys with DER-encoded ECDSA
+    /// signatures, all others Ed25519 keys with 64-byte signatures.
+    fn check_signature(&self, element: &[u8], key: &[u8]) -> Result<bool, ScriptError> {
+        let Some((&sighash_byte, signature_bytes)) = element.split_last() else { return Ok(false) };
+        let key = if key.len() == COMPRESSED_SECP256K1_KEY_LEN {
+            SchemePublicKey::from_bytes(SignatureScheme::EcdsaSecp256k1, key)
+        } else {
+            SchemePublicKey::from_bytes(SignatureScheme::Ed25519, key)
+        }.map_err(|_| ScriptError::InvalidPublicKey)?;
+        let signature = match key.scheme() {
+            SignatureScheme::EcdsaSecp256k1 => SchemeSignature::from_der(signature_bytes),
+            scheme => SchemeSignature::from_bytes(scheme, signature_bytes),
+        }.map_err(|_| ScriptError::InvalidSignatureEncoding)?;
+        let message = SighashType::from_u8(sighash_byte)
+            .and_then(|sighash_type| self.tx.sighash(self.input_index, sighash_type))
+            .ok_or(ScriptError::InvalidSighash)?;
+
+        Ok(key.verify(&signature, message.as_bytes()))
+    }
+}
+
+fn is_known(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        OP_1NEGATE
+            | OP_1..=OP_16
+            | OP_NOP
+            | OP_VERIFY
+            | OP_RETURN
+            | OP_DROP
+            | OP_DUP
+            | OP_SWAP
+            | OP_SIZE
+            | OP_EQUAL
+            | OP_EQUALVERIFY
+            | OP_SHA256
+            | OP_HASH160
+            | OP_CHECKSIG
+            | OP_CHECKSIGVERIFY
+            | OP_CHECKMULTISIG
+            | OP_CHECKMULTISIGVERIFY
+            | OP_CHECKLOCKTIMEVERIFY
+            | OP_CHECKSEQUENCEVERIFY
+    )
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, ScriptError> {
+    stack.pop().ok_or(ScriptError::StackUnderflow)
+}
+
+fn verify(condition: bool) -> Result<(), ScriptError> {
+    if condition {
+        Ok(())
+    } else {
+        Err(ScriptError::VerifyFailed)
+    }
+}
+
+fn check_stack_size(stack: &[Vec<u8>]) -> Result<(), ScriptError> {
+    if stack.len() > MAX_STACK_SIZE {
+        return Err(ScriptError::StackOverflow);
+    }
+    Ok(())
+}
+
+/// Read (without popping) the non-negative lock value CLTV/CSV compare against
+fn lock_number(stack: &[Vec<u8>]) -> Result<u64, ScriptError> {
+    let top = stack.last().ok_or(ScriptError::StackUnderflow)?;
+    let value = decode_num(top, MAX_LOCK_NUM_LEN)?;
+    if value < 0 {
+        return Err(ScriptError::NegativeLockTime);
+    }
+    Ok(value as u64)
+}
+
+/// False for empty, all-zero and negative-zero elements
+fn cast_to_bool(element: &[u8]) -> bool {
+    match element.split_last() {
+        Some((&last, rest)) => rest.iter().any(|&byte| byte != 0) || last & 0x7f != 0,
+        None => false,
+    }
+}
+
+fn encode_bool(value: bool) -> Vec<u8> {
+    if value { vec![1] } else { Vec::new() }
+}
+
+/// Minimal little-endian sign-magnitude encoding
+fn encode_num(value: i64) -> Vec<u8> {
+    let mut magnitude = value.unsigned_abs();
+    let mut bytes = Vec::new();
+    while magnitude > 0 {
+        bytes.push(magnitude as u8);
+        magnitude >>= 8;
+    }
+    match bytes.last_mut() {
+        Some(last) if *last & 0x80 != 0 => bytes.push(if value < 0 { 0x80 } else { 0 }),
+        Some(last) if value < 0 => *last |= 0x80,
+        _ => {}
+    }
+    bytes
+}
+
+/// Decode a minimally encoded number of at most `max_len` bytes
+fn decode_num(bytes: &[u8], max_len: usize) -> Result<i64, ScriptError> {
+    if bytes.len() > max_len {
+        return Err(ScriptError::InvalidNumber);
+    }
+    let Some((&last, rest)) = bytes.split_last() else { return Ok(0) };
+    // The last byte may only be 0x00/0x80 if it carries the sign for the byte before
+    if last & 0x7f == 0 && rest.last().is_none_or(|&byte| byte & 0x80 == 0) {
+        return Err(ScriptError::InvalidNumber);
+    }
+
+    let magnitude = bytes.iter().enumerate()
+        .fold(0i64, |value, (i, &byte)| value | (byte as i64) << (8 * i));
+    if last & 0x80 != 0 {
+        Ok(-(magnitude & !(0x80i64 << (8 * rest.len()))))
+    } else {
+        Ok(magnitude)
+    }
+}
@@ -0,0 +1,313 @@
+use std::cmp::Reverse;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use thiserror::Error;
+
+use super::cache::{CacheError, UtxoCache};
+use super::models::Utxo;
+
+/// Estimated size of the fixed part of a transaction (version, counts, lock time)
+pub const TX_OVERHEAD_BYTES: u64 = 10;
+/// Estimated size of one signed input
+pub const INPUT_BYTES: u64 = 148;
+/// Estimated size of one output
+pub const OUTPUT_BYTES: u64 = 34;
+/// Change below this value is not worth an output and is added to the fee
+pub const DUST_THRESHOLD: u64 = 546;
+
+/// Maximum number of search steps branch-and-bound takes before giving up
+const BNB_MAX_TRIES: usize = 100_000;
+
+#[derive(Error, Debug)]
+pub enum SelectionError {
+    #[error("Selection target must be greater than zero")]
+    ZeroTarget,
+    #[error("Insufficient funds: {available} available, at least {required} required")]
+    InsufficientFunds { available: u64, required: u64 },
+    #[error("No input set matches the target without change")]
+    NoExactMatch,
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+}
+
+/// Algorithm used to pick inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// Spend the largest outputs first; fewest inputs, usually leaves change
+    LargestFirst,
+    /// Search for an input set that covers the target and fee without change
+    BranchAndBound,
+    /// Pick randomly, then keep adding inputs that move change towards the
+    /// target amount, which keeps the UTXO set healthy over time
+    RandomImprove,
+}
+
+/// What to fund and which outputs may be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionParams {
+    /// Amount the payment output must carry
+    pub target: u64,
+    /// Fee per estimated transaction byte
+    pub fee_rate: u64,
+    /// Minimum confirmations an output needs to be spent; 0 allows unconfirmed
+    pub min_confirmations: u32,
+}
+
+impl SelectionParams {
+    /// Select for `target` at `fee_rate`, spending confirmed outputs only
+    pub fn new(target: u64, fee_rate: u64) -> Self {
+        Self { target, fee_rate, min_confirmations: 1 }
+    }
+
+    /// Require at least `min_confirmations` confirmations per input
+    pub fn with_min_confirmations(mut self, min_confirmations: u32) -> Self {
+        self.min_confirmations = min_confirmations;
+        self
+    }
+
+    /// Estimated fee of a transaction with the given number of inputs and outputs
+    pub fn fee_for(&self, inputs: usize, outputs: usize) -> u64 {
+        let size = TX_OVERHEAD_BYTES + INPUT_BYTES * inputs as u64 + OUTPUT_BYTES * outputs as u64;
+        self.fee_rate.saturating_mul(size)
+    }
+
+    /// Value an input adds once the cost of spending it is paid
+    fn effective_value(&self, utxo: &Utxo) -> u64 {
+        utxo.output.value.saturating_sub(self.fee_rate.saturating_mul(INPUT_BYTES))
+    }
+}
+
+/// Result of coin selection
+///
+/// `inputs` always cover `target + change + fee` exactly; any change too small
+/// to be worth an output is folded into `fee`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    /// Chosen UTXOs to spend
+    pub inputs: Vec<Utxo>,
+    /// Value of the change output, 0 if the transaction has none
+    pub change: u64,
+    /// Estimated fee, including any dust that was not returned as change
+    pub fee: u64,
+}
+
+impl Selection {
+    /// Sum of the chosen input values
+    pub fn total_input(&self) -> u64 {
+        self.inputs.iter().map(|utxo| utxo.output.value).sum()
+    }
+}
+
+/// Select inputs owned by `public_key_hash` from the UTXO set
+///
+/// Only outputs with at least `params.min_confirmations` confirmations
+/// relative to the current chain tip are considered.
+pub fn select_coins(
+    cache: &UtxoCache,
+    public_key_hash: &[u8],
+    params: &SelectionParams,
+    strategy: SelectionStrategy,
+) -> Result<Selection, SelectionError> {
+    let owned = cache.utxos_for_owner(public_key_hash)?;
+    let tip = cache.chain_tip()?;
+    let candidates = eligible(owned, tip, params.min_confirmations);
+
+    match strategy {
+        SelectionStrategy::LargestFirst => largest_first(&candidates, params),
+        SelectionStrategy::BranchAndBound => branch_and_bound(&candidates, params),
+        SelectionStrategy::RandomImprove => random_improve(&candidates, params, &mut rand::thread_rng()),
+    }
+}
+
+/// Number of confirmations of `utxo` given the chain tip, 0 if unconfirmed
+pub fn confirmations(utxo: &Utxo, tip: Option<u32>) -> u32 {
+    if !utxo.is_confirmed {
+        return 0;
+    }
+    let tip = tip.unwrap_or(utxo.block_height).max(utxo.block_height);
+    tip - utxo.block_height + 1
+}
+
+/// Keep only outputs with at least `min_confirmations` confirmations
+///
+/// Outputs confirmed above the recorded tip (e.g. added directly with a block
+/// height) extend the tip used for counting.
+pub fn eligible(utxos: Vec<Utxo>, tip: Option<u32>, min_confirmations: u32) -> Vec<Utxo> {
+    let highest = utxos.iter().filter(|utxo| utxo.is_confirmed).map(|utxo| utxo.block_height).max();
+    let tip = tip.max(highest);
+
+    utxos.into_iter()
+        .filter(|utxo| confirmations(utxo, tip) >= min_confirmations)
+        .collect()
+}
+
+/// Spend the largest candidates until the target and fee are covered
+pub fn largest_first(candidates: &[Utxo], params: &SelectionParams) -> Result<Selection, SelectionError> {
+    check_target(params)?;
+
+    let mut sorted = candidates.to_vec();
+    sorted.sort_by_key(|utxo| Reverse(utxo.output.value));
+
+    let mut chosen = Vec::new();
+    for utxo in sorted {
+        chosen.push(utxo);
+        if let Some(selection) = finalize(&chosen, params) {
+            return Ok(selection);
+        }
+    }
+
+    Err(insufficient_funds(candidates, params))
+}
+
+/// Find an input set that pays the target and fee with no change output
+///
+/// Depth-first search over candidates sorted by effective value, accepting
+/// any set whose excess is smaller than the cost of creating and later
+/// spending a change output. The set wasting the least is returned.
+pub fn branch_and_bound(candidates: &[Utxo], params: &SelectionParams) -> Result<Selection, SelectionError> {
+    check_target(params)?;
+
+    let mut pool: Vec<(u64, &Utxo)> = candidates.iter()
+        .map(|utxo| (params.effective_value(utxo), utxo))
+        .filter(|(value, _)| *value > 0)
+        .collect();
+    pool.sort_by_key(|(value, _)| Reverse(*value));
+
+    let available: u64 = pool.iter().map(|(value, _)| value).sum();
+    let target = params.target.saturating_add(params.fee_for(0, 1));
+    if available < target {
+        return Err(insufficient_funds(candidates, params));
+    }
+    let cost_of_change = params.fee_rate.saturating_mul(OUTPUT_BYTES + INPUT_BYTES);
+    let upper = target.saturating_add(cost_of_change);
+
+    let mut best: Option<(u64, Vec<usize>)> = None;
+    let mut current: Vec<usize> = Vec::new();
+    let mut total = 0u64;
+    let mut remaining = available;
+    let mut index = 0;
+
+    for _ in 0..BNB_MAX_TRIES {
+        let backtrack = if total + remaining < target || total > upper {
+            true
+        } else if total >= target {
+            let waste = total - target;
+            if best.as_ref().is_none_or(|(best_waste, _)| waste < *best_waste) {
+                best = Some((waste, current.clone()));
+            }
+            true
+        } else {
+            index >= pool.len()
+        };
+
+        if backtrack {
+            // Undo trailing exclusions, then turn the last inclusion into an exclusion
+            let Some(&last) = current.last() else { break };
+            remaining += pool[last + 1..index].iter().map(|(value, _)| value).sum::<u64>();
+            current.pop();
+            total -= pool[last].0;
+            index = last + 1;
+            if best.as_ref().is_some_and(|(waste, _)| *waste == 0) {
+                break;
+            }
+            continue;
+        }
+
+        remaining -= pool[index].0;
+        total += pool[index].0;
+        current.push(index);
+        index += 1;
+    }
+
+    let (_, indices) = best.ok_or(SelectionError::NoExactMatch)?;
+    let inputs: Vec<Utxo> = indices.iter().map(|&i| pool[i].1.clone()).collect();
+    let fee = inputs.iter().map(|utxo| utxo.output.value).sum::<u64>() - params.target;
+    Ok(Selection { inputs, change: 0, fee })
+}
+
+/// Random selection followed by an improvement pass
+///
+/// Inputs are drawn at random until the target and fee are covered, then
+/// further random inputs are added while they bring the total closer to twice
+/// the target without exceeding three times it. The resulting change is of a
+/// similar size to the payment, which keeps future selections cheap.
+pub fn random_improve<R: Rng + ?Sized>(
+    candidates: &[Utxo],
+    params: &SelectionParams,
+    rng: &mut R,
+) -> Result<Selection, SelectionError> {
+    check_target(params)?;
+
+    let mut pool = candidates.to_vec();
+    pool.shuffle(rng);
+
+    let mut chosen = Vec::new();
+    let mut selection = loop {
+        if let Some(selection) = finalize(&chosen, params) {
+            break selection;
+        }
+        match pool.pop() {
+            Some(utxo) => chosen.push(utxo),
+            None => return Err(insufficient_funds(candidates, params)),
+        }
+    };
+
+    let ideal = params.target.saturating_mul(2);
+    let limit = params.target.saturating_mul(3);
+    while let Some(utxo) = pool.pop() {
+        let total = selection.total_input();
+        let improved = total + utxo.output.value;
+        if improved > limit || ideal.abs_diff(improved) >= ideal.abs_diff(total) {
+            continue;
+        }
+        chosen.push(utxo);
+        match finalize(&chosen, params) {
+            Some(next) => selection = next,
+            None => {
+                chosen.pop();
+            }
+        }
+    }
+
+    Ok(selection)
+}
+
+fn check_target(params: &SelectionParams) -> Result<(), SelectionError> {
+    if params.target == 0 {
+        return Err(SelectionError::ZeroTarget);
+    }
+    Ok(())
+}
+
+/// Compute change and fee for a candidate input set, `None` if it falls short
+fn finalize(inputs: &[Utxo], params: &SelectionParams) -> Option<Selection> {
+    let total: u64 = inputs.iter().map(|utxo| utxo.output.value).sum();
+
+    let with_change = params.target.saturating_add(params.fee_for(inputs.len(), 2));
+    if total >= with_change && total - with_change >= DUST_THRESHOLD {
+        return Some(Selection {
+            inputs: inputs.to_vec(),
+            change: total - with_change,
+            fee: params.fee_for(inputs.len(), 2),
+        });
+    }
+
+    let without_change = params.target.saturating_add(params.fee_for(inputs.len(), 1));
+    if total >= without_change {
+        return Some(Selection {
+            inputs: inputs.to_vec(),
+            change: 0,
+            fee: total - params.target,
+        });
+    }
+
+    None
+}
+
+fn insufficient_funds(candidates: &[Utxo], params: &SelectionParams) -> SelectionError {
+    SelectionError::InsufficientFunds {
+        available: candidates.iter().map(|utxo| utxo.output.value).sum(),
+        required: params.target.saturating_add(params.fee_for(1, 1)),
+    }
+}
@@ -0,0 +1,95 @@
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::merkle::MerkleTree;
+use crate::utxo::models::Utxo;
+use crate::utxo::store::StoreError;
+
+/// Magic bytes identifying a UTXO snapshot file
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"UTXOSNAP";
+/// Current snapshot format version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Length of the trailing SHA-256 checksum
+const CHECKSUM_LEN: usize = 32;
+/// Length of the fixed header (magic + version)
+const HEADER_LEN: usize = SNAPSHOT_MAGIC.len() + 4;
+
+/// A point-in-time copy of the whole UTXO set
+///
+/// On disk a snapshot is laid out as:
+/// `magic (8) | version (u32 LE) | bincode body | SHA-256 of everything before it (32)`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UtxoSnapshot {
+    /// Height of the chain tip when the snapshot was taken, if any
+    pub height: Option<u32>,
+    /// Merkle root over the serialized UTXOs, in storage key order
+    pub merkle_root: H256,
+    /// Every UTXO in the set, in storage key order
+    pub utxos: Vec<Utxo>,
+}
+
+impl UtxoSnapshot {
+    /// Build a snapshot and compute its Merkle root
+    pub fn new(height: Option<u32>, utxos: Vec<Utxo>) -> Result<Self, StoreError> {
+        let merkle_root = merkle_root(&utxos)?;
+        Ok(Self { height, merkle_root, utxos })
+    }
+
+    /// Encode the snapshot into its versioned, checksummed file format
+    pub fn encode(&self) -> Result<Vec<u8>, StoreError> {
+        let body = bincode::serialize(self)
+            .map_err(|e| StoreError::SerializationError(e.to_string()))?;
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + body.len() + CHECKSUM_LEN);
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum);
+        Ok(bytes)
+    }
+
+    /// Decode a snapshot file, checking magic, version, checksum and Merkle root
+    pub fn decode(bytes: &[u8]) -> Result<Self, StoreError> {
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+            return Err(StoreError::InvalidSnapshot("file too short".to_string()));
+        }
+        if &bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+            return Err(StoreError::InvalidSnapshot("bad magic".to_string()));
+        }
+
+        let mut version = [0u8; 4];
+        version.copy_from_slice(&bytes[SNAPSHOT_MAGIC.len()..HEADER_LEN]);
+        let version = u32::from_le_bytes(version);
+        if version != SNAPSHOT_VERSION {
+            return Err(StoreError::InvalidSnapshot(format!("unsupported version {}", version)));
+        }
+
+        let (content, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if Sha256::digest(content).as_slice() != checksum {
+            return Err(StoreError::InvalidSnapshot("checksum mismatch".to_string()));
+        }
+
+        let snapshot: Self = bincode::deserialize(&content[HEADER_LEN..])
+            .map_err(|e| StoreError::SerializationError(e.to_string()))?;
+
+        // The embedded root must match the UTXOs actually contained
+        let actual = merkle_root(&snapshot.utxos)?;
+        if actual != snapshot.merkle_root {
+            return Err(StoreError::SnapshotRootMismatch { expected: snapshot.merkle_root, actual });
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// Compute the Merkle root of a UTXO set, `H256::zero()` if empty
+pub fn merkle_root(utxos: &[Utxo]) -> Result<H256, StoreError> {
+    let leaves = utxos.iter()
+        .map(|utxo| bincode::serialize(utxo).map_err(|e| StoreError::SerializationError(e.to_string())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(MerkleTree::from_leaves(leaves).root_hash().unwrap_or_else(H256::zero))
+}
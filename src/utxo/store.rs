@@ -1,9 +1,31 @@
-use crate::utxo::models::{Input, Transaction};
+use crate::utxo::models::{BlockUndo, Input, Output, Transaction, TxUndo, Utxo};
+use crate::utxo::snapshot::{self, UtxoSnapshot};
 use primitive_types::H256;
-use sled::Db;
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, TransactionError,
+    TransactionalTree,
+};
+use sled::{Db, Transactional, Tree};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
 use std::path::Path;
 use thiserror::Error;
 
+/// Name of the sled tree indexing unspent output indices by transaction hash
+const TX_INDEX_TREE: &str = "tx_index";
+/// Name of the sled tree indexing unspent outputs by owner public key hash
+const OWNER_INDEX_TREE: &str = "owner_index";
+/// Name of the sled tree holding per-block undo data keyed by height
+const UNDO_TREE: &str = "block_undo";
+/// Name of the sled tree holding the txids of transactions applied while
+/// unconfirmed and not yet connected in a block
+const UNCONFIRMED_TREE: &str = "unconfirmed_txs";
+/// Name of the sled tree holding store metadata
+const META_TREE: &str = "meta";
+/// Metadata key for the height of the last imported snapshot
+const CHECKPOINT_HEIGHT_KEY: &[u8] = b"checkpoint_height";
+
 /// Unified error type for UTXO storage operations
 #[derive(Debug, Error)]
 pub enum StoreError {
@@ -15,91 +37,603 @@ pub enum StoreError {
     StorageError(String),
     #[error("Serialization error: {0}")]
     SerializationError(String),
+    #[error("Input {0:?}:{1} not found or already spent")]
+    InputNotFound(H256, u32),
+    #[error("Input {0:?}:{1} spent more than once in the same transaction")]
+    DuplicateInput(H256, u32),
+    #[error("Invalid block height: expected {expected}, got {got}")]
+    InvalidBlockHeight { expected: u32, got: u32 },
+    #[error("Block {height} is not the chain tip (tip: {tip:?})")]
+    NotChainTip { height: u32, tip: Option<u32> },
+    #[error("No undo data for block {0}")]
+    MissingUndoData(u32),
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Snapshot Merkle root mismatch: expected {expected:?}, got {actual:?}")]
+    SnapshotRootMismatch { expected: H256, actual: H256 },
 }
 
 /// Persistent UTXO storage implementation using Sled key-value store
-/// 
+///
 /// # Features
-/// - Atomic batch operations
+/// - Atomic multi-tree transactions
 /// - Crash-resistant storage
-/// - Efficient key lookups using transaction hashes and output indices
+/// - Efficient key lookups using signature-independent txids and output indices
+/// - Secondary index from transaction hash to its unspent outputs
+/// - Secondary index from owner public key hash to its unspent outputs
+/// - Per-block undo data for chain reorganizations
+/// - Record of transactions applied while unconfirmed
+/// - Checksummed snapshots of the whole UTXO set for checkpointing
 #[derive(Debug)]
 pub struct SdbStore {
     db: Db,
+    tx_index: Tree,
+    owner_index: Tree,
+    undo: Tree,
+    unconfirmed: Tree,
+    meta: Tree,
+}
+
+/// Transactional view over every tree backing the UTXO set.
+///
+/// All mutations go through these helpers so the secondary indexes
+/// can never drift from the primary UTXO records.
+struct UtxoTrees<'a> {
+    utxos: &'a TransactionalTree,
+    tx_index: &'a TransactionalTree,
+    owner_index: &'a TransactionalTree,
+    undo: &'a TransactionalTree,
+    unconfirmed: &'a TransactionalTree,
+    meta: &'a TransactionalTree,
+}
+
+impl UtxoTrees<'_> {
+    fn get(&self, tx_hash: H256, output_index: u32) -> ConflictableTransactionResult<Option<Utxo>, StoreError> {
+        let key_bytes = serialize(&(tx_hash, output_index)).map_err(ConflictableTransactionError::Abort)?;
+        match self.utxos.get(key_bytes)? {
+            Some(value) => Ok(Some(deserialize(&value).map_err(ConflictableTransactionError::Abort)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn tx_outputs(&self, tx_hash: H256) -> ConflictableTransactionResult<Vec<u32>, StoreError> {
+        match self.tx_index.get(tx_hash.as_bytes())? {
+            Some(value) => Ok(deserialize(&value).map_err(ConflictableTransactionError::Abort)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn set_tx_outputs(&self, tx_hash: H256, indices: &[u32]) -> ConflictableTransactionResult<(), StoreError> {
+        if indices.is_empty() {
+            self.tx_index.remove(tx_hash.as_bytes())?;
+        } else {
+            let value = serialize(&indices).map_err(ConflictableTransactionError::Abort)?;
+            self.tx_index.insert(tx_hash.as_bytes(), value)?;
+        }
+        Ok(())
+    }
+
+    /// Insert or overwrite a UTXO record and index it
+    fn put(&self, utxo: &Utxo) -> ConflictableTransactionResult<(), StoreError> {
+        let key_bytes = serialize(&(utxo.tx_hash, utxo.output_index)).map_err(ConflictableTransactionError::Abort)?;
+        let value_bytes = serialize(utxo).map_err(ConflictableTransactionError::Abort)?;
+        self.utxos.insert(key_bytes, value_bytes)?;
+        self.owner_index.insert(owner_key(utxo).map_err(ConflictableTransactionError::Abort)?, &[])?;
+
+        let mut indices = self.tx_outputs(utxo.tx_hash)?;
+        if let Err(pos) = indices.binary_search(&utxo.output_index) {
+            indices.insert(pos, utxo.output_index);
+            self.set_tx_outputs(utxo.tx_hash, &indices)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a UTXO record and its index entry, returning the removed record
+    fn take(&self, tx_hash: H256, output_index: u32) -> ConflictableTransactionResult<Option<Utxo>, StoreError> {
+        let key_bytes = serialize(&(tx_hash, output_index)).map_err(ConflictableTransactionError::Abort)?;
+        let removed = match self.utxos.remove(key_bytes)? {
+            Some(value) => deserialize::<Utxo>(&value).map_err(ConflictableTransactionError::Abort)?,
+            None => return Ok(None),
+        };
+        self.owner_index.remove(owner_key(&removed).map_err(ConflictableTransactionError::Abort)?)?;
+
+        let mut indices = self.tx_outputs(tx_hash)?;
+        if let Ok(pos) = indices.binary_search(&output_index) {
+            indices.remove(pos);
+            self.set_tx_outputs(tx_hash, &indices)?;
+        }
+        Ok(Some(removed))
+    }
+
+    /// Record a transaction as applied but not yet confirmed
+    fn mark_unconfirmed(&self, tx_hash: H256) -> ConflictableTransactionResult<(), StoreError> {
+        self.unconfirmed.insert(tx_hash.as_bytes(), &[])?;
+        Ok(())
+    }
+
+    /// Clear a transaction's unconfirmed record, returning whether it had one
+    fn clear_unconfirmed(&self, tx_hash: H256) -> ConflictableTransactionResult<bool, StoreError> {
+        Ok(self.unconfirmed.remove(tx_hash.as_bytes())?.is_some())
+    }
+
+    fn get_undo(&self, height: u32) -> ConflictableTransactionResult<Option<BlockUndo>, StoreError> {
+        match self.undo.get(height.to_be_bytes())? {
+            Some(value) => Ok(Some(deserialize(&value).map_err(ConflictableTransactionError::Abort)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn put_undo(&self, undo: &BlockUndo) -> ConflictableTransactionResult<(), StoreError> {
+        let value = serialize(undo).map_err(ConflictableTransactionError::Abort)?;
+        self.undo.insert(&undo.height.to_be_bytes(), value)?;
+        Ok(())
+    }
 }
 
 impl SdbStore {
     /// Initialize persistent storage with sled database
-    /// 
+    ///
     /// # Arguments
     /// * `path` - Filesystem path for database storage
-    /// 
+    ///
     /// # Example
     /// ```no_run
     /// use std::path::Path;
     /// use state_channel_node::utxo::store::SdbStore;
-    /// 
+    ///
     /// let store = SdbStore::new(Path::new("./utxo-db")).unwrap();
     /// ```
     pub fn new(path: &Path) -> Result<Self, StoreError> {
         let db = sled::open(path)
             .map_err(|e| StoreError::StorageError(e.to_string()))?;
-            
-        Ok(Self { db })
+        let tx_index = db.open_tree(TX_INDEX_TREE)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+        let owner_index = db.open_tree(OWNER_INDEX_TREE)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+        let undo = db.open_tree(UNDO_TREE)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+        let unconfirmed = db.open_tree(UNCONFIRMED_TREE)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+        let meta = db.open_tree(META_TREE)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+
+        let store = Self { db, tx_index, owner_index, undo, unconfirmed, meta };
+        if store.owner_index.is_empty() && !store.db.is_empty() {
+            store.rebuild_owner_index()?;
+        }
+        if store.unconfirmed.is_empty() && !store.db.is_empty() {
+            store.rebuild_unconfirmed()?;
+        }
+        Ok(store)
     }
 
-    /// Add transaction outputs to UTXO set with batch insertion
-    /// 
-    /// # Arguments
-    /// * `tx` - Transaction containing outputs to add
-    pub fn add_outputs(&mut self, tx: &Transaction) -> Result<(), StoreError> {
+    /// Populate the owner index from the UTXO records of a database created
+    /// before the index existed
+    fn rebuild_owner_index(&self) -> Result<(), StoreError> {
         let mut batch = sled::Batch::default();
+        for utxo in self.all_utxos()? {
+            batch.insert(owner_key(&utxo)?, &[]);
+        }
+        self.owner_index.apply_batch(batch)
+            .map_err(|e| StoreError::StorageError(e.to_string()))
+    }
 
-        for (index, output) in tx.outputs.iter().enumerate() {
-            let key = (tx.hash, index as u32);
-            let key_bytes = self.serialize(&key)?;
-            let value_bytes = self.serialize(output)?;
-
-            batch.insert(key_bytes, value_bytes);
+    /// Record the transactions of unconfirmed UTXOs in a database created
+    /// before unconfirmed transactions were tracked
+    fn rebuild_unconfirmed(&self) -> Result<(), StoreError> {
+        let mut batch = sled::Batch::default();
+        for utxo in self.all_utxos()? {
+            if !utxo.is_confirmed {
+                batch.insert(utxo.tx_hash.as_bytes(), &[]);
+            }
         }
+        self.unconfirmed.apply_batch(batch)
+            .map_err(|e| StoreError::StorageError(e.to_string()))
+    }
 
-        self.db.apply_batch(batch)
-            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+    /// Add transaction outputs to UTXO set in a single atomic transaction
+    ///
+    /// Each output is stored as a full `Utxo` record so that block height and
+    /// confirmation state survive a restart.
+    ///
+    /// # Arguments
+    /// * `tx` - Transaction containing outputs to add
+    /// * `block_height` - Height of the containing block, `None` if unconfirmed
+    pub fn add_outputs(&mut self, tx: &Transaction, block_height: Option<u32>) -> Result<(), StoreError> {
+        let utxos = Utxo::from_transaction(tx, block_height);
 
-        Ok(())
+        self.transaction(|trees| {
+            for utxo in &utxos {
+                trees.put(utxo)?;
+            }
+            if block_height.is_none() {
+                trees.mark_unconfirmed(tx.txid())?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Insert or overwrite full UTXO records in a single atomic transaction
+    ///
+    /// Used to write back records that were only held in memory. The
+    /// transactions of unconfirmed records are recorded as unconfirmed.
+    pub fn put_utxos(&mut self, utxos: &[Utxo]) -> Result<(), StoreError> {
+        self.transaction(|trees| {
+            for utxo in utxos {
+                trees.put(utxo)?;
+                if !utxo.is_confirmed {
+                    trees.mark_unconfirmed(utxo.tx_hash)?;
+                }
+            }
+            Ok(())
+        })
     }
 
     /// Remove spent inputs from UTXO set with atomic operations
-    /// 
+    ///
     /// # Arguments
     /// * `inputs` - List of inputs to remove
     pub fn remove_inputs(&mut self, inputs: &[Input]) -> Result<(), StoreError> {
-        let mut batch = sled::Batch::default();
+        self.transaction(|trees| {
+            for input in inputs {
+                trees.take(input.previous_output, input.index)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Spend a transaction's inputs and create its outputs in one sled transaction
+    ///
+    /// Either every input is removed and every output inserted, or nothing is
+    /// written. Returns the spent UTXO records in input order.
+    ///
+    /// # Errors
+    /// * `DuplicateInput` if the transaction spends the same outpoint twice
+    /// * `InputNotFound` if any input is missing from the UTXO set
+    pub fn apply_transaction(&mut self, tx: &Transaction, block_height: Option<u32>) -> Result<Vec<Utxo>, StoreError> {
+        check_duplicate_inputs(&tx.inputs)?;
+
+        let created = Utxo::from_transaction(tx, block_height);
+
+        self.transaction(|trees| {
+            let mut spent = Vec::with_capacity(tx.inputs.len());
+            for input in &tx.inputs {
+                match trees.take(input.previous_output, input.index)? {
+                    Some(utxo) => spent.push(utxo),
+                    None => {
+                        return Err(ConflictableTransactionError::Abort(
+                            StoreError::InputNotFound(input.previous_output, input.index),
+                        ))
+                    }
+                }
+            }
+            for utxo in &created {
+                trees.put(utxo)?;
+            }
+            if block_height.is_none() {
+                trees.mark_unconfirmed(tx.txid())?;
+            }
+            Ok(spent)
+        })
+    }
+
+    /// Connect a block at `height`, recording undo data for an exact rollback
+    ///
+    /// Transactions recorded as applied while unconfirmed (e.g. added from the
+    /// mempool) only have their remaining outputs confirmed, even if all of
+    /// them were spent in the meantime; all others have their inputs spent and
+    /// outputs created at `height`. The whole block is applied
+    /// in one sled transaction together with its undo record.
+    ///
+    /// # Errors
+    /// * `InvalidBlockHeight` if `height` doesn't extend the current tip
+    /// * `InputNotFound` / `DuplicateInput` if any transaction spends invalid inputs
+    /// * `OutputExists` if a transaction's outputs are already confirmed
+    pub fn connect_block(&mut self, height: u32, txs: &[Transaction]) -> Result<BlockUndo, StoreError> {
+        if let Some(tip) = self.chain_tip()? {
+            if height != tip.wrapping_add(1) {
+                return Err(StoreError::InvalidBlockHeight { expected: tip.wrapping_add(1), got: height });
+            }
+        }
+        for tx in txs {
+            check_duplicate_inputs(&tx.inputs)?;
+        }
 
-        for input in inputs {
-            let key = (input.previous_output, input.index);
-            let key_bytes = self.serialize(&key)?;
-            batch.remove(key_bytes);
+        self.transaction(|trees| {
+            let mut transactions = Vec::with_capacity(txs.len());
+            for tx in txs {
+                let txid = tx.txid();
+                let was_unconfirmed = trees.clear_unconfirmed(txid)?;
+                let mut previously_unconfirmed = Vec::new();
+                let mut spent = Vec::new();
+
+                if !was_unconfirmed {
+                    // New transaction: spend its inputs and create confirmed outputs
+                    for input in &tx.inputs {
+                        match trees.take(input.previous_output, input.index)? {
+                            Some(utxo) => spent.push(utxo),
+                            None => {
+                                return Err(ConflictableTransactionError::Abort(
+                                    StoreError::InputNotFound(input.previous_output, input.index),
+                                ))
+                            }
+                        }
+                    }
+                    for utxo in Utxo::from_transaction(tx, Some(height)) {
+                        trees.put(&utxo)?;
+                    }
+                } else {
+                    // Already applied while unconfirmed: only confirm its outputs
+                    for index in trees.tx_outputs(txid)? {
+                        if let Some(mut utxo) = trees.get(txid, index)? {
+                            if utxo.is_confirmed {
+                                return Err(ConflictableTransactionError::Abort(StoreError::OutputExists));
+                            }
+                            utxo.block_height = height;
+                            utxo.is_confirmed = true;
+                            trees.put(&utxo)?;
+                            previously_unconfirmed.push(index);
+                        }
+                    }
+                }
+
+                transactions.push(TxUndo {
+                    tx_hash: txid,
+                    output_count: tx.outputs.len() as u32,
+                    was_unconfirmed,
+                    previously_unconfirmed,
+                    spent,
+                });
+            }
+
+            let undo = BlockUndo { height, transactions };
+            trees.put_undo(&undo)?;
+            Ok(undo)
+        })
+    }
+
+    /// Disconnect the block at the chain tip using its recorded undo data
+    ///
+    /// Outputs created by the block are removed and the UTXOs it spent are
+    /// restored. Transactions that were unconfirmed before the block revert to
+    /// unconfirmed. Returns the undo record that was applied.
+    ///
+    /// # Errors
+    /// * `NotChainTip` if `height` is not the most recently connected block
+    /// * `MissingUndoData` if no undo record exists for `height`
+    pub fn disconnect_block(&mut self, height: u32) -> Result<BlockUndo, StoreError> {
+        let tip = self.chain_tip()?;
+        if tip != Some(height) {
+            return Err(StoreError::NotChainTip { height, tip });
         }
 
-        self.db.apply_batch(batch)
+        self.transaction(|trees| {
+            let undo = trees.get_undo(height)?
+                .ok_or(ConflictableTransactionError::Abort(StoreError::MissingUndoData(height)))?;
+
+            for tx_undo in undo.transactions.iter().rev() {
+                if tx_undo.was_unconfirmed {
+                    for &index in &tx_undo.previously_unconfirmed {
+                        if let Some(mut utxo) = trees.get(tx_undo.tx_hash, index)? {
+                            utxo.block_height = 0;
+                            utxo.is_confirmed = false;
+                            trees.put(&utxo)?;
+                        }
+                    }
+                    trees.mark_unconfirmed(tx_undo.tx_hash)?;
+                } else {
+                    for index in 0..tx_undo.output_count {
+                        trees.take(tx_undo.tx_hash, index)?;
+                    }
+                    for utxo in &tx_undo.spent {
+                        trees.put(utxo)?;
+                    }
+                }
+            }
+
+            trees.undo.remove(&height.to_be_bytes())?;
+            Ok(undo)
+        })
+    }
+
+    /// Height of the most recently connected block, if any
+    ///
+    /// After a snapshot import this is the snapshot height until a later block
+    /// is connected.
+    pub fn chain_tip(&self) -> Result<Option<u32>, StoreError> {
+        let last = self.undo.last().map_err(|e| StoreError::StorageError(e.to_string()))?;
+        let connected = last.map(|(key, _)| decode_height(&key));
+        let checkpoint = self.meta.get(CHECKPOINT_HEIGHT_KEY)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?
+            .map(|value| decode_height(&value));
+
+        Ok(connected.max(checkpoint))
+    }
+
+    /// Get the undo record of a connected block
+    pub fn block_undo(&self, height: u32) -> Result<Option<BlockUndo>, StoreError> {
+        match self.undo.get(height.to_be_bytes()).map_err(|e| StoreError::StorageError(e.to_string()))? {
+            Some(value) => Ok(Some(deserialize(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Read every UTXO in storage key order
+    pub fn all_utxos(&self) -> Result<Vec<Utxo>, StoreError> {
+        self.db.iter()
+            .map(|entry| {
+                let (_, value) = entry.map_err(|e| StoreError::StorageError(e.to_string()))?;
+                deserialize(&value)
+            })
+            .collect()
+    }
+
+    /// Compute the Merkle root of the current UTXO set
+    pub fn merkle_root(&self) -> Result<H256, StoreError> {
+        snapshot::merkle_root(&self.all_utxos()?)
+    }
+
+    /// Write a versioned, checksummed snapshot of the whole UTXO set to `path`
+    ///
+    /// The file is written to a temporary sibling and renamed into place so a
+    /// crash never leaves a partial snapshot behind. Returns the Merkle root
+    /// recorded in the snapshot.
+    pub fn export_snapshot(&self, path: &Path) -> Result<H256, StoreError> {
+        let snapshot = UtxoSnapshot::new(self.chain_tip()?, self.all_utxos()?)?;
+        let bytes = snapshot.encode()?;
+
+        let tmp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp_path)
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| StoreError::StorageError(e.to_string()))?;
+        fs::rename(&tmp_path, path)
             .map_err(|e| StoreError::StorageError(e.to_string()))?;
 
-        Ok(())
+        Ok(snapshot.merkle_root)
+    }
+
+    /// Replace the live UTXO set with the contents of a snapshot file
+    ///
+    /// The snapshot's checksum and Merkle root are verified before anything is
+    /// written; if `expected_root` is given (e.g. a trusted checkpoint) it must
+    /// match as well. Undo data is discarded, so blocks at or below the
+    /// snapshot height can no longer be disconnected.
+    pub fn import_snapshot(&mut self, path: &Path, expected_root: Option<H256>) -> Result<UtxoSnapshot, StoreError> {
+        let bytes = fs::read(path).map_err(|e| StoreError::StorageError(e.to_string()))?;
+        let snapshot = UtxoSnapshot::decode(&bytes)?;
+
+        if let Some(expected) = expected_root {
+            if expected != snapshot.merkle_root {
+                return Err(StoreError::SnapshotRootMismatch { expected, actual: snapshot.merkle_root });
+            }
+        }
+
+        let existing_utxos = collect_keys(&self.db)?;
+        let existing_undo = collect_keys(&self.undo)?;
+        let existing_unconfirmed = collect_keys(&self.unconfirmed)?;
+
+        self.transaction(|trees| {
+            for key in &existing_utxos {
+                let (tx_hash, output_index): (H256, u32) =
+                    deserialize(key).map_err(ConflictableTransactionError::Abort)?;
+                trees.take(tx_hash, output_index)?;
+            }
+            for key in &existing_undo {
+                trees.undo.remove(key.as_slice())?;
+            }
+            for key in &existing_unconfirmed {
+                trees.unconfirmed.remove(key.as_slice())?;
+            }
+            for utxo in &snapshot.utxos {
+                trees.put(utxo)?;
+                if !utxo.is_confirmed {
+                    trees.mark_unconfirmed(utxo.tx_hash)?;
+                }
+            }
+            match snapshot.height {
+                Some(height) => trees.meta.insert(CHECKPOINT_HEIGHT_KEY, &height.to_be_bytes())?,
+                None => trees.meta.remove(CHECKPOINT_HEIGHT_KEY)?,
+            };
+            Ok(())
+        })?;
+
+        Ok(snapshot)
+    }
+
+    /// Mark every unspent output of a transaction as confirmed at `block_height`
+    ///
+    /// Outputs are located through the transaction hash index and updated in a
+    /// single atomic transaction, and the transaction's unconfirmed record is
+    /// cleared. Returns the updated records.
+    ///
+    /// # Arguments
+    /// * `tx_hash` - Hash of the transaction being confirmed
+    /// * `block_height` - Height of the block that includes it
+    pub fn confirm_outputs(&mut self, tx_hash: H256, block_height: u32) -> Result<Vec<Utxo>, StoreError> {
+        self.transaction(|trees| {
+            trees.clear_unconfirmed(tx_hash)?;
+            let mut confirmed = Vec::new();
+            for index in trees.tx_outputs(tx_hash)? {
+                if let Some(mut utxo) = trees.get(tx_hash, index)? {
+                    utxo.block_height = block_height;
+                    utxo.is_confirmed = true;
+                    trees.put(&utxo)?;
+                    confirmed.push(utxo);
+                }
+            }
+            Ok(confirmed)
+        })
     }
 
     /// Check if input exists in UTXO set
-    /// 
+    ///
     /// # Arguments
     /// * `input` - Input to verify existence
     pub fn contains_input(&self, input: &Input) -> Result<bool, StoreError> {
         let key = (input.previous_output, input.index);
-        let key_bytes = self.serialize(&key)?;
-        
+        let key_bytes = serialize(&key)?;
+
         self.db.contains_key(key_bytes)
             .map_err(|e| StoreError::StorageError(e.to_string()))
     }
 
+    /// Look up a UTXO by its transaction hash and output index
+    ///
+    /// # Arguments
+    /// * `tx_hash` - Hash of the transaction that created the output
+    /// * `output_index` - Index of the output in that transaction
+    pub fn get_utxo(&self, tx_hash: H256, output_index: u32) -> Result<Option<Utxo>, StoreError> {
+        let key_bytes = serialize(&(tx_hash, output_index))?;
+
+        match self.db.get(key_bytes).map_err(|e| StoreError::StorageError(e.to_string()))? {
+            Some(value) => Ok(Some(deserialize(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Look up only the output of a UTXO by its transaction hash and output index
+    pub fn get_output(&self, tx_hash: H256, output_index: u32) -> Result<Option<Output>, StoreError> {
+        Ok(self.get_utxo(tx_hash, output_index)?.map(|utxo| utxo.output))
+    }
+
+    /// Get the indices of a transaction's outputs that are still unspent
+    pub fn unspent_outputs_of(&self, tx_hash: H256) -> Result<Vec<u32>, StoreError> {
+        match self.tx_index.get(tx_hash.as_bytes()).map_err(|e| StoreError::StorageError(e.to_string()))? {
+            Some(value) => deserialize(&value),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Get every unspent output locked to `public_key_hash`, in outpoint order
+    ///
+    /// Served from the owner index, so the cost depends on the number of
+    /// outputs the owner holds rather than the size of the UTXO set.
+    pub fn utxos_for_owner(&self, public_key_hash: &[u8]) -> Result<Vec<Utxo>, StoreError> {
+        let prefix = serialize(public_key_hash)?;
+
+        let mut utxos = Vec::new();
+        for key in self.owner_index.scan_prefix(&prefix).keys() {
+            let key = key.map_err(|e| StoreError::StorageError(e.to_string()))?;
+            let (tx_hash, output_index) = decode_outpoint(&key[prefix.len()..]);
+            let utxo = self.get_utxo(tx_hash, output_index)?
+                .ok_or(StoreError::OutputNotFound(tx_hash))?;
+            utxos.push(utxo);
+        }
+        Ok(utxos)
+    }
+
+    /// Total value of every unspent output locked to `public_key_hash`
+    ///
+    /// Includes unconfirmed outputs; filter `utxos_for_owner` on
+    /// `is_confirmed` for a confirmed-only balance.
+    pub fn balance_of(&self, public_key_hash: &[u8]) -> Result<u64, StoreError> {
+        Ok(self.utxos_for_owner(public_key_hash)?
+            .iter()
+            .fold(0u64, |total, utxo| total.saturating_add(utxo.output.value)))
+    }
+
     /// Get current UTXO count with proper error handling
     pub fn len(&self) -> Result<usize, StoreError> {
         Ok(self.db.len())
@@ -110,9 +644,75 @@ impl SdbStore {
         Ok(self.db.len() == 0)
     }
 
-    /// Generic serialization helper with unified error handling
-    fn serialize<T: serde::Serialize>(&self, value: &T) -> Result<Vec<u8>, StoreError> {
-        bincode::serialize(value)
-            .map_err(|e| StoreError::SerializationError(e.to_string()))
+    /// Run `f` atomically across every tree backing the UTXO set
+    fn transaction<T, F>(&self, f: F) -> Result<T, StoreError>
+    where
+        F: Fn(&UtxoTrees) -> ConflictableTransactionResult<T, StoreError>,
+    {
+        let utxos: &Tree = &self.db;
+        (utxos, &self.tx_index, &self.owner_index, &self.undo, &self.unconfirmed, &self.meta)
+            .transaction(|(utxos, tx_index, owner_index, undo, unconfirmed, meta)| {
+                f(&UtxoTrees { utxos, tx_index, owner_index, undo, unconfirmed, meta })
+            })
+            .map_err(|e| match e {
+                TransactionError::Abort(e) => e,
+                TransactionError::Storage(e) => StoreError::StorageError(e.to_string()),
+            })
     }
 }
+
+/// Reject transactions that reference the same outpoint more than once
+fn check_duplicate_inputs(inputs: &[Input]) -> Result<(), StoreError> {
+    let mut seen = HashSet::with_capacity(inputs.len());
+    for input in inputs {
+        if !seen.insert((input.previous_output, input.index)) {
+            return Err(StoreError::DuplicateInput(input.previous_output, input.index));
+        }
+    }
+    Ok(())
+}
+
+/// Decode a big-endian block height key
+fn decode_height(bytes: &[u8]) -> u32 {
+    let mut height = [0u8; 4];
+    height.copy_from_slice(&bytes[..4]);
+    u32::from_be_bytes(height)
+}
+
+/// Owner index key: serialized public key hash, then tx hash and big-endian index
+///
+/// The public key hash is length-prefixed by bincode, so a prefix scan on one
+/// owner can never match a longer hash that happens to share leading bytes.
+fn owner_key(utxo: &Utxo) -> Result<Vec<u8>, StoreError> {
+    let mut key = serialize(utxo.output.public_key_hash.as_slice())?;
+    key.extend_from_slice(utxo.tx_hash.as_bytes());
+    key.extend_from_slice(&utxo.output_index.to_be_bytes());
+    Ok(key)
+}
+
+/// Decode the outpoint suffix of an owner index key
+fn decode_outpoint(bytes: &[u8]) -> (H256, u32) {
+    let mut output_index = [0u8; 4];
+    output_index.copy_from_slice(&bytes[32..36]);
+    (H256::from_slice(&bytes[..32]), u32::from_be_bytes(output_index))
+}
+
+/// Collect every key of a tree so it can be cleared inside a transaction
+fn collect_keys(tree: &Tree) -> Result<Vec<Vec<u8>>, StoreError> {
+    tree.iter()
+        .keys()
+        .map(|key| key.map(|k| k.to_vec()).map_err(|e| StoreError::StorageError(e.to_string())))
+        .collect()
+}
+
+/// Generic serialization helper with unified error handling
+fn serialize<T: serde::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, StoreError> {
+    bincode::serialize(value)
+        .map_err(|e| StoreError::SerializationError(e.to_string()))
+}
+
+/// Generic deserialization helper with unified error handling
+fn deserialize<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, StoreError> {
+    bincode::deserialize(bytes)
+        .map_err(|e| StoreError::SerializationError(e.to_string()))
+}
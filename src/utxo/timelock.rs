@@ -0,0 +1,190 @@
+use super::models::{Transaction, Utxo};
+use super::validation::ValidationError;
+
+/// `lock_time` values below this are block heights, values at or above are
+/// UNIX timestamps
+pub const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+/// Sequence value that opts an input out of `lock_time` and relative locks
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+/// When set, the sequence number carries no relative lock (BIP68)
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+/// When set, the relative lock is in units of 512 seconds instead of blocks
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+/// Bits of the sequence number holding the relative lock value
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+/// Relative time locks are measured in units of `1 << 9` = 512 seconds
+pub const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+/// Transactions below this version don't enforce relative locks
+pub const RELATIVE_LOCKTIME_MIN_VERSION: u32 = 2;
+
+/// State of the chain a transaction is evaluated against
+///
+/// The transaction is checked for inclusion in the block after the tip,
+/// i.e. at height `height + 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainTip {
+    /// Height of the current tip
+    pub height: u32,
+    /// Median time past of the tip (median timestamp of the last 11 blocks)
+    pub median_time_past: u64,
+}
+
+/// A relative lock encoded in an input's sequence number (BIP68)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLock {
+    /// Spendable once the spent output has this many confirmations
+    Blocks(u16),
+    /// Spendable this many 512-second units after the spent output confirmed
+    Time(u16),
+}
+
+impl RelativeLock {
+    /// Decode the relative lock of a sequence number, `None` if disabled
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (sequence & SEQUENCE_LOCKTIME_MASK) as u16;
+        if sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLock::Time(value))
+        } else {
+            Some(RelativeLock::Blocks(value))
+        }
+    }
+
+    /// Encode as a sequence number
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            RelativeLock::Blocks(blocks) => blocks as u32,
+            RelativeLock::Time(units) => SEQUENCE_LOCKTIME_TYPE_FLAG | units as u32,
+        }
+    }
+
+    /// Lock duration in seconds for time locks, `None` for block locks
+    pub fn seconds(self) -> Option<u64> {
+        match self {
+            RelativeLock::Blocks(_) => None,
+            RelativeLock::Time(units) => Some((units as u64) << SEQUENCE_LOCKTIME_GRANULARITY),
+        }
+    }
+}
+
+/// Check both absolute and relative timelocks of a transaction
+///
+/// `spent` holds the UTXOs spent by `tx`, in input order. Time-based relative
+/// locks start from the median time past of the block *before* the one that
+/// confirmed the spent output, which `median_time_past_at(height)` must
+/// supply; it is not called for block-based locks.
+///
+/// # Errors
+/// * `LockTimeNotReached` if `lock_time` is in the future
+/// * `SequenceLockNotReached` if an input's relative lock hasn't matured
+/// * `MissingInput` if `spent` has no entry for an input
+/// * `MissingBlockTime` if a needed median time past is unavailable
+pub fn validate_timelocks<F>(
+    tx: &Transaction,
+    spent: &[Utxo],
+    tip: &ChainTip,
+    median_time_past_at: F,
+) -> Result<(), ValidationError>
+where
+    F: Fn(u32) -> Option<u64>,
+{
+    check_lock_time(tx, tip)?;
+    check_sequence_locks(tx, spent, tip, median_time_past_at)
+}
+
+/// Check `lock_time` against the next block (absolute lock)
+///
+/// Heights are compared with the next block height and timestamps with the
+/// tip's median time past (BIP113). A transaction whose inputs all use
+/// `SEQUENCE_FINAL` ignores its `lock_time`.
+pub fn check_lock_time(tx: &Transaction, tip: &ChainTip) -> Result<(), ValidationError> {
+    if tx.lock_time == 0 || tx.inputs.iter().all(|input| input.sequence == SEQUENCE_FINAL) {
+        return Ok(());
+    }
+
+    let reference = if tx.lock_time < LOCKTIME_THRESHOLD {
+        tip.height as u64 + 1
+    } else {
+        tip.median_time_past
+    };
+    if tx.lock_time >= reference {
+        return Err(ValidationError::LockTimeNotReached { lock_time: tx.lock_time });
+    }
+    Ok(())
+}
+
+/// Check every input's relative lock against the age of its spent output
+///
+/// Only enforced for transactions of version 2 or higher (BIP68). Outputs
+/// that are still unconfirmed count as confirmed in the next block.
+pub fn check_sequence_locks<F>(
+    tx: &Transaction,
+    spent: &[Utxo],
+    tip: &ChainTip,
+    median_time_past_at: F,
+) -> Result<(), ValidationError>
+where
+    F: Fn(u32) -> Option<u64>,
+{
+    if tx.version < RELATIVE_LOCKTIME_MIN_VERSION {
+        return Ok(());
+    }
+
+    let next_height = tip.height as u64 + 1;
+    for (index, input) in tx.inputs.iter().enumerate() {
+        let Some(lock) = RelativeLock::from_sequence(input.sequence) else { continue };
+        let utxo = spent.get(index)
+            .ok_or(ValidationError::MissingInput(input.previous_output, input.index))?;
+        let coin_height = if utxo.is_confirmed { utxo.block_height as u64 } else { next_height };
+
+        let matured = match lock {
+            RelativeLock::Blocks(blocks) => next_height >= coin_height + blocks as u64,
+            RelativeLock::Time(_) => {
+                let coin_time = if utxo.is_confirmed {
+                    let prior = utxo.block_height.saturating_sub(1);
+                    median_time_past_at(prior).ok_or(ValidationError::MissingBlockTime(prior))?
+                } else {
+                    tip.median_time_past
+                };
+                tip.median_time_past >= coin_time + lock.seconds().unwrap_or(0)
+            }
+        };
+        if !matured {
+            return Err(ValidationError::SequenceLockNotReached { input: index, lock });
+        }
+    }
+    Ok(())
+}
+
+/// Whether `tx` satisfies a required absolute lock, per CHECKLOCKTIMEVERIFY (BIP65)
+///
+/// `required` must be of the same kind (height or time) as `lock_time` and
+/// not exceed it, and the input must not be final, otherwise `lock_time`
+/// would not be enforced.
+pub fn lock_time_satisfies(tx: &Transaction, input_index: usize, required: u64) -> bool {
+    let same_kind = (required < LOCKTIME_THRESHOLD) == (tx.lock_time < LOCKTIME_THRESHOLD);
+    let not_final = tx.inputs.get(input_index)
+        .is_some_and(|input| input.sequence != SEQUENCE_FINAL);
+    same_kind && required <= tx.lock_time && not_final
+}
+
+/// Whether input `input_index` satisfies a required relative lock, per
+/// CHECKSEQUENCEVERIFY (BIP112)
+///
+/// A disabled `required` lock always passes. Otherwise the input's own lock
+/// must be enabled, of the same type and at least as long.
+pub fn sequence_satisfies(tx: &Transaction, input_index: usize, required: u32) -> bool {
+    let Some(required) = RelativeLock::from_sequence(required) else { return true };
+    if tx.version < RELATIVE_LOCKTIME_MIN_VERSION {
+        return false;
+    }
+    let Some(input) = tx.inputs.get(input_index) else { return false };
+
+    match (RelativeLock::from_sequence(input.sequence), required) {
+        (Some(RelativeLock::Blocks(have)), RelativeLock::Blocks(need)) => have >= need,
+        (Some(RelativeLock::Time(have)), RelativeLock::Time(need)) => have >= need,
+        _ => false,
+    }
+}
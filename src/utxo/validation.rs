@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+
+use primitive_types::H256;
+use thiserror::Error;
+
+use super::cache::{CacheError, UtxoCache};
+use super::models::{SighashType, Transaction, Utxo};
+use super::script::{self, ScriptError};
+use super::timelock::{self, ChainTip, RelativeLock};
+use crate::crypto::{KeyPair, PublicKey, Signature};
+
+/// Length of a serialized Ed25519 public key
+const PUBLIC_KEY_LEN: usize = 32;
+/// Length of a serialized Ed25519 signature
+const SIGNATURE_LEN: usize = 64;
+/// Length of a pay-to-public-key-hash input signature:
+/// `public key || signature || sighash type`
+pub const P2PKH_SIGNATURE_LEN: usize = PUBLIC_KEY_LEN + SIGNATURE_LEN + 1;
+
+/// Reasons a transaction is rejected
+///
+/// Each variant maps to a stable error code via [`ValidationError::code`],
+/// matching the codes listed in API_SPEC.md where one exists.
+#[derive(Error, Debug)]
+pub enum ValidationError {
+    #[error("Transaction hash mismatch: declared {declared:?}, computed {computed:?}")]
+    HashMismatch { declared: H256, computed: H256 },
+    #[error("Transaction has no inputs")]
+    NoInputs,
+    #[error("Transaction has no outputs")]
+    NoOutputs,
+    #[error("Input {0:?}:{1} spent more than once")]
+    DuplicateInput(H256, u32),
+    #[error("Input {0:?}:{1} does not reference an unspent output")]
+    MissingInput(H256, u32),
+    #[error("Output value overflow")]
+    ValueOverflow,
+    #[error("Outputs ({outputs}) exceed inputs ({inputs})")]
+    Inflation { inputs: u64, outputs: u64 },
+    #[error("Input {0} has a malformed signature")]
+    MalformedSignature(usize),
+    #[error("Input {0} has an unknown sighash type or one that doesn't apply to it")]
+    InvalidSighash(usize),
+    #[error("Input {0} is signed by a key that doesn't own the spent output")]
+    OwnershipMismatch(usize),
+    #[error("Input {0} signature verification failed")]
+    InvalidSignature(usize),
+    #[error("Input {input} does not satisfy the spent output's lock script: {source}")]
+    Script { input: usize, source: ScriptError },
+    #[error("Lock time {lock_time} not reached")]
+    LockTimeNotReached { lock_time: u64 },
+    #[error("Input {input} relative lock {lock:?} not reached")]
+    SequenceLockNotReached { input: usize, lock: RelativeLock },
+    #[error("No median time past known for block {0}")]
+    MissingBlockTime(u32),
+    #[error("Cache error: {0}")]
+    Cache(#[from] CacheError),
+}
+
+impl ValidationError {
+    /// Stable, machine-readable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::HashMismatch { .. } => "INVALID_HASH",
+            ValidationError::NoInputs | ValidationError::NoOutputs => "EMPTY_TRANSACTION",
+            ValidationError::DuplicateInput(..) => "DUPLICATE_INPUT",
+            ValidationError::MissingInput(..) => "INVALID_ANCESTRY",
+            ValidationError::ValueOverflow => "VALUE_OVERFLOW",
+            ValidationError::Inflation { .. } => "INFLATION",
+            ValidationError::MalformedSignature(_) => "INVALID_SIGNATURE_FORMAT",
+            ValidationError::InvalidSighash(_) => "INVALID_SIGHASH",
+            ValidationError::OwnershipMismatch(_) => "INVALID_OWNERSHIP",
+            ValidationError::InvalidSignature(_) => "INVALID_SIGNATURE",
+            ValidationError::Script { source, .. } => match source {
+                ScriptError::LockTimeNotSatisfied | ScriptError::SequenceNotSatisfied => "INVALID_TIMELOCK",
+                _ => "INVALID_SCRIPT",
+            },
+            ValidationError::LockTimeNotReached { .. } => "INVALID_TIMELOCK",
+            ValidationError::SequenceLockNotReached { .. } => "INVALID_TIMELOCK",
+            ValidationError::MissingBlockTime(_) => "MISSING_BLOCK_TIME",
+            ValidationError::Cache(_) => "STORAGE_ERROR",
+        }
+    }
+}
+
+/// Validate a transaction against the current UTXO set
+///
+/// Runs [`validate_transaction_at`] for the block after the cache's
+/// [`chain_tip`](UtxoCache::chain_tip). The cache keeps no block times, so
+/// time-based locks are never satisfied here; validate those with
+/// `validate_transaction_at` and the chain's median time past.
+pub fn validate_transaction(tx: &Transaction, cache: &UtxoCache) -> Result<u64, ValidationError> {
+    let tip = ChainTip { height: cache.chain_tip()?.unwrap_or(0), median_time_past: 0 };
+    validate_transaction_at(tx, cache, &tip, |_| None)
+}
+
+/// Validate a transaction against the current UTXO set for inclusion in the
+/// block after `tip`
+///
+/// Checks, in order:
+/// 1. `hash` matches `calculate_hash()`
+/// 2. The transaction has inputs and outputs, and no input is spent twice
+/// 3. Every input references an unspent output (`INVALID_ANCESTRY`)
+/// 4. Outputs don't exceed inputs (no inflation)
+/// 5. Absolute and relative timelocks are satisfied (`INVALID_TIMELOCK`),
+///    with `median_time_past_at` supplying block times as in
+///    [`validate_timelocks`](timelock::validate_timelocks)
+/// 6. Every input satisfies the spent output's `lock_script`, with the
+///    input's `signature` field holding the unlock script. Outputs without a
+///    lock script instead require `public key || signature || sighash type`
+///    where the key hashes to the output's `public_key_hash` and the
+///    signature verifies over `sighash(index, sighash type)`
+///
+/// Nothing is modified. Returns the fee, i.e. inputs minus outputs.
+pub fn validate_transaction_at<F>(
+    tx: &Transaction,
+    cache: &UtxoCache,
+    tip: &ChainTip,
+    median_time_past_at: F,
+) -> Result<u64, ValidationError>
+where
+    F: Fn(u32) -> Option<u64>,
+{
+    let computed = tx.calculate_hash();
+    if tx.hash != computed {
+        return Err(ValidationError::HashMismatch { declared: tx.hash, computed });
+    }
+
+    if tx.inputs.is_empty() {
+        return Err(ValidationError::NoInputs);
+    }
+    if tx.outputs.is_empty() {
+        return Err(ValidationError::NoOutputs);
+    }
+
+    let mut seen = HashSet::with_capacity(tx.inputs.len());
+    for input in &tx.inputs {
+        if !seen.insert((input.previous_output, input.index)) {
+            return Err(ValidationError::DuplicateInput(input.previous_output, input.index));
+        }
+    }
+
+    let mut spent = Vec::with_capacity(tx.inputs.len());
+    for input in &tx.inputs {
+        let utxo = cache.get_utxo(input.previous_output, input.index)?
+            .ok_or(ValidationError::MissingInput(input.previous_output, input.index))?;
+        spent.push(utxo);
+    }
+
+    let input_value = checked_sum(spent.iter().map(|utxo| utxo.output.value))?;
+    let output_value = checked_sum(tx.outputs.iter().map(|output| output.value))?;
+    if output_value > input_value {
+        return Err(ValidationError::Inflation { inputs: input_value, outputs: output_value });
+    }
+
+    timelock::validate_timelocks(tx, &spent, tip, median_time_past_at)?;
+
+    for (index, utxo) in spent.iter().enumerate() {
+        verify_input(tx, index, utxo)?;
+    }
+
+    Ok(input_value - output_value)
+}
+
+/// Sign input `index` with `keypair`, committing to the parts of the
+/// transaction selected by `sighash_type`
+///
+/// The input's signature becomes `public key || signature || sighash type`.
+/// Signatures aren't part of the txid, so inputs can be signed in any order
+/// and `tx.hash` stays valid.
+///
+/// # Errors
+/// * `InvalidSighash` if `index` is out of range or `sighash_type` is
+///   `Single` without a matching output (see [`Transaction::sighash`])
+pub fn sign_input(
+    tx: &mut Transaction,
+    index: usize,
+    keypair: &KeyPair,
+    sighash_type: SighashType,
+) -> Result<(), ValidationError> {
+    let message = tx.sighash(index, sighash_type)
+        .ok_or(ValidationError::InvalidSighash(index))?;
+    let signature = keypair.sign(message.as_bytes());
+
+    let mut witness = Vec::with_capacity(P2PKH_SIGNATURE_LEN);
+    witness.extend_from_slice(&keypair.public_key().as_bytes());
+    witness.extend_from_slice(&signature.to_bytes());
+    witness.push(sighash_type.to_u8());
+    tx.inputs[index].signature = witness;
+    Ok(())
+}
+
+/// Check that input `index` is allowed to spend `utxo`
+fn verify_input(tx: &Transaction, index: usize, utxo: &Utxo) -> Result<(), ValidationError> {
+    let witness = &tx.inputs[index].signature;
+    if !utxo.output.lock_script.is_empty() {
+        return script::verify_script(witness, &utxo.output.lock_script, tx, index)
+            .map_err(|source| ValidationError::Script { input: index, source });
+    }
+
+    if witness.len() != P2PKH_SIGNATURE_LEN {
+        return Err(ValidationError::MalformedSignature(index));
+    }
+
+    let (key_bytes, rest) = witness.split_at(PUBLIC_KEY_LEN);
+    let (signature_bytes, sighash_byte) = rest.split_at(SIGNATURE_LEN);
+    let message = SighashType::from_u8(sighash_byte[0])
+        .and_then(|sighash_type| tx.sighash(index, sighash_type))
+        .ok_or(ValidationError::InvalidSighash(index))?;
+    let public_key = PublicKey::from_bytes(key_bytes)
+        .map_err(|_| ValidationError::MalformedSignature(index))?;
+    let signature = Signature::from_bytes(signature_bytes)
+        .map_err(|_| ValidationError::MalformedSignature(index))?;
+
+    if public_key.public_key_hash().as_slice() != utxo.output.public_key_hash.as_slice() {
+        return Err(ValidationError::OwnershipMismatch(index));
+    }
+    if !public_key.verify_strict(&signature, message.as_bytes()) {
+        return Err(ValidationError::InvalidSignature(index));
+    }
+    Ok(())
+}
+
+fn checked_sum(mut values: impl Iterator<Item = u64>) -> Result<u64, ValidationError> {
+    values.try_fold(0u64, |total, value| total.checked_add(value))
+        .ok_or(ValidationError::ValueOverflow)
+}
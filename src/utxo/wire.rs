@@ -0,0 +1,401 @@
+use primitive_types::H256;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::models::{Input, Output, Transaction};
+use super::script::Script;
+
+/// Marker byte that follows the version in the segwit serialization (BIP144)
+pub const SEGWIT_MARKER: u8 = 0x00;
+/// Flag byte that follows the marker; the only flag currently defined
+pub const SEGWIT_FLAG: u8 = 0x01;
+
+/// Length of a standard P2PKH `scriptPubKey`
+const P2PKH_SCRIPT_LEN: usize = 25;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum WireError {
+    #[error("Unexpected end of data")]
+    UnexpectedEof,
+    #[error("{0} trailing bytes after transaction")]
+    TrailingBytes(usize),
+    #[error("Non-canonical compact size encoding")]
+    NonCanonicalVarInt,
+    #[error("Count {0} exceeds the remaining data")]
+    CountTooLarge(u64),
+    #[error("Unknown segwit flag {0:#04x}")]
+    InvalidSegwitFlag(u8),
+    #[error("Segwit serialization without witness data")]
+    SuperfluousWitness,
+    #[error("Output value {0} out of range")]
+    ValueOutOfRange(u64),
+    #[error("Lock time {0} doesn't fit in 32 bits")]
+    LockTimeOutOfRange(u64),
+    #[error("Input {0} carries witness data, which Input can't represent")]
+    UnsupportedWitness(usize),
+    #[error("Invalid hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+}
+
+/// Transaction in Bitcoin's consensus serialization
+///
+/// Mirrors the wire format field for field, so decoding and re-encoding a raw
+/// transaction reproduces it byte for byte. Convert with
+/// [`WireTransaction::from_transaction`] and [`WireTransaction::into_transaction`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireTransaction {
+    pub version: u32,
+    pub inputs: Vec<WireInput>,
+    pub outputs: Vec<WireOutput>,
+    pub lock_time: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireInput {
+    /// Txid of the spent transaction, in internal (serialized) byte order
+    pub previous_output: H256,
+    pub index: u32,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    /// Witness stack, empty for non-segwit inputs
+    pub witness: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WireOutput {
+    /// Value in satoshis
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+impl WireTransaction {
+    /// Whether any input has witness data, i.e. the segwit serialization is used
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    /// Serialize, using the segwit format if any input has witness data
+    pub fn encode(&self) -> Vec<u8> {
+        self.serialize(self.has_witness())
+    }
+
+    /// Serialize without witness data, as hashed for the txid
+    pub fn encode_legacy(&self) -> Vec<u8> {
+        self.serialize(false)
+    }
+
+    /// Parse a complete transaction, rejecting trailing bytes
+    pub fn decode(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut reader = Reader { bytes };
+        let tx = Self::read(&mut reader)?;
+        if !reader.bytes.is_empty() {
+            return Err(WireError::TrailingBytes(reader.bytes.len()));
+        }
+        Ok(tx)
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.encode())
+    }
+
+    pub fn from_hex(raw: &str) -> Result<Self, WireError> {
+        Self::decode(&hex::decode(raw.trim())?)
+    }
+
+    /// Double SHA-256 of the serialization without witness data
+    ///
+    /// In internal byte order; block explorers display it reversed, see
+    /// [`WireTransaction::txid_hex`].
+    pub fn txid(&self) -> H256 {
+        double_sha256(&self.encode_legacy())
+    }
+
+    /// Double SHA-256 of the full serialization, equal to `txid` without witnesses
+    pub fn wtxid(&self) -> H256 {
+        double_sha256(&self.encode())
+    }
+
+    /// Txid in the reversed hex form used by Bitcoin nodes and explorers
+    pub fn txid_hex(&self) -> String {
+        let mut bytes = self.txid().to_fixed_bytes();
+        bytes.reverse();
+        hex::encode(bytes)
+    }
+
+    /// Map a [`Transaction`] to the wire format
+    ///
+    /// Input signatures become `scriptSig`s. Outputs with a `lock_script` use
+    /// it as `scriptPubKey`; outputs without one get the standard P2PKH script
+    /// for their `public_key_hash`.
+    ///
+    /// # Errors
+    /// * `LockTimeOutOfRange` if `lock_time` exceeds 32 bits
+    /// * `ValueOutOfRange` if an output value exceeds `i64::MAX`
+    pub fn from_transaction(tx: &Transaction) -> Result<Self, WireError> {
+        let lock_time = u32::try_from(tx.lock_time)
+            .map_err(|_| WireError::LockTimeOutOfRange(tx.lock_time))?;
+
+        let inputs = tx.inputs.iter()
+            .map(|input| WireInput {
+                previous_output: input.previous_output,
+                index: input.index,
+                script_sig: input.signature.clone(),
+                sequence: input.sequence,
+                witness: Vec::new(),
+            })
+            .collect();
+
+        let outputs = tx.outputs.iter()
+            .map(|output| {
+                check_value(output.value)?;
+                let script_pubkey = if output.lock_script.is_empty() {
+                    Script::p2pkh(&output.public_key_hash).into_bytes()
+                } else {
+                    output.lock_script.clone()
+                };
+                Ok(WireOutput { value: output.value, script_pubkey })
+            })
+            .collect::<Result<_, WireError>>()?;
+
+        Ok(Self { version: tx.version, inputs, outputs, lock_time })
+    }
+
+    /// Map to a [`Transaction`], the inverse of [`WireTransaction::from_transaction`]
+    ///
+    /// Standard P2PKH outputs become a `public_key_hash` with an empty
+    /// `lock_script`; any other `scriptPubKey` is kept as the lock script.
+    /// `hash` is set to the transaction's own txid, not the Bitcoin txid.
+    ///
+    /// # Errors
+    /// * `UnsupportedWitness` if an input has witness data
+    pub fn into_transaction(self) -> Result<Transaction, WireError> {
+        let inputs = self.inputs.into_iter().enumerate()
+            .map(|(index, input)| {
+                if !input.witness.is_empty() {
+                    return Err(WireError::UnsupportedWitness(index));
+                }
+                Ok(Input {
+                    previous_output: input.previous_output,
+                    index: input.index,
+                    signature: input.script_sig,
+                    sequence: input.sequence,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let outputs = self.outputs.into_iter()
+            .map(|output| match p2pkh_hash(&output.script_pubkey) {
+                Some(public_key_hash) => Output {
+                    value: output.value,
+                    public_key_hash: public_key_hash.to_vec(),
+                    lock_script: Vec::new(),
+                },
+                None => Output {
+                    value: output.value,
+                    public_key_hash: Vec::new(),
+                    lock_script: output.script_pubkey,
+                },
+            })
+            .collect();
+
+        let mut tx = Transaction {
+            version: self.version,
+            inputs,
+            outputs,
+            lock_time: self.lock_time as u64,
+            hash: H256::zero(),
+        };
+        tx.hash = tx.calculate_hash();
+        Ok(tx)
+    }
+
+    fn serialize(&self, with_witness: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.version.to_le_bytes());
+        if with_witness {
+            out.extend_from_slice(&[SEGWIT_MARKER, SEGWIT_FLAG]);
+        }
+
+        write_varint(&mut out, self.inputs.len() as u64);
+        for input in &self.inputs {
+            out.extend_from_slice(input.previous_output.as_bytes());
+            out.extend_from_slice(&input.index.to_le_bytes());
+            write_bytes(&mut out, &input.script_sig);
+            out.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+
+        write_varint(&mut out, self.outputs.len() as u64);
+        for output in &self.outputs {
+            out.extend_from_slice(&output.value.to_le_bytes());
+            write_bytes(&mut out, &output.script_pubkey);
+        }
+
+        if with_witness {
+            for input in &self.inputs {
+                write_varint(&mut out, input.witness.len() as u64);
+                for item in &input.witness {
+                    write_bytes(&mut out, item);
+                }
+            }
+        }
+
+        out.extend_from_slice(&self.lock_time.to_le_bytes());
+        out
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, WireError> {
+        let version = reader.read_u32()?;
+
+        // A zero input count can't start a valid transaction, so it marks segwit
+        let segwit = reader.bytes.first() == Some(&SEGWIT_MARKER);
+        if segwit {
+            reader.take(1)?;
+            let flag = reader.read_u8()?;
+            if flag != SEGWIT_FLAG {
+                return Err(WireError::InvalidSegwitFlag(flag));
+            }
+        }
+
+        // Each input is at least 41 bytes, each output at least 9
+        let input_count = reader.read_count(41)?;
+        let mut inputs = Vec::with_capacity(input_count);
+        for _ in 0..input_count {
+            inputs.push(WireInput {
+                previous_output: H256::from_slice(reader.take(32)?),
+                index: reader.read_u32()?,
+                script_sig: reader.read_bytes()?,
+                sequence: reader.read_u32()?,
+                witness: Vec::new(),
+            });
+        }
+
+        let output_count = reader.read_count(9)?;
+        let mut outputs = Vec::with_capacity(output_count);
+        for _ in 0..output_count {
+            let value = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+            check_value(value)?;
+            outputs.push(WireOutput { value, script_pubkey: reader.read_bytes()? });
+        }
+
+        if segwit {
+            for input in &mut inputs {
+                let items = reader.read_count(1)?;
+                input.witness = (0..items).map(|_| reader.read_bytes()).collect::<Result<_, _>>()?;
+            }
+            if inputs.iter().all(|input| input.witness.is_empty()) {
+                return Err(WireError::SuperfluousWitness);
+            }
+        }
+
+        let lock_time = reader.read_u32()?;
+        Ok(Self { version, inputs, outputs, lock_time })
+    }
+}
+
+/// Append a Bitcoin compact size integer
+pub fn write_varint(out: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0xfc => out.push(value as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+/// Read a compact size integer from the front of `bytes`
+///
+/// Returns the value and the number of bytes consumed. Encodings longer than
+/// necessary are rejected, as Bitcoin nodes do.
+pub fn read_varint(bytes: &[u8]) -> Result<(u64, usize), WireError> {
+    let mut reader = Reader { bytes };
+    let value = reader.read_varint()?;
+    Ok((value, bytes.len() - reader.bytes.len()))
+}
+
+fn write_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    write_varint(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn check_value(value: u64) -> Result<(), WireError> {
+    if value > i64::MAX as u64 {
+        return Err(WireError::ValueOutOfRange(value));
+    }
+    Ok(())
+}
+
+/// Public key hash of a standard P2PKH `scriptPubKey`
+fn p2pkh_hash(script_pubkey: &[u8]) -> Option<&[u8]> {
+    if script_pubkey.len() != P2PKH_SCRIPT_LEN {
+        return None;
+    }
+    let hash = &script_pubkey[3..23];
+    (Script::p2pkh(hash).as_bytes() == script_pubkey).then_some(hash)
+}
+
+fn double_sha256(data: &[u8]) -> H256 {
+    H256::from_slice(&Sha256::digest(Sha256::digest(data)))
+}
+
+/// Cursor over the bytes left to decode
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        if self.bytes.len() < len {
+            return Err(WireError::UnexpectedEof);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, WireError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, WireError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_varint(&mut self) -> Result<u64, WireError> {
+        let (value, min) = match self.read_u8()? {
+            0xfd => (u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as u64, 0xfd),
+            0xfe => (u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as u64, 0x1_0000),
+            0xff => (u64::from_le_bytes(self.take(8)?.try_into().unwrap()), 0x1_0000_0000),
+            byte => return Ok(byte as u64),
+        };
+        if value < min {
+            return Err(WireError::NonCanonicalVarInt);
+        }
+        Ok(value)
+    }
+
+    /// Read a count of items that each take at least `min_item_len` bytes
+    ///
+    /// Bounding the count by the remaining data keeps a forged count from
+    /// triggering a huge allocation.
+    fn read_count(&mut self, min_item_len: usize) -> Result<usize, WireError> {
+        let count = self.read_varint()?;
+        if count > (self.bytes.len() / min_item_len) as u64 {
+            return Err(WireError::CountTooLarge(count));
+        }
+        Ok(count as usize)
+    }
+
+    fn read_bytes(&mut self) -> Result<Vec<u8>, WireError> {
+        let len = self.read_count(1)?;
+        Ok(self.take(len)?.to_vec())
+    }
+}
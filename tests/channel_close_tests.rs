@@ -0,0 +1,90 @@
+mod common;
+
+use std::path::PathBuf;
+use state_channel_node::channel::close::{self, CloseError, CooperativeClose};
+use state_channel_node::channel::state::ChannelStatus;
+use state_channel_node::crypto::{self, KeyPair};
+use common::channel::{open_channel, transfer};
+use common::test_utils;
+
+#[test]
+fn test_cooperative_close_settles_channel() {
+    let test_db_path = PathBuf::from("test_close_settle.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let carol = crypto::generate_keypair();
+    let (cache, mut channel, secrets) = open_channel(&test_db_path, &[&alice, &bob, &carol]);
+    transfer(&mut channel, &secrets, &alice, &bob, 20_000);
+
+    let mut close = CooperativeClose::propose(&channel, 1).unwrap();
+    assert!(matches!(
+        close::close_channel(&mut channel, &close, &cache),
+        Err(CloseError::MissingSignature(_))
+    ));
+    for kp in [&alice, &bob, &carol] {
+        close.add_signature(close.sign(kp).unwrap()).unwrap();
+    }
+    let settlement = close::close_channel(&mut channel, &close, &cache).unwrap();
+
+    // Carol's zero balance gets no output; Alice and Bob share the fee
+    assert_eq!(settlement.outputs.len(), 2);
+    let fee = 50_000 - settlement.outputs.iter().map(|output| output.value).sum::<u64>();
+    assert_eq!(fee, 10 + 148 + 2 * 34);
+    let paid_to = |kp: &KeyPair| settlement.outputs.iter()
+        .find(|output| output.public_key_hash == kp.public_key().public_key_hash())
+        .map(|output| output.value);
+    assert!(paid_to(&alice).unwrap() > 29_880);
+    assert!(paid_to(&bob).unwrap() > 19_880);
+
+    // The funding output is spent and the channel is settled
+    let funding = channel.funding.clone().unwrap();
+    assert!(cache.get_utxo(funding.txid, funding.index).unwrap().is_none());
+    assert!(cache.get_utxo(settlement.hash, 0).unwrap().is_some());
+    assert_eq!(channel.status, ChannelStatus::Settled);
+    let statuses: Vec<_> = channel.status_history.iter().map(|t| t.to).collect();
+    assert_eq!(statuses, vec![ChannelStatus::Active, ChannelStatus::Closing, ChannelStatus::Settled]);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_close_rejects_stale_or_invalid_settlement() {
+    let test_db_path = PathBuf::from("test_close_stale.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let mallory = crypto::generate_keypair();
+    let (cache, mut channel, secrets) = open_channel(&test_db_path, &[&alice, &bob]);
+
+    let mut close = CooperativeClose::propose(&channel, 1).unwrap();
+    assert!(matches!(close.sign(&mallory), Err(CloseError::UnknownParticipant)));
+    let mut forged = close.sign(&bob).unwrap();
+    forged.signature[0] ^= 1;
+    assert!(matches!(
+        close.add_signature(forged),
+        Err(CloseError::InvalidSignature(key)) if key == bob.public_key().as_bytes()
+    ));
+    for kp in [&alice, &bob] {
+        close.add_signature(close.sign(kp).unwrap()).unwrap();
+    }
+
+    // A settlement of an older state can't close the channel
+    transfer(&mut channel, &secrets, &alice, &bob, 1_000);
+    assert!(matches!(
+        close::close_channel(&mut channel, &close, &cache),
+        Err(CloseError::StaleSettlement)
+    ));
+    assert_eq!(channel.status, ChannelStatus::Active);
+
+    // Balances must still add up to the funding output
+    channel.balances.insert(bob.public_key(), 2_000);
+    assert!(matches!(
+        CooperativeClose::propose(&channel, 1),
+        Err(CloseError::BalanceMismatch { balances: 51_000, funding: 50_000 })
+    ));
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
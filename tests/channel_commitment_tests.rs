@@ -0,0 +1,145 @@
+mod common;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use primitive_types::H256;
+use state_channel_node::channel::chain::{ChainBackend, ChainError};
+use state_channel_node::channel::commitment::{self, CommitmentError, CommitmentParams, CommitmentTransaction, DelayedOutput};
+use state_channel_node::channel::state::{ChannelState, ChannelStatus};
+use state_channel_node::crypto::{self, KeyPair, PublicKey};
+use state_channel_node::utxo::models::{Input, SighashType, Transaction};
+use state_channel_node::utxo::script::{self, Script};
+use state_channel_node::utxo::timelock::RelativeLock;
+use state_channel_node::utxo::validation::ValidationError;
+use common::channel::{open_channel, transfer};
+use common::test_utils;
+use common::utxo;
+
+/// Each participant's tracked revocation hash for the channel's current state
+fn revocation_hashes(channel: &ChannelState) -> HashMap<PublicKey, [u8; 32]> {
+    channel.revocations.iter()
+        .map(|(pk, store)| (pk.clone(), store.revocation_hash(channel.sequence_number).unwrap()))
+        .collect()
+}
+
+/// Build and fully sign every participant's commitment for the current state
+fn signed_commitments(channel: &ChannelState, keypairs: &[&KeyPair], params: &CommitmentParams) -> Vec<CommitmentTransaction> {
+    let hashes = revocation_hashes(channel);
+    let mut commitments = commitment::build_commitments(channel, &hashes, params).unwrap();
+    for commitment in &mut commitments {
+        for kp in keypairs {
+            commitment.add_signature(commitment.sign(kp).unwrap()).unwrap();
+        }
+    }
+    commitments
+}
+
+/// Sweep of `delayed` to its owner, built without `ForceClose::sweep`'s maturity check
+fn early_sweep(delayed: &DelayedOutput, owner: &KeyPair) -> Transaction {
+    let mut tx = Transaction {
+        version: 2,
+        inputs: vec![Input {
+            previous_output: delayed.txid,
+            index: delayed.index,
+            signature: vec![],
+            sequence: RelativeLock::Blocks(delayed.to_self_delay).to_sequence(),
+        }],
+        outputs: vec![utxo::create_output_for(delayed.value - 1_000, &owner.public_key().public_key_hash())],
+        lock_time: 0,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+    let signature = script::sign(&tx, 0, owner, SighashType::All).unwrap();
+    tx.inputs[0].signature = Script::new().push_data(&signature).push_int(0).into_bytes();
+    tx
+}
+
+#[test]
+fn test_force_close_and_sweep_after_delay() {
+    let test_db_path = PathBuf::from("test_commitment_force_close.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let carol = crypto::generate_keypair();
+    let keypairs = [&alice, &bob, &carol];
+    let (cache, mut channel, secrets) = open_channel(&test_db_path, &keypairs);
+    transfer(&mut channel, &secrets, &alice, &bob, 20_000);
+
+    // Carol has nothing to claim, so she gets no commitment
+    let params = CommitmentParams::new(1).with_to_self_delay(3);
+    let commitments = signed_commitments(&channel, &keypairs, &params);
+    assert_eq!(commitments.len(), 2);
+    let alices = commitments.iter().find(|c| c.holder == alice.public_key()).unwrap();
+
+    // Alice publishes her commitment; Bob's output is spendable right away
+    let force_close = commitment::force_close(&mut channel, alices, &cache).unwrap();
+    assert_eq!(channel.status, ChannelStatus::Disputed);
+    let bobs_output = alices.tx().outputs.iter()
+        .position(|output| output.public_key_hash == bob.public_key().public_key_hash())
+        .unwrap();
+    let bobs_utxo = cache.get_utxo(force_close.commitment_txid, bobs_output as u32).unwrap().unwrap();
+    assert_eq!(bobs_utxo.output.value, 20_000);
+    assert!(bobs_utxo.output.lock_script.is_empty());
+
+    // Alice's own output waits for three confirmations
+    assert_eq!(force_close.delayed_output.value, 30_000 - (10 + 148 + 2 * 34));
+    assert!(matches!(
+        force_close.sweep(&mut channel, &alice, 1, &cache),
+        Err(CommitmentError::NotMatured { remaining: 3 })
+    ));
+    let commitment_tx = alices.finalize().unwrap();
+    cache.connect_block(3, &[commitment_tx]).unwrap();
+    cache.connect_block(4, &[]).unwrap();
+    assert_eq!(force_close.remaining_blocks(&cache).unwrap(), 1);
+
+    // Broadcasting a sweep directly doesn't get around the relative lock
+    assert!(matches!(
+        cache.broadcast(&early_sweep(&force_close.delayed_output, &alice)),
+        Err(ChainError::Rejected(ValidationError::SequenceLockNotReached { input: 0, lock: RelativeLock::Blocks(3) }))
+    ));
+    assert!(matches!(
+        force_close.sweep(&mut channel, &bob, 1, &cache),
+        Err(CommitmentError::UnknownParticipant)
+    ));
+    cache.connect_block(5, &[]).unwrap();
+
+    let sweep = force_close.sweep(&mut channel, &alice, 1, &cache).unwrap();
+    assert_eq!(sweep.outputs[0].public_key_hash, alice.public_key().public_key_hash());
+    assert_eq!(channel.status, ChannelStatus::Resolved);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_force_close_requires_latest_signed_commitment() {
+    let test_db_path = PathBuf::from("test_commitment_stale.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let keypairs = [&alice, &bob];
+    let (cache, mut channel, secrets) = open_channel(&test_db_path, &keypairs);
+    transfer(&mut channel, &secrets, &alice, &bob, 10_000);
+
+    // Every participant has to sign
+    let params = CommitmentParams::new(1);
+    let revocation_hash = revocation_hashes(&channel)[&bob.public_key()];
+    let mut unsigned = CommitmentTransaction::build(&channel, &bob.public_key(), revocation_hash, &params).unwrap();
+    unsigned.add_signature(unsigned.sign(&bob).unwrap()).unwrap();
+    assert!(matches!(
+        commitment::force_close(&mut channel, &unsigned, &cache),
+        Err(CommitmentError::MissingSignature(key)) if key == alice.public_key().as_bytes()
+    ));
+
+    // Commitments of older states can't be used
+    let old = signed_commitments(&channel, &keypairs, &params);
+    transfer(&mut channel, &secrets, &bob, &alice, 5_000);
+    assert!(matches!(
+        commitment::force_close(&mut channel, &old[0], &cache),
+        Err(CommitmentError::StaleCommitment)
+    ));
+    assert_eq!(channel.status, ChannelStatus::Active);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
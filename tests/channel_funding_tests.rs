@@ -0,0 +1,266 @@
+mod common;
+mod test_helpers;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use state_channel_node::channel::chain::ChainBackend;
+use state_channel_node::channel::commitment::CommitmentParams;
+use state_channel_node::channel::funding::{self, ChannelOpening, FundingError, FundingParams};
+use state_channel_node::channel::revocation::{self, RevocationSecrets};
+use state_channel_node::channel::state::{ChannelStatus, StateUpdate};
+use state_channel_node::crypto::{self, KeyPair, PublicKey};
+use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::models::SighashType;
+use state_channel_node::utxo::script::{self, Script};
+use state_channel_node::utxo::store::SdbStore;
+use state_channel_node::utxo::validation;
+use common::test_utils;
+use common::utxo;
+use test_helpers::{sign_update, sort_participants};
+
+/// Cache in which `funder` owns one confirmed 100_000 output
+fn funded_cache(test_db_path: &PathBuf, funder: &KeyPair) -> UtxoCache {
+    test_utils::cleanup_test_db(test_db_path);
+    let store = SdbStore::new(test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+    let coinbase = utxo::create_transaction(vec![utxo::create_output_for(100_000, &funder.public_key().public_key_hash())]);
+    cache.connect_block(1, &[coinbase]).unwrap();
+    cache
+}
+
+/// Fresh revocation secrets for every participant
+fn revocation_secrets(keypairs: &[&KeyPair]) -> HashMap<PublicKey, RevocationSecrets> {
+    keypairs.iter().map(|kp| (kp.public_key(), RevocationSecrets::generate())).collect()
+}
+
+/// Revocation hashes of states 0 and 1, as exchanged before opening
+fn revocation_hashes(secrets: &HashMap<PublicKey, RevocationSecrets>) -> HashMap<PublicKey, [[u8; 32]; 2]> {
+    secrets.iter()
+        .map(|(pk, secrets)| (pk.clone(), [secrets.revocation_hash(0).unwrap(), secrets.revocation_hash(1).unwrap()]))
+        .collect()
+}
+
+#[test]
+fn test_open_channel_with_signed_commitments() {
+    let test_db_path = PathBuf::from("test_funding_open.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let carol = crypto::generate_keypair();
+    let cache = funded_cache(&test_db_path, &alice);
+    let secrets = revocation_secrets(&[&alice, &bob, &carol]);
+
+    let participants = vec![alice.public_key(), bob.public_key(), carol.public_key()];
+    let balances = HashMap::from([(alice.public_key(), 30_000), (bob.public_key(), 20_000)]);
+    let mut opening = ChannelOpening::propose(
+        &cache, &alice.public_key(), participants, balances, 50_000, revocation_hashes(&secrets), &FundingParams::new(1),
+    ).unwrap();
+
+    // The funding transaction can't be released before the first commitments are signed
+    assert!(matches!(
+        opening.clone().release_funding(&alice),
+        Err(FundingError::MissingCommitmentSignature(_))
+    ));
+
+    for kp in [&alice, &bob, &carol] {
+        let signatures = opening.proposal().sign_commitments(kp).unwrap();
+        opening.add_commitment_signatures(signatures).unwrap();
+    }
+    let opened = opening.release_funding(&alice).unwrap();
+
+    // The channel is bound to output 0 of the funding transaction
+    let funding = opened.channel.funding.clone().unwrap();
+    assert_eq!(funding.txid, opened.funding_tx.hash);
+    assert_eq!(funding.value, 50_000);
+    assert_eq!(opened.channel.status, ChannelStatus::Opening);
+    assert_eq!(opened.channel.balances[&carol.public_key()], 0);
+
+    // Carol has no balance, so only Alice and Bob get a first commitment
+    assert_eq!(opened.commitments.len(), 2);
+    let alices = opened.commitments.iter().find(|c| c.holder == alice.public_key()).unwrap();
+    assert_eq!(alices.sequence_number, 0);
+    assert_eq!(alices.tx().inputs[0].previous_output, funding.txid);
+
+    // Both transactions are valid on chain, the commitment once funding confirmed
+    validation::validate_transaction(&opened.funding_tx, &cache).unwrap();
+    let mut channel = opened.channel;
+    assert!(matches!(
+        funding::confirm_funding(&mut channel, &cache, 1),
+        Err(FundingError::FundingNotConfirmed)
+    ));
+    cache.connect_block(2, std::slice::from_ref(&opened.funding_tx)).unwrap();
+    let alices_tx = alices.finalize().unwrap();
+    validation::validate_transaction(&alices_tx, &cache).unwrap();
+
+    // By default every participant must sign; two of three can't spend the funding output
+    assert_eq!(funding.threshold, 3);
+    let mut signers = [&alice, &bob, &carol];
+    signers.sort_by_key(|kp| kp.public_key().as_bytes());
+    let mut two_of_three = alices_tx.clone();
+    let unlock = signers[..2].iter()
+        .fold(Script::new(), |unlock, kp| unlock.push_data(&script::sign(&two_of_three, 0, kp, SighashType::All).unwrap()));
+    two_of_three.inputs[0].signature = unlock.into_bytes();
+    let err = validation::validate_transaction(&two_of_three, &cache).unwrap_err();
+    assert_eq!(err.code(), "INVALID_SCRIPT");
+
+    // Alice's commitment pays her (less the fee) and Bob
+    let paid: u64 = alices_tx.outputs.iter().map(|output| output.value).sum();
+    assert_eq!(alices_tx.outputs.len(), 2);
+    assert!(paid < 50_000 && paid > 49_000);
+
+    funding::confirm_funding(&mut channel, &cache, 1).unwrap();
+    assert_eq!(channel.status, ChannelStatus::Active);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_first_update_revokes_first_commitments() {
+    let test_db_path = PathBuf::from("test_funding_revoked.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let cache = funded_cache(&test_db_path, &alice);
+    let secrets = revocation_secrets(&[&alice, &bob]);
+
+    // Bob brings nothing and has no initial balance
+    let participants = vec![alice.public_key(), bob.public_key()];
+    let balances = HashMap::from([(alice.public_key(), 50_000)]);
+    let mut opening = ChannelOpening::propose(
+        &cache, &alice.public_key(), participants, balances, 50_000, revocation_hashes(&secrets), &FundingParams::new(1),
+    ).unwrap();
+    for kp in [&alice, &bob] {
+        let signatures = opening.proposal().sign_commitments(kp).unwrap();
+        opening.add_commitment_signatures(signatures).unwrap();
+    }
+    let opened = opening.release_funding(&alice).unwrap();
+    cache.connect_block(2, std::slice::from_ref(&opened.funding_tx)).unwrap();
+    let mut channel = opened.channel;
+    funding::confirm_funding(&mut channel, &cache, 1).unwrap();
+
+    // A signed update credits Bob, revoking everyone's state 0
+    let changes = HashMap::from([(alice.public_key(), -20_000), (bob.public_key(), 20_000)]);
+    let mut affected = vec![alice.public_key(), bob.public_key()];
+    sort_participants(&mut affected);
+    let signatures = affected.iter()
+        .map(|pk| {
+            let signer = if *pk == alice.public_key() { &alice } else { &bob };
+            sign_update(signer, &affected, 1, &changes, 1, channel.channel_id)
+        })
+        .collect();
+    let revocations = channel.participants.iter()
+        .map(|pk| secrets[pk].revoke(channel.channel_id, pk, 0).unwrap())
+        .collect();
+    let update = StateUpdate {
+        sequence_number: 1,
+        balance_changes: changes,
+        signatures,
+        affected_participants: affected,
+        timestamp: 1,
+        revocations,
+    };
+    channel.apply_update(&update).unwrap();
+    assert_eq!(channel.balances[&bob.public_key()], 20_000);
+
+    // Alice's first commitment still pays her the whole initial balance...
+    let alices = opened.commitments.iter().find(|c| c.holder == alice.public_key()).unwrap();
+    let revoked = alices.finalize().unwrap();
+    cache.broadcast(&revoked).unwrap();
+
+    // ...but it is revoked, so Bob takes her output before her delay expires
+    let alices_store = &channel.revocations[&alice.public_key()];
+    let params = CommitmentParams::new(1);
+    let justice = revocation::build_justice(&channel, &alice.public_key(), &revoked, alices_store, &bob, &params).unwrap();
+    let alices_output = &revoked.outputs[justice.inputs[0].index as usize];
+    assert!(alices_output.value > 49_000);
+    assert_eq!(justice.outputs[0].public_key_hash, bob.public_key().public_key_hash());
+
+    revocation::penalize(&mut channel, &justice, &cache).unwrap();
+    assert_eq!(channel.status, ChannelStatus::Penalized);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_open_rejects_balance_mismatch() {
+    let test_db_path = PathBuf::from("test_funding_mismatch.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let cache = funded_cache(&test_db_path, &alice);
+
+    let participants = vec![alice.public_key(), bob.public_key()];
+    let balances = HashMap::from([(alice.public_key(), 30_000), (bob.public_key(), 10_000)]);
+    let hashes = revocation_hashes(&revocation_secrets(&[&alice, &bob]));
+    let result = ChannelOpening::propose(
+        &cache, &alice.public_key(), participants.clone(), balances.clone(), 50_000, hashes.clone(), &FundingParams::new(1),
+    );
+    assert!(matches!(
+        result,
+        Err(FundingError::BalanceMismatch { balances: 40_000, funding: 50_000 })
+    ));
+
+    // The funder must be able to pay for the funding output
+    let result = ChannelOpening::propose(
+        &cache, &alice.public_key(), participants.clone(), HashMap::from([(alice.public_key(), 200_000)]),
+        200_000, hashes.clone(), &FundingParams::new(1),
+    );
+    assert!(matches!(result, Err(FundingError::Selection(_))));
+
+    // A 3-of-2 multisig could never be spent
+    let result = ChannelOpening::propose(
+        &cache, &alice.public_key(), participants, balances, 40_000, hashes, &FundingParams::new(1).with_threshold(3),
+    );
+    assert!(matches!(result, Err(FundingError::InvalidThreshold { threshold: 3, participants: 2 })));
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_commitment_signatures_are_verified() {
+    let test_db_path = PathBuf::from("test_funding_signatures.db");
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let mallory = crypto::generate_keypair();
+    let cache = funded_cache(&test_db_path, &alice);
+
+    let participants = vec![alice.public_key(), bob.public_key()];
+    let balances = HashMap::from([(alice.public_key(), 25_000), (bob.public_key(), 25_000)]);
+    let hashes = revocation_hashes(&revocation_secrets(&[&alice, &bob]));
+    let mut opening = ChannelOpening::propose(
+        &cache, &alice.public_key(), participants, balances, 50_000, hashes, &FundingParams::new(1),
+    ).unwrap();
+
+    // Corrupted signatures are rejected, as are incomplete sets
+    let mut forged = opening.proposal().sign_commitments(&bob).unwrap();
+    forged.signatures.get_mut(&alice.public_key()).unwrap()[0] ^= 1;
+    assert!(matches!(
+        opening.add_commitment_signatures(forged),
+        Err(FundingError::InvalidCommitmentSignature(key)) if key == bob.public_key().as_bytes()
+    ));
+    let mut incomplete = opening.proposal().sign_commitments(&bob).unwrap();
+    incomplete.signatures.remove(&bob.public_key());
+    assert!(matches!(
+        opening.add_commitment_signatures(incomplete),
+        Err(FundingError::MissingCommitmentSignature(_))
+    ));
+
+    // Outsiders can't sign
+    assert!(matches!(opening.proposal().sign_commitments(&mallory), Err(FundingError::UnknownParticipant)));
+
+    // Participants refuse to sign without everyone's revocation hashes, or
+    // when the funding output doesn't hold the initial balances
+    let mut tampered = opening.proposal().clone();
+    tampered.revocation_hashes.remove(&alice.public_key());
+    assert!(matches!(tampered.sign_commitments(&bob), Err(FundingError::InvalidProposal)));
+    let mut tampered = opening.proposal().clone();
+    tampered.funding_tx.outputs[0].value -= 1;
+    assert!(matches!(tampered.sign_commitments(&bob), Err(FundingError::BalanceMismatch { .. })));
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
@@ -0,0 +1,149 @@
+use state_channel_node::crypto::{self, KeyPair};
+use state_channel_node::channel::state::{ChannelState, ChannelStatus, StateUpdate};
+use state_channel_node::channel::transitions::{validate_state_transition, ChannelError};
+use state_channel_node::channel::wal::{ChannelWal, WalError};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod test_helpers;
+use test_helpers::{create_test_channel, sign_update, sort_participants};
+
+fn cleanup_wal(path: &Path) {
+    if path.exists() {
+        fs::remove_file(path).expect("Failed to cleanup WAL file");
+    }
+}
+
+fn opening_channel(kp1: &KeyPair, kp2: &KeyPair) -> ChannelState {
+    let balances = HashMap::from([(kp1.public_key(), 1_000), (kp2.public_key(), 1_000)]);
+    ChannelState::opening(vec![kp1.public_key(), kp2.public_key()], balances)
+}
+
+// Build a signed transfer of `amount` from `from` to `to`
+fn transfer(channel: &ChannelState, from: &KeyPair, to: &KeyPair, amount: i64) -> StateUpdate {
+    let sequence = channel.sequence_number + 1;
+    let mut affected_participants = vec![from.public_key(), to.public_key()];
+    sort_participants(&mut affected_participants);
+    let changes = HashMap::from([(from.public_key(), -amount), (to.public_key(), amount)]);
+    let signatures = affected_participants.iter()
+        .map(|participant| {
+            let kp = if *participant == from.public_key() { from } else { to };
+            sign_update(kp, &affected_participants, sequence, &changes, sequence, channel.channel_id)
+        })
+        .collect();
+
+    StateUpdate {
+        sequence_number: sequence,
+        balance_changes: changes,
+        signatures,
+        affected_participants,
+        timestamp: sequence,
+        revocations: Vec::new(),
+    }
+}
+
+#[test]
+fn test_cooperative_lifecycle() {
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let mut channel = opening_channel(&kp1, &kp2);
+    assert_eq!(channel.status, ChannelStatus::Opening);
+
+    // No updates before the funding confirms
+    let update = transfer(&channel, &kp1, &kp2, 100);
+    assert_eq!(validate_state_transition(&channel, &update), Err(ChannelError::ChannelNotActive(ChannelStatus::Opening)));
+    assert_eq!(channel.apply_update(&update), Err("Channel is not active"));
+
+    channel.activate().unwrap();
+    validate_state_transition(&channel, &update).unwrap();
+    channel.apply_update(&update).unwrap();
+
+    // Closing freezes the balances
+    channel.begin_close().unwrap();
+    let update = transfer(&channel, &kp2, &kp1, 50);
+    assert_eq!(validate_state_transition(&channel, &update), Err(ChannelError::ChannelNotActive(ChannelStatus::Closing)));
+    assert_eq!(channel.apply_update(&update), Err("Channel is not active"));
+    assert_eq!(channel.balances[&kp1.public_key()], 900);
+
+    channel.settle().unwrap();
+    assert!(channel.status.is_final());
+    assert_eq!(
+        channel.activate(),
+        Err(ChannelError::InvalidStatusTransition { from: ChannelStatus::Settled, to: ChannelStatus::Active })
+    );
+
+    let history: Vec<_> = channel.status_history.iter()
+        .map(|t| (t.from, t.to, t.sequence_number))
+        .collect();
+    assert_eq!(history, vec![
+        (ChannelStatus::Opening, ChannelStatus::Active, 0),
+        (ChannelStatus::Active, ChannelStatus::Closing, 1),
+        (ChannelStatus::Closing, ChannelStatus::Settled, 1),
+    ]);
+}
+
+#[test]
+fn test_dispute_transitions() {
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+
+    let mut channel = opening_channel(&kp1, &kp2);
+    assert!(matches!(channel.dispute(), Err(ChannelError::InvalidStatusTransition { .. })));
+    channel.activate().unwrap();
+    channel.dispute().unwrap();
+    assert!(matches!(channel.settle(), Err(ChannelError::InvalidStatusTransition { .. })));
+    channel.resolve().unwrap();
+    assert_eq!(channel.status, ChannelStatus::Resolved);
+    assert!(channel.status.is_final());
+
+    // A close can also be contested
+    let mut channel = opening_channel(&kp1, &kp2);
+    channel.activate().unwrap();
+    channel.begin_close().unwrap();
+    channel.dispute().unwrap();
+    channel.penalize().unwrap();
+    assert!(matches!(channel.resolve(), Err(ChannelError::InvalidStatusTransition { .. })));
+    assert_eq!(channel.status_history.len(), 4);
+
+    // Channels created with `new` are already active
+    let channel = create_test_channel(&[kp1.public_key(), kp2.public_key()], 1_000);
+    assert_eq!(channel.status, ChannelStatus::Active);
+    assert!(channel.status_history.is_empty());
+}
+
+#[test]
+fn test_wal_persists_status_history() {
+    let wal_path = PathBuf::from("test_wal_lifecycle.log");
+    cleanup_wal(&wal_path);
+
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let mut channel = opening_channel(&kp1, &kp2);
+
+    {
+        let mut wal = ChannelWal::open(&wal_path).unwrap();
+        wal.log_snapshot(&channel).unwrap();
+        wal.transition(&mut channel, ChannelStatus::Active).unwrap();
+        let update = transfer(&channel, &kp1, &kp2, 100);
+        wal.apply_update(&mut channel, &update).unwrap();
+        wal.transition(&mut channel, ChannelStatus::Closing).unwrap();
+
+        // Invalid transitions and updates on a closing channel are not logged
+        let before = channel.clone();
+        assert!(matches!(
+            wal.transition(&mut channel, ChannelStatus::Penalized),
+            Err(WalError::Rejected(ChannelError::InvalidStatusTransition { .. }))
+        ));
+        let update = transfer(&channel, &kp1, &kp2, 100);
+        assert!(wal.apply_update(&mut channel, &update).is_err());
+        assert_eq!(channel, before);
+    }
+
+    let recovered = ChannelWal::open(&wal_path).unwrap().recover().unwrap();
+    assert_eq!(recovered[&channel.channel_id], channel);
+    assert_eq!(recovered[&channel.channel_id].status, ChannelStatus::Closing);
+    assert_eq!(recovered[&channel.channel_id].status_history.len(), 2);
+
+    cleanup_wal(&wal_path);
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use state_channel_node::channel::policy::SignaturePolicy;
+use state_channel_node::channel::state::{ChannelState, StateUpdate};
+use state_channel_node::channel::transitions::{validate_state_transition, ChannelError};
+use state_channel_node::crypto::{self, KeyPair, PublicKey};
+
+mod test_helpers;
+use test_helpers::{create_test_channel, sign_update, sort_participants};
+
+/// Update #1 moving `amount` from `payer` to `payee`, signed by `signers`
+fn payment(channel: &ChannelState, payer: &KeyPair, payee: &KeyPair, amount: i64, signers: &[&KeyPair]) -> StateUpdate {
+    let changes = HashMap::from([(payer.public_key(), -amount), (payee.public_key(), amount)]);
+    let mut affected: Vec<PublicKey> = signers.iter().map(|kp| kp.public_key()).collect();
+    sort_participants(&mut affected);
+    let signatures = affected
+        .iter()
+        .map(|pk| {
+            let signer = signers.iter().find(|kp| kp.public_key() == *pk).unwrap();
+            sign_update(signer, &affected, 1, &changes, 7, channel.channel_id)
+        })
+        .collect();
+    StateUpdate {
+        sequence_number: 1,
+        balance_changes: changes,
+        signatures,
+        affected_participants: affected,
+        timestamp: 7,
+        revocations: Vec::new(),
+    }
+}
+
+fn sorted(mut keys: Vec<PublicKey>) -> Vec<PublicKey> {
+    sort_participants(&mut keys);
+    keys
+}
+
+#[test]
+fn test_default_policy_requires_every_affected_participant() {
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let mut channel = create_test_channel(&[alice.public_key(), bob.public_key()], 1_000);
+    assert_eq!(channel.signature_policy, SignaturePolicy::AllAffected);
+
+    assert!(validate_state_transition(&channel, &payment(&channel, &alice, &bob, 100, &[&alice, &bob])).is_ok());
+
+    let update = payment(&channel, &alice, &bob, 100, &[&alice]);
+    let err = validate_state_transition(&channel, &update).unwrap_err();
+    assert_eq!(err, ChannelError::MissingSigners(vec![bob.public_key()]));
+    assert!(err.to_string().contains(&hex::encode(bob.public_key().as_bytes())));
+
+    // Applying directly enforces the policy too
+    assert_eq!(channel.apply_update(&update), Err("Signature policy not satisfied"));
+    assert_eq!(channel.sequence_number, 0);
+}
+
+#[test]
+fn test_debited_parties_policy() {
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let mut channel = create_test_channel(&[alice.public_key(), bob.public_key()], 1_000)
+        .with_signature_policy(SignaturePolicy::AllDebited);
+
+    // The payee doesn't need to sign, but may
+    let update = payment(&channel, &alice, &bob, 100, &[&alice]);
+    assert!(validate_state_transition(&channel, &update).is_ok());
+    assert!(validate_state_transition(&channel, &payment(&channel, &alice, &bob, 100, &[&alice, &bob])).is_ok());
+    channel.apply_update(&update).unwrap();
+    assert_eq!(channel.balances[&bob.public_key()], 1_100);
+
+    let channel = create_test_channel(&[alice.public_key(), bob.public_key()], 1_000)
+        .with_signature_policy(SignaturePolicy::AllDebited);
+    assert_eq!(
+        validate_state_transition(&channel, &payment(&channel, &alice, &bob, 100, &[&bob])),
+        Err(ChannelError::MissingSigners(vec![alice.public_key()]))
+    );
+}
+
+#[test]
+fn test_operator_threshold_plus_payer() {
+    let operators: Vec<KeyPair> = (0..5).map(|_| crypto::generate_keypair()).collect();
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let outsider = crypto::generate_keypair();
+    let operator_keys: Vec<PublicKey> = operators.iter().map(KeyPair::public_key).collect();
+
+    let mut participants = operator_keys.clone();
+    participants.extend([alice.public_key(), bob.public_key()]);
+    let channel = create_test_channel(&participants, 1_000).with_signature_policy(SignaturePolicy::All(vec![
+        SignaturePolicy::Threshold { threshold: 3, signers: operator_keys.clone() },
+        SignaturePolicy::AllDebited,
+    ]));
+
+    let update = payment(&channel, &alice, &bob, 250, &[&operators[0], &operators[2], &operators[4], &alice]);
+    assert!(validate_state_transition(&channel, &update).is_ok());
+
+    // Two operators aren't enough; the error names the ones that didn't sign
+    let update = payment(&channel, &alice, &bob, 250, &[&operators[1], &operators[3], &alice]);
+    assert_eq!(
+        validate_state_transition(&channel, &update),
+        Err(ChannelError::ThresholdNotMet {
+            threshold: 3,
+            signed: 2,
+            missing: sorted(vec![operator_keys[0].clone(), operator_keys[2].clone(), operator_keys[4].clone()]),
+        })
+    );
+
+    // Operators alone can't move the payer's funds
+    let update = payment(&channel, &alice, &bob, 250, &[&operators[0], &operators[1], &operators[2]]);
+    assert_eq!(
+        validate_state_transition(&channel, &update),
+        Err(ChannelError::MissingSigners(vec![alice.public_key()]))
+    );
+
+    // Signatures from outside the channel never count
+    let update = payment(&channel, &alice, &bob, 250, &[&operators[0], &operators[1], &outsider, &alice]);
+    assert_eq!(validate_state_transition(&channel, &update), Err(ChannelError::UnknownParticipant));
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use state_channel_node::channel::chain::ChainBackend;
+use state_channel_node::channel::commitment::{CommitmentParams, CommitmentTransaction};
+use state_channel_node::channel::revocation::{
+    self, RevocationError, RevocationSecrets, RevocationStore, FIRST_SECRET_INDEX,
+};
+use state_channel_node::channel::state::ChannelStatus;
+use state_channel_node::channel::transitions::ChannelError;
+use state_channel_node::channel::wal::{ChannelWal, WalError};
+use state_channel_node::crypto;
+use common::channel::{open_channel, signed_transfer};
+use common::test_utils;
+
+fn cleanup_wal(path: &Path) {
+    if path.exists() {
+        fs::remove_file(path).expect("Failed to cleanup WAL file");
+    }
+}
+
+#[test]
+fn test_secrets_match_bolt3_vectors() {
+    let vectors = [
+        ([0x00; 32], FIRST_SECRET_INDEX, "02a40c85b6f28da08dfdbe0926c53fab2de6d28c10301f8f7c4073d5e42e3148"),
+        ([0xff; 32], FIRST_SECRET_INDEX, "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc"),
+        ([0xff; 32], 0xaaaaaaaaaaa, "56f4008fb007ca9acf0e15b054d5c9fd12ee06cea347914ddbaed70d1c13a528"),
+        ([0xff; 32], 0x555555555555, "9015daaeb06dba4ccc05b91b2f73bd54405f2be9f217fbacd3c5ac2e62327d31"),
+        ([0x01; 32], 1, "915c75942a26bb3a433a8ce2cb0427c29ec6c1775cfc78328b57f6ba7bfeaa9c"),
+    ];
+    for (seed, index, expected) in vectors {
+        let secrets = RevocationSecrets::from_seed(seed);
+        assert_eq!(hex::encode(secrets.secret(FIRST_SECRET_INDEX - index).unwrap()), expected);
+    }
+}
+
+#[test]
+fn test_store_derives_every_revealed_secret() {
+    let secrets = RevocationSecrets::generate();
+    let mut store = RevocationStore::new();
+    let channel_id = [7u8; 32];
+    let holder = crypto::generate_keypair().public_key();
+
+    for sequence in 0..1_000 {
+        let revocation = secrets.revoke(channel_id, &holder, sequence).unwrap();
+        assert_eq!(revocation.next_revocation_hash, secrets.revocation_hash(sequence + 2).unwrap());
+        store.add(&revocation, &secrets.revocation_hash(sequence).unwrap()).unwrap();
+    }
+    for sequence in 0..1_000 {
+        assert_eq!(store.secret(sequence), Some(secrets.secret(sequence).unwrap()));
+    }
+    assert_eq!(store.secret(1_000), None);
+
+    // Secrets arrive in order and must hash to the commitment's revocation hash
+    let next = secrets.revoke(channel_id, &holder, 1_000).unwrap();
+    let skipped = secrets.revoke(channel_id, &holder, 1_001).unwrap();
+    assert!(matches!(
+        store.add(&skipped, &secrets.revocation_hash(1_001).unwrap()),
+        Err(RevocationError::OutOfOrder { expected: 1_000, got: 1_001 })
+    ));
+    assert!(matches!(
+        store.add(&next, &secrets.revocation_hash(999).unwrap()),
+        Err(RevocationError::HashMismatch(1_000))
+    ));
+
+    // A secret from another seed can't derive the earlier ones
+    store.add(&next, &secrets.revocation_hash(1_000).unwrap()).unwrap();
+    let forger = RevocationSecrets::generate();
+    let forged = forger.revoke(channel_id, &holder, 1_001).unwrap();
+    assert!(matches!(
+        store.add(&forged, &forger.revocation_hash(1_001).unwrap()),
+        Err(RevocationError::InconsistentSecret(1_001))
+    ));
+}
+
+#[test]
+fn test_updates_must_revoke_the_previous_state() {
+    let test_db_path = PathBuf::from("test_revocation_updates.db");
+    let wal_path = PathBuf::from("test_revocation_updates.log");
+    cleanup_wal(&wal_path);
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let keypairs = [&alice, &bob];
+    let (cache, mut channel, secrets) = open_channel(&test_db_path, &keypairs);
+    let mut wal = ChannelWal::open(&wal_path).unwrap();
+    wal.log_snapshot(&channel).unwrap();
+
+    // Revocation hashes are exchanged when opening; other secrets don't match them
+    let untracked: HashMap<_, _> = keypairs.iter().map(|kp| (kp.public_key(), RevocationSecrets::generate())).collect();
+    let update = signed_transfer(&channel, &untracked, &alice, &bob, 10_000);
+    assert!(matches!(
+        wal.apply_update(&mut channel, &update),
+        Err(WalError::Rejected(ChannelError::InvalidRevocation(_)))
+    ));
+    let mut no_revocations = update.clone();
+    no_revocations.revocations.clear();
+    assert!(matches!(
+        wal.apply_update(&mut channel, &no_revocations),
+        Err(WalError::Rejected(ChannelError::MissingRevocation(_)))
+    ));
+
+    let update = signed_transfer(&channel, &secrets, &alice, &bob, 10_000);
+
+    // Every participant must revoke, and applying directly checks it too
+    let mut missing = update.clone();
+    missing.revocations.retain(|revocation| revocation.public_key != bob.public_key());
+    assert!(matches!(
+        wal.apply_update(&mut channel, &missing),
+        Err(WalError::Rejected(ChannelError::MissingRevocation(key))) if key == bob.public_key().as_bytes()
+    ));
+    assert_eq!(channel.clone().apply_update(&missing), Err("Invalid revocations"));
+
+    // Secrets must match the tracked hash of the state being replaced
+    let forger = RevocationSecrets::generate();
+    let mut forged = update.clone();
+    forged.revocations[0] = forger.revoke(channel.channel_id, &forged.revocations[0].public_key, 0).unwrap();
+    let forged_key = forged.revocations[0].public_key.as_bytes();
+    assert!(matches!(
+        wal.apply_update(&mut channel, &forged),
+        Err(WalError::Rejected(ChannelError::InvalidRevocation(key))) if key == forged_key
+    ));
+    let mut wrong_state = update.clone();
+    wrong_state.revocations[1] = secrets[&wrong_state.revocations[1].public_key]
+        .revoke(channel.channel_id, &wrong_state.revocations[1].public_key, 1)
+        .unwrap();
+    assert!(matches!(
+        wal.apply_update(&mut channel, &wrong_state),
+        Err(WalError::Rejected(ChannelError::InvalidRevocation(_)))
+    ));
+    assert_eq!(channel.sequence_number, 0);
+
+    // The accepted update stores the secret of state 0 and tracks the hash of state 2
+    wal.apply_update(&mut channel, &update).unwrap();
+    assert_eq!(channel.balances[&bob.public_key()], 10_000);
+    let bobs_store = &channel.revocations[&bob.public_key()];
+    assert_eq!(bobs_store.secret(0), Some(secrets[&bob.public_key()].secret(0).unwrap()));
+    assert_eq!(bobs_store.revocation_hash(2), Some(secrets[&bob.public_key()].revocation_hash(2).unwrap()));
+
+    // Replaying the log restores the revocation stores
+    let recovered = wal.recover().unwrap();
+    assert_eq!(recovered[&channel.channel_id], channel);
+
+    // Cleanup
+    drop(wal);
+    cleanup_wal(&wal_path);
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_justice_transaction_sweeps_revoked_commitment() {
+    let test_db_path = PathBuf::from("test_revocation_justice.db");
+    let wal_path = PathBuf::from("test_revocation_justice.log");
+    cleanup_wal(&wal_path);
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let keypairs = [&alice, &bob];
+    let (cache, mut channel, secrets) = open_channel(&test_db_path, &keypairs);
+    let mut wal = ChannelWal::open(&wal_path).unwrap();
+    wal.log_snapshot(&channel).unwrap();
+    let params = CommitmentParams::new(1);
+
+    // State 1: Bob holds 10_000 and gets a fully signed commitment
+    let update = signed_transfer(&channel, &secrets, &alice, &bob, 10_000);
+    wal.apply_update(&mut channel, &update).unwrap();
+    let revocation_hash = channel.revocations[&bob.public_key()].revocation_hash(1).unwrap();
+    let mut bobs_old = CommitmentTransaction::build(&channel, &bob.public_key(), revocation_hash, &params).unwrap();
+    for kp in keypairs {
+        bobs_old.add_signature(bobs_old.sign(kp).unwrap()).unwrap();
+    }
+
+    // State 2: Bob pays back 5_000, revoking state 1
+    let update = signed_transfer(&channel, &secrets, &bob, &alice, 5_000);
+    wal.apply_update(&mut channel, &update).unwrap();
+    let bobs_store = channel.revocations[&bob.public_key()].clone();
+    assert_eq!(bobs_store.next_sequence(), 2);
+
+    // Bob publishes the revoked state anyway
+    let revoked = bobs_old.finalize().unwrap();
+    cache.broadcast(&revoked).unwrap();
+    assert!(matches!(
+        revocation::build_justice(&channel, &bob.public_key(), &revoked, &bobs_store, &bob, &params),
+        Err(RevocationError::NotACounterparty)
+    ));
+    assert!(matches!(
+        revocation::build_justice(&channel, &alice.public_key(), &revoked, &bobs_store, &bob, &params),
+        Err(RevocationError::NotACommitment)
+    ));
+
+    // Alice takes Bob's whole output before his delay expires
+    let justice = revocation::build_justice(&channel, &bob.public_key(), &revoked, &bobs_store, &alice, &params).unwrap();
+    let cheater_output = &revoked.outputs[justice.inputs[0].index as usize];
+    assert_eq!(cheater_output.value, 10_000 - (10 + 148 + 2 * 34));
+    assert_eq!(justice.outputs[0].public_key_hash, alice.public_key().public_key_hash());
+    assert_eq!(justice.outputs[0].value, cheater_output.value - (10 + 148 + 34));
+
+    revocation::penalize(&mut channel, &justice, &cache).unwrap();
+    assert_eq!(channel.status, ChannelStatus::Penalized);
+    assert!(cache.get_utxo(revoked.hash, justice.inputs[0].index).unwrap().is_none());
+
+    // The current state hasn't been revoked, so there is no justice for it
+    let current_hash = bobs_store.revocation_hash(2).unwrap();
+    let mut current = CommitmentTransaction::build(&channel, &bob.public_key(), current_hash, &params).unwrap();
+    for kp in keypairs {
+        current.add_signature(current.sign(kp).unwrap()).unwrap();
+    }
+    let current = current.finalize().unwrap();
+    assert!(matches!(
+        revocation::build_justice(&channel, &bob.public_key(), &current, &bobs_store, &alice, &params),
+        Err(RevocationError::NotRevoked(2))
+    ));
+
+    // Cleanup
+    drop(wal);
+    cleanup_wal(&wal_path);
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
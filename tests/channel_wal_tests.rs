@@ -0,0 +1,217 @@
+use state_channel_node::crypto::{self, KeyPair};
+use state_channel_node::channel::state::{ChannelState, StateUpdate};
+use state_channel_node::channel::wal::{ChannelWal, WalError};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+mod test_helpers;
+use test_helpers::{create_test_channel, sign_update, sort_participants};
+
+fn cleanup_wal(path: &Path) {
+    if path.exists() {
+        fs::remove_file(path).expect("Failed to cleanup WAL file");
+    }
+}
+
+// Build a signed transfer of `amount` from `from` to `to`
+fn transfer(
+    channel: &ChannelState,
+    from: &KeyPair,
+    to: &KeyPair,
+    amount: i64,
+    sequence: u64,
+) -> StateUpdate {
+    let mut affected_participants = vec![from.public_key(), to.public_key()];
+    sort_participants(&mut affected_participants);
+
+    let mut changes = HashMap::new();
+    changes.insert(from.public_key(), -amount);
+    changes.insert(to.public_key(), amount);
+
+    let timestamp = 1_700_000_000 + sequence;
+    let signatures = affected_participants.iter()
+        .map(|participant| {
+            let kp = if *participant == from.public_key() { from } else { to };
+            sign_update(kp, &affected_participants, sequence, &changes, timestamp, channel.channel_id)
+        })
+        .collect();
+
+    StateUpdate {
+        sequence_number: sequence,
+        balance_changes: changes,
+        signatures,
+        affected_participants,
+        timestamp,
+        revocations: Vec::new(),
+    }
+}
+
+#[test]
+fn test_wal_recovers_channel_state() {
+    let wal_path = PathBuf::from("test_wal_recover.log");
+    cleanup_wal(&wal_path);
+
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let mut channel = create_test_channel(&[kp1.public_key(), kp2.public_key()], 1_000_000);
+
+    {
+        let mut wal = ChannelWal::open(&wal_path).expect("Failed to open WAL");
+        wal.log_snapshot(&channel).unwrap();
+        for seq in 1..=3 {
+            let update = transfer(&channel, &kp1, &kp2, 10_000, seq);
+            wal.apply_update(&mut channel, &update).expect("Failed to apply update");
+        }
+    }
+
+    // Simulate restart
+    let wal = ChannelWal::open(&wal_path).expect("Failed to reopen WAL");
+    let recovered = wal.recover().expect("Failed to recover channels");
+    assert_eq!(recovered.len(), 1);
+    assert_eq!(recovered[&channel.channel_id], channel);
+    assert_eq!(recovered[&channel.channel_id].sequence_number, 3);
+    assert_eq!(recovered[&channel.channel_id].balances[&kp1.public_key()], 970_000);
+
+    cleanup_wal(&wal_path);
+}
+
+#[test]
+fn test_wal_rejected_update_is_not_logged() {
+    let wal_path = PathBuf::from("test_wal_rejected.log");
+    cleanup_wal(&wal_path);
+
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let mut channel = create_test_channel(&[kp1.public_key(), kp2.public_key()], 1_000);
+
+    let mut wal = ChannelWal::open(&wal_path).expect("Failed to open WAL");
+    wal.log_snapshot(&channel).unwrap();
+    let len_before = fs::metadata(&wal_path).unwrap().len();
+
+    // Overdraws the payer
+    let update = transfer(&channel, &kp1, &kp2, 5_000, 1);
+    assert!(matches!(wal.apply_update(&mut channel, &update), Err(WalError::Rejected(_))));
+
+    assert_eq!(fs::metadata(&wal_path).unwrap().len(), len_before);
+    assert_eq!(channel.sequence_number, 0);
+    assert_eq!(channel.balances[&kp1.public_key()], 1_000);
+
+    cleanup_wal(&wal_path);
+}
+
+#[test]
+fn test_wal_truncates_torn_tail() {
+    let wal_path = PathBuf::from("test_wal_torn.log");
+    cleanup_wal(&wal_path);
+
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let mut channel = create_test_channel(&[kp1.public_key(), kp2.public_key()], 1_000_000);
+
+    let valid_len = {
+        let mut wal = ChannelWal::open(&wal_path).expect("Failed to open WAL");
+        wal.log_snapshot(&channel).unwrap();
+        let update = transfer(&channel, &kp1, &kp2, 25_000, 1);
+        wal.apply_update(&mut channel, &update).unwrap();
+        fs::metadata(&wal_path).unwrap().len()
+    };
+
+    // Simulate a crash halfway through writing the next record
+    let next = transfer(&channel, &kp2, &kp1, 5_000, 2);
+    let full_record = {
+        let mut scratch = channel.clone();
+        let scratch_path = PathBuf::from("test_wal_torn_scratch.log");
+        cleanup_wal(&scratch_path);
+        let mut scratch_wal = ChannelWal::open(&scratch_path).unwrap();
+        scratch_wal.apply_update(&mut scratch, &next).unwrap();
+        let bytes = fs::read(&scratch_path).unwrap();
+        cleanup_wal(&scratch_path);
+        bytes
+    };
+    let mut file = OpenOptions::new().append(true).open(&wal_path).unwrap();
+    file.write_all(&full_record[..full_record.len() / 2]).unwrap();
+    drop(file);
+
+    let mut wal = ChannelWal::open(&wal_path).expect("Failed to reopen WAL");
+    assert_eq!(fs::metadata(&wal_path).unwrap().len(), valid_len);
+
+    let recovered = wal.recover().unwrap();
+    assert_eq!(recovered[&channel.channel_id], channel);
+
+    // The log is usable again after truncation
+    wal.apply_update(&mut channel, &next).unwrap();
+    let recovered = wal.recover().unwrap();
+    assert_eq!(recovered[&channel.channel_id].sequence_number, 2);
+
+    cleanup_wal(&wal_path);
+}
+
+#[test]
+fn test_wal_corrupted_record_is_truncated() {
+    let wal_path = PathBuf::from("test_wal_corrupt.log");
+    cleanup_wal(&wal_path);
+
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let mut channel = create_test_channel(&[kp1.public_key(), kp2.public_key()], 1_000_000);
+    let snapshot = channel.clone();
+
+    let snapshot_len = {
+        let mut wal = ChannelWal::open(&wal_path).unwrap();
+        wal.log_snapshot(&channel).unwrap();
+        let len = fs::metadata(&wal_path).unwrap().len();
+        let update = transfer(&channel, &kp1, &kp2, 1_000, 1);
+        wal.apply_update(&mut channel, &update).unwrap();
+        len
+    };
+
+    // Flip the last byte of the update record
+    let mut bytes = fs::read(&wal_path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    fs::write(&wal_path, &bytes).unwrap();
+
+    let wal = ChannelWal::open(&wal_path).unwrap();
+    assert_eq!(fs::metadata(&wal_path).unwrap().len(), snapshot_len);
+    assert_eq!(wal.recover().unwrap()[&channel.channel_id], snapshot);
+
+    cleanup_wal(&wal_path);
+}
+
+#[test]
+fn test_wal_replays_from_last_snapshot_after_compaction() {
+    let wal_path = PathBuf::from("test_wal_compact.log");
+    cleanup_wal(&wal_path);
+
+    let kp1 = crypto::generate_keypair();
+    let kp2 = crypto::generate_keypair();
+    let kp3 = crypto::generate_keypair();
+    let mut channel_a = create_test_channel(&[kp1.public_key(), kp2.public_key()], 500_000);
+    let mut channel_b = create_test_channel(&[kp2.public_key(), kp3.public_key()], 800_000);
+
+    let mut wal = ChannelWal::open(&wal_path).unwrap();
+    wal.log_snapshot(&channel_a).unwrap();
+    wal.log_snapshot(&channel_b).unwrap();
+    for seq in 1..=5 {
+        let update = transfer(&channel_a, &kp1, &kp2, 1_000, seq);
+        wal.apply_update(&mut channel_a, &update).unwrap();
+        let update = transfer(&channel_b, &kp3, &kp2, 2_000, seq);
+        wal.apply_update(&mut channel_b, &update).unwrap();
+    }
+    let len_before = fs::metadata(&wal_path).unwrap().len();
+
+    wal.compact([&channel_a, &channel_b]).unwrap();
+    assert!(fs::metadata(&wal_path).unwrap().len() < len_before);
+
+    let update = transfer(&channel_a, &kp2, &kp1, 500, 6);
+    wal.apply_update(&mut channel_a, &update).unwrap();
+
+    let recovered = ChannelWal::open(&wal_path).unwrap().recover().unwrap();
+    assert_eq!(recovered.len(), 2);
+    assert_eq!(recovered[&channel_a.channel_id], channel_a);
+    assert_eq!(recovered[&channel_b.channel_id], channel_b);
+
+    cleanup_wal(&wal_path);
+}
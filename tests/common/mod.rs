@@ -1,6 +1,7 @@
 use primitive_types::H256;
 use state_channel_node::utxo::models::{Transaction, Input, Output};
-use std::path::PathBuf;
+use state_channel_node::utxo::store::SdbStore;
+use std::path::{Path, PathBuf};
 
 /// Test utilities and common setup
 #[allow(dead_code)]
@@ -15,6 +16,20 @@ pub mod test_utils {
         }
     }
     
+    /// Reopen a test database to simulate a node restart
+    /// 
+    /// sled releases its file lock from a background thread after the previous
+    /// handle is dropped, so opening is retried for a short while.
+    pub fn reopen_store(test_db_path: &Path) -> SdbStore {
+        for _ in 0..50 {
+            if let Ok(store) = SdbStore::new(test_db_path) {
+                return store;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        SdbStore::new(test_db_path).expect("Failed to reopen test store")
+    }
+    
     /// Create a test transaction with specified outputs
     pub fn create_transaction(outputs: Vec<Output>) -> Transaction {
         let mut tx = Transaction {
@@ -91,4 +106,116 @@ pub mod utxo {
             lock_script: vec![], // Empty lock script for testing
         }
     }
+
+    /// Create a test output with specified value locked to `public_key_hash`
+    pub fn create_output_for(value: u64, public_key_hash: &[u8]) -> Output {
+        Output {
+            value,
+            public_key_hash: public_key_hash.to_vec(),
+            lock_script: vec![],
+        }
+    }
+}
+
+/// Channel test utilities
+#[allow(dead_code)]
+pub mod channel {
+    use super::*;
+    use std::collections::HashMap;
+    use state_channel_node::channel::funding::{self, ChannelOpening, FundingParams};
+    use state_channel_node::channel::revocation::RevocationSecrets;
+    use state_channel_node::channel::state::{ChannelState, StateUpdate};
+    use state_channel_node::channel::transitions::StateUpdateForSigning;
+    use state_channel_node::crypto::{KeyPair, PublicKey};
+    use state_channel_node::utxo::cache::UtxoCache;
+
+    /// Fresh revocation secrets for every participant
+    pub fn revocation_secrets(keypairs: &[&KeyPair]) -> HashMap<PublicKey, RevocationSecrets> {
+        keypairs.iter().map(|kp| (kp.public_key(), RevocationSecrets::generate())).collect()
+    }
+
+    /// Revocation hashes of states 0 and 1, as exchanged before opening
+    pub fn initial_revocation_hashes(secrets: &HashMap<PublicKey, RevocationSecrets>) -> HashMap<PublicKey, [[u8; 32]; 2]> {
+        secrets.iter()
+            .map(|(pk, secrets)| (pk.clone(), [secrets.revocation_hash(0).unwrap(), secrets.revocation_hash(1).unwrap()]))
+            .collect()
+    }
+
+    /// Open and confirm a channel funded by the first keypair with 50_000,
+    /// returning every participant's revocation secrets
+    pub fn open_channel(
+        test_db_path: &PathBuf,
+        keypairs: &[&KeyPair],
+    ) -> (UtxoCache, ChannelState, HashMap<PublicKey, RevocationSecrets>) {
+        test_utils::cleanup_test_db(test_db_path);
+        let store = SdbStore::new(test_db_path).expect("Failed to create test store");
+        let cache = UtxoCache::new(store);
+        let funder = keypairs[0];
+        let coinbase = utxo::create_transaction(vec![utxo::create_output_for(100_000, &funder.public_key().public_key_hash())]);
+        cache.connect_block(1, &[coinbase]).unwrap();
+
+        let secrets = revocation_secrets(keypairs);
+        let participants: Vec<_> = keypairs.iter().map(|kp| kp.public_key()).collect();
+        let balances = HashMap::from([(funder.public_key(), 50_000)]);
+        let mut opening = ChannelOpening::propose(
+            &cache, &funder.public_key(), participants, balances, 50_000,
+            initial_revocation_hashes(&secrets), &FundingParams::new(1),
+        ).unwrap();
+        for kp in keypairs {
+            let signatures = opening.proposal().sign_commitments(kp).unwrap();
+            opening.add_commitment_signatures(signatures).unwrap();
+        }
+        let opened = opening.release_funding(funder).unwrap();
+        cache.connect_block(2, std::slice::from_ref(&opened.funding_tx)).unwrap();
+
+        let mut channel = opened.channel;
+        funding::confirm_funding(&mut channel, &cache, 1).unwrap();
+        (cache, channel, secrets)
+    }
+
+    /// Update moving `amount` from `from` to `to`, signed by both and revoking
+    /// every participant's commitment of the current state
+    pub fn signed_transfer(
+        channel: &ChannelState,
+        secrets: &HashMap<PublicKey, RevocationSecrets>,
+        from: &KeyPair,
+        to: &KeyPair,
+        amount: i64,
+    ) -> StateUpdate {
+        let sequence = channel.sequence_number + 1;
+        let changes = HashMap::from([(from.public_key(), -amount), (to.public_key(), amount)]);
+        let mut affected = vec![from.public_key(), to.public_key()];
+        affected.sort_by_key(|pk| pk.as_bytes());
+
+        let mut message = StateUpdateForSigning::new(sequence, channel.channel_id, &changes, &affected);
+        message.timestamp = sequence;
+        let message = bincode::serialize(&message).unwrap();
+        let signatures = affected.iter()
+            .map(|pk| if *pk == from.public_key() { from.sign(&message) } else { to.sign(&message) })
+            .collect();
+        let revocations = channel.participants.iter()
+            .map(|pk| secrets[pk].revoke(channel.channel_id, pk, channel.sequence_number).unwrap())
+            .collect();
+
+        StateUpdate {
+            sequence_number: sequence,
+            balance_changes: changes,
+            signatures,
+            affected_participants: affected,
+            timestamp: sequence,
+            revocations,
+        }
+    }
+
+    /// Apply a signed transfer of `amount` from `from` to `to`
+    pub fn transfer(
+        channel: &mut ChannelState,
+        secrets: &HashMap<PublicKey, RevocationSecrets>,
+        from: &KeyPair,
+        to: &KeyPair,
+        amount: i64,
+    ) {
+        let update = signed_transfer(channel, secrets, from, to, amount);
+        channel.apply_update(&update).expect("Failed to apply transfer");
+    }
 }
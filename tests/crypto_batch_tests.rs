@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use state_channel_node::channel::state::StateUpdate;
+use state_channel_node::channel::transitions::{self, ChannelError};
+use state_channel_node::crypto::{self, KeyPair, PublicKey, Signature};
+use state_channel_node::crypto::batch::{self, BatchError};
+
+mod test_helpers;
+use test_helpers::{create_test_channel, sign_update, sort_participants};
+
+fn signed_messages(count: usize) -> (Vec<Vec<u8>>, Vec<Signature>, Vec<PublicKey>) {
+    let keys: Vec<KeyPair> = (0..count).map(|_| crypto::generate_keypair()).collect();
+    let messages: Vec<Vec<u8>> = (0..count).map(|i| format!("state update {}", i).into_bytes()).collect();
+    let signatures = keys.iter().zip(&messages).map(|(key, message)| key.sign(message)).collect();
+    (messages, signatures, keys.iter().map(KeyPair::public_key).collect())
+}
+
+#[test]
+fn test_verify_batch() {
+    let (messages, mut signatures, public_keys) = signed_messages(16);
+    let message_refs: Vec<&[u8]> = messages.iter().map(Vec::as_slice).collect();
+    assert!(batch::verify_batch(&message_refs, &signatures, &public_keys).is_ok());
+    assert!(batch::verify_batch(&[], &[], &[]).is_ok());
+    assert!(batch::verify_batch(&message_refs[..1], &signatures[..1], &public_keys[..1]).is_ok());
+
+    // The fallback pinpoints the first bad signature
+    signatures.swap(5, 9);
+    assert_eq!(
+        batch::verify_batch(&message_refs, &signatures, &public_keys),
+        Err(BatchError::InvalidSignature(5))
+    );
+    signatures.swap(5, 9);
+
+    // A signature over another message, and a malformed signature
+    let mut wrong_message = message_refs.clone();
+    wrong_message[12] = b"forged";
+    assert_eq!(
+        batch::verify_batch(&wrong_message, &signatures, &public_keys),
+        Err(BatchError::InvalidSignature(12))
+    );
+    let mut bytes = signatures[3].to_bytes();
+    bytes[63] = 0xff;
+    signatures[3] = Signature::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        batch::verify_batch(&message_refs, &signatures, &public_keys),
+        Err(BatchError::InvalidSignature(3))
+    );
+
+    assert_eq!(
+        batch::verify_batch(&message_refs[1..], &signatures, &public_keys),
+        Err(BatchError::LengthMismatch { messages: 15, signatures: 16, public_keys: 16 })
+    );
+}
+
+#[test]
+fn test_verify_update_signatures_in_parallel() {
+    let keys: Vec<KeyPair> = (0..4).map(|_| crypto::generate_keypair()).collect();
+    let participants: Vec<PublicKey> = keys.iter().map(KeyPair::public_key).collect();
+    let channel = create_test_channel(&participants, 1_000_000);
+
+    // A long history of payments around the ring of participants
+    let mut updates: Vec<StateUpdate> = (0..40u64)
+        .map(|i| {
+            let from = &keys[i as usize % keys.len()];
+            let to = &keys[(i as usize + 1) % keys.len()];
+            let mut affected = vec![from.public_key(), to.public_key()];
+            sort_participants(&mut affected);
+            let changes = HashMap::from([(from.public_key(), -1_000), (to.public_key(), 1_000)]);
+            let signatures = affected
+                .iter()
+                .map(|pk| {
+                    let signer = if *pk == from.public_key() { from } else { to };
+                    sign_update(signer, &affected, i + 1, &changes, i, channel.channel_id)
+                })
+                .collect();
+            StateUpdate {
+                sequence_number: i + 1,
+                balance_changes: changes,
+                signatures,
+                affected_participants: affected,
+                timestamp: i,
+                revocations: Vec::new(),
+            }
+        })
+        .collect();
+
+    let results = transitions::verify_update_signatures(&channel, &updates);
+    assert_eq!(results.len(), updates.len());
+    assert!(results.iter().all(Result::is_ok));
+
+    // Tamper with a few updates; only those are reported
+    updates[7].signatures.swap(0, 1);
+    updates[23].timestamp += 1;
+    updates[31].signatures.pop();
+    updates[15].affected_participants.reverse();
+    updates[15].signatures.reverse();
+    updates[38].affected_participants[0] = crypto::generate_keypair().public_key();
+
+    let results = transitions::verify_update_signatures(&channel, &updates);
+    for (i, result) in results.iter().enumerate() {
+        match i {
+            7 | 23 => assert_eq!(result, &Err(ChannelError::InvalidSignature(0))),
+            15 | 31 => assert_eq!(result, &Err(ChannelError::InvalidSignatureCount)),
+            38 => assert_eq!(result, &Err(ChannelError::UnknownParticipant)),
+            _ => assert!(result.is_ok(), "update {} should verify", i),
+        }
+    }
+
+    assert!(transitions::verify_update_signatures(&channel, &[]).is_empty());
+}
@@ -0,0 +1,121 @@
+use state_channel_node::crypto::hd::{
+    ChannelKeys, ChildNumber, Curve, DerivationPath, ExtendedPrivateKey, HdError, KeyFamily, Seed,
+};
+use state_channel_node::crypto::SignatureScheme;
+
+fn test_vector_seed() -> Seed {
+    Seed::from_bytes(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap()
+}
+
+#[test]
+fn test_bip32_vector() {
+    // BIP32 test vector 1
+    let master = ExtendedPrivateKey::from_seed(Curve::Secp256k1, &test_vector_seed()).unwrap();
+    assert_eq!(
+        hex::encode(master.chain_code()),
+        "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"
+    );
+
+    let child = master.derive_path(&"m/0'".parse().unwrap()).unwrap();
+    assert_eq!(
+        hex::encode(child.secret_bytes()),
+        "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+    );
+    assert_eq!(
+        hex::encode(child.chain_code()),
+        "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141"
+    );
+    assert_eq!(child.depth(), 1);
+    assert_eq!(child.parent_fingerprint(), master.fingerprint());
+    assert_eq!(hex::encode(master.fingerprint()), "3442193e");
+}
+
+#[test]
+fn test_slip10_ed25519_vector() {
+    // SLIP-10 ed25519 test vector 1
+    let master = ExtendedPrivateKey::from_seed(Curve::Ed25519, &test_vector_seed()).unwrap();
+    assert_eq!(
+        hex::encode(master.secret_bytes()),
+        "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+    );
+    assert_eq!(
+        hex::encode(master.chain_code()),
+        "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+    );
+
+    let child = master.derive_child(ChildNumber::Hardened(0)).unwrap();
+    assert_eq!(
+        hex::encode(child.secret_bytes()),
+        "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+    );
+    assert_eq!(
+        hex::encode(child.chain_code()),
+        "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69"
+    );
+
+    assert_eq!(master.derive_child(ChildNumber::Normal(0)).unwrap_err(), HdError::NonHardenedEd25519);
+    assert!(master.to_extended_public_key().is_none());
+}
+
+#[test]
+fn test_public_derivation_matches_private() {
+    let master = ExtendedPrivateKey::from_seed(Curve::Secp256k1, &Seed::generate()).unwrap();
+    let account = master.derive_path(&"m/1017h/0h/3h".parse().unwrap()).unwrap();
+    let tail: DerivationPath = vec![ChildNumber::Normal(0), ChildNumber::Normal(42)].into();
+
+    let from_private = account.derive_path(&tail).unwrap()
+        .keypair(SignatureScheme::EcdsaSecp256k1).unwrap()
+        .public_key();
+    let xpub = account.to_extended_public_key().unwrap();
+    let from_public = xpub.derive_path(&tail).unwrap()
+        .public_key(SignatureScheme::EcdsaSecp256k1).unwrap();
+    assert_eq!(from_private, from_public);
+
+    assert_eq!(xpub.derive_child(ChildNumber::Hardened(0)).unwrap_err(), HdError::HardenedFromPublic);
+}
+
+#[test]
+fn test_derivation_paths() {
+    let path: DerivationPath = "m/1017'/0'/1'/0/5".parse().unwrap();
+    assert_eq!(path.as_slice().len(), 5);
+    assert_eq!(path.as_slice()[2], ChildNumber::Hardened(1));
+    assert_eq!(path.as_slice()[4].to_index(), 5);
+    assert_eq!(path.to_string(), "m/1017'/0'/1'/0/5");
+    assert_eq!("m/1017h/0h".parse::<DerivationPath>().unwrap().to_string(), "m/1017'/0'");
+    assert_eq!("m".parse::<DerivationPath>().unwrap(), DerivationPath::master());
+    assert_eq!(ChildNumber::from_index(0x8000_0002), ChildNumber::Hardened(2));
+
+    for bad in ["", "1/2", "m/x", "m/2147483648", "m//1"] {
+        assert!(matches!(bad.parse::<DerivationPath>(), Err(HdError::InvalidPath(_))), "{bad}");
+    }
+
+    assert!(matches!(Seed::from_bytes(&[0; 8]), Err(HdError::InvalidSeedLength(8))));
+}
+
+#[test]
+fn test_channel_keys_restore_from_seed() {
+    let seed = Seed::generate();
+
+    for scheme in [SignatureScheme::EcdsaSecp256k1, SignatureScheme::Ed25519] {
+        let keys = ChannelKeys::derive(&seed, 7, scheme).unwrap();
+        let restored = ChannelKeys::derive(&Seed::from_bytes(seed.as_bytes()).unwrap(), 7, scheme).unwrap();
+        assert_eq!(keys.funding.public_key(), restored.funding.public_key());
+        assert_eq!(keys.revocation_basepoint.public_key(), restored.revocation_basepoint.public_key());
+        assert_eq!(keys.funding.scheme(), scheme);
+
+        // Distinct keys per purpose and per channel
+        let other = ChannelKeys::derive(&seed, 8, scheme).unwrap();
+        assert_ne!(keys.funding.public_key(), other.funding.public_key());
+        assert_ne!(keys.funding.public_key(), keys.payment_basepoint.public_key());
+        assert_ne!(keys.payment_basepoint.public_key(), keys.delayed_payment_basepoint.public_key());
+    }
+
+    assert_eq!(
+        ChannelKeys::path(Curve::Secp256k1, KeyFamily::RevocationBase, 7).unwrap().to_string(),
+        "m/1017'/0'/1'/0/7"
+    );
+    assert_eq!(
+        ChannelKeys::path(Curve::Ed25519, KeyFamily::Funding, 7).unwrap().to_string(),
+        "m/1017'/0'/0'/0'/7'"
+    );
+}
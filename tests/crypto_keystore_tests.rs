@@ -0,0 +1,140 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use state_channel_node::crypto;
+use state_channel_node::crypto::keystore::{KdfParams, Keystore, KeystoreError};
+use common::test_utils;
+
+/// Cheap Argon2 parameters so tests stay fast
+const TEST_KDF: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+
+fn open(path: &Path) -> Keystore {
+    Keystore::open(path).unwrap().with_kdf_params(TEST_KDF)
+}
+
+#[test]
+fn test_store_and_load() {
+    let path = PathBuf::from("test_keystore_roundtrip");
+    test_utils::cleanup_test_db(&path);
+    let keystore = open(&path);
+    let keypair = crypto::generate_keypair();
+
+    keystore.store(&keypair, "channel-1", "correct horse").unwrap();
+    assert!(keystore.contains(&keypair.public_key()));
+    assert!(matches!(
+        keystore.store(&keypair, "again", "correct horse"),
+        Err(KeystoreError::AlreadyExists(_))
+    ));
+
+    // A fresh handle (i.e. a node restart) can load and sign with the key
+    let restarted = open(&path);
+    let loaded = restarted.load(&keypair.public_key(), "correct horse").unwrap();
+    assert_eq!(loaded.public_key(), keypair.public_key());
+    assert_eq!(loaded.signing_key.to_bytes(), keypair.signing_key.to_bytes());
+    let signature = loaded.sign(b"state update");
+    assert!(keypair.public_key().verify_strict(&signature, b"state update"));
+
+    assert!(matches!(
+        restarted.load(&keypair.public_key(), "wrong password"),
+        Err(KeystoreError::DecryptionFailed)
+    ));
+    let unknown = crypto::generate_keypair().public_key();
+    assert!(matches!(restarted.load(&unknown, "correct horse"), Err(KeystoreError::NotFound(_))));
+
+    // The secret key never appears in plain text
+    let file = fs::read_to_string(path.join(format!("{}.json", hex::encode(keypair.public_key().as_bytes())))).unwrap();
+    assert!(!file.contains(&hex::encode(keypair.signing_key.to_bytes())));
+
+    // Cleanup
+    test_utils::cleanup_test_db(&path);
+}
+
+#[test]
+fn test_list_label_and_remove() {
+    let path = PathBuf::from("test_keystore_labels");
+    test_utils::cleanup_test_db(&path);
+    let keystore = open(&path);
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+
+    keystore.store(&alice, "alice", "pw").unwrap();
+    keystore.store(&bob, "bob", "pw").unwrap();
+
+    let mut labels: Vec<_> = keystore.list().unwrap().into_iter().map(|info| info.label).collect();
+    labels.sort();
+    assert_eq!(labels, vec!["alice", "bob"]);
+
+    // Relabeling doesn't need the password and keeps the key loadable
+    keystore.set_label(&alice.public_key(), "alice-funding").unwrap();
+    assert_eq!(keystore.get(&alice.public_key()).unwrap().label, "alice-funding");
+    assert!(keystore.load(&alice.public_key(), "pw").is_ok());
+
+    keystore.remove(&bob.public_key()).unwrap();
+    assert_eq!(keystore.list().unwrap().len(), 1);
+    assert!(matches!(keystore.remove(&bob.public_key()), Err(KeystoreError::NotFound(_))));
+
+    // Cleanup
+    test_utils::cleanup_test_db(&path);
+}
+
+#[test]
+fn test_tampered_key_file_rejected() {
+    let path = PathBuf::from("test_keystore_tamper");
+    test_utils::cleanup_test_db(&path);
+    let keystore = open(&path);
+    let alice = crypto::generate_keypair();
+    let mallory = crypto::generate_keypair();
+    keystore.store(&alice, "alice", "pw").unwrap();
+    keystore.store(&mallory, "mallory", "pw").unwrap();
+
+    // Swapping in another key's ciphertext fails authentication
+    let file_for = |key: &crypto::KeyPair| path.join(format!("{}.json", hex::encode(key.public_key().as_bytes())));
+    let alice_file: serde_json::Value = serde_json::from_str(&fs::read_to_string(file_for(&alice)).unwrap()).unwrap();
+    let mut mallory_file: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(file_for(&mallory)).unwrap()).unwrap();
+    for field in ["salt", "nonce", "ciphertext"] {
+        mallory_file[field] = alice_file[field].clone();
+    }
+    fs::write(file_for(&mallory), serde_json::to_vec(&mallory_file).unwrap()).unwrap();
+    assert!(matches!(
+        keystore.load(&mallory.public_key(), "pw"),
+        Err(KeystoreError::DecryptionFailed)
+    ));
+
+    // Cleanup
+    test_utils::cleanup_test_db(&path);
+}
+
+#[test]
+fn test_excessive_kdf_params_and_mismatched_keys_rejected() {
+    let path = PathBuf::from("test_keystore_limits");
+    test_utils::cleanup_test_db(&path);
+    let keystore = open(&path);
+    let alice = crypto::generate_keypair();
+    keystore.store(&alice, "alice", "pw").unwrap();
+
+    // A key file demanding an absurd KDF cost is refused before deriving
+    let file_path = path.join(format!("{}.json", hex::encode(alice.public_key().as_bytes())));
+    let file: serde_json::Value = serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+    for (field, value) in [("memory_kib", u32::MAX), ("iterations", u32::MAX), ("parallelism", 1_000)] {
+        let mut tampered = file.clone();
+        tampered["kdf"][field] = value.into();
+        fs::write(&file_path, serde_json::to_vec(&tampered).unwrap()).unwrap();
+        assert!(matches!(
+            keystore.load(&alice.public_key(), "pw"),
+            Err(KeystoreError::KdfParamsTooHigh(_))
+        ));
+    }
+    fs::write(&file_path, serde_json::to_vec(&file).unwrap()).unwrap();
+    assert!(keystore.load(&alice.public_key(), "pw").is_ok());
+
+    // A key pair whose verifying key belongs to another signing key is not stored
+    let bob = crypto::generate_keypair();
+    let mismatched = crypto::KeyPair { signing_key: bob.signing_key.clone(), verifying_key: alice.public_key() };
+    assert!(matches!(keystore.store(&mismatched, "bob", "pw"), Err(KeystoreError::KeyMismatch)));
+    assert!(!keystore.contains(&bob.public_key()));
+
+    // Cleanup
+    test_utils::cleanup_test_db(&path);
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use k256::schnorr::{SigningKey, VerifyingKey};
+use rand::rngs::OsRng;
+use state_channel_node::channel::state::{AggregateStateUpdate, ChannelState};
+use state_channel_node::channel::wal::{ChannelWal, WalError};
+use state_channel_node::channel::transitions::StateUpdateForSigning;
+use state_channel_node::crypto::{self, SchemeSignature};
+use state_channel_node::crypto::musig::{
+    KeyAggContext, MusigError, PartialSignature, PublicNonce, SecretNonce, SigningSession,
+};
+
+mod test_helpers;
+use test_helpers::{create_test_channel, sort_participants};
+
+/// Run both rounds for `signers` and return the session and partial signatures
+fn run_session(key_agg: &KeyAggContext, signers: &[SigningKey], message: &[u8]) -> (SigningSession, Vec<PartialSignature>) {
+    // Signers are indexed in key aggregation order
+    let mut signers: Vec<&SigningKey> = signers.iter().collect();
+    signers.sort_by_key(|key| key_agg.signer_index(key.verifying_key()));
+
+    let secret_nonces: Vec<SecretNonce> = signers.iter().map(|key| SecretNonce::generate(key, key_agg, message)).collect();
+    let public_nonces: Vec<PublicNonce> = secret_nonces.iter().map(SecretNonce::public_nonce).collect();
+    let session = SigningSession::new(key_agg, &public_nonces, message).unwrap();
+    let partials = signers
+        .into_iter()
+        .zip(secret_nonces)
+        .map(|(key, nonce)| session.sign(key, nonce).unwrap())
+        .collect();
+    (session, partials)
+}
+
+/// An update of `channel` with `changes`, signed by all of `signers` under `key_agg`
+fn aggregate_update(
+    channel: &ChannelState,
+    key_agg: &KeyAggContext,
+    signers: &[SigningKey],
+    changes: HashMap<crypto::PublicKey, i64>,
+) -> AggregateStateUpdate {
+    let mut affected: Vec<_> = changes.keys().cloned().collect();
+    sort_participants(&mut affected);
+    let sequence_number = channel.sequence_number + 1;
+    let mut message = StateUpdateForSigning::new(sequence_number, channel.channel_id, &changes, &affected);
+    message.timestamp = 42;
+    let message = bincode::serialize(&message).unwrap();
+
+    let (session, partials) = run_session(key_agg, signers, &message);
+    AggregateStateUpdate {
+        sequence_number,
+        balance_changes: changes,
+        affected_participants: affected,
+        timestamp: 42,
+        revocations: Vec::new(),
+        signature: SchemeSignature::Schnorr(session.aggregate(&partials).unwrap()),
+    }
+}
+
+#[test]
+fn test_two_party_signing() {
+    let alice = SigningKey::random(&mut OsRng);
+    let bob = SigningKey::random(&mut OsRng);
+    let public_keys = [*alice.verifying_key(), *bob.verifying_key()];
+    let key_agg = KeyAggContext::new(&public_keys).unwrap();
+
+    // Key order doesn't matter, and the aggregate is neither signer's key
+    let reversed = KeyAggContext::new(&[public_keys[1], public_keys[0]]).unwrap();
+    assert_eq!(key_agg.aggregate_key(), reversed.aggregate_key());
+    assert!(!public_keys.contains(&key_agg.aggregate_key()));
+
+    let message = b"channel state commitment";
+    let (session, partials) = run_session(&key_agg, &[alice.clone(), bob.clone()], message);
+    for (i, partial) in partials.iter().enumerate() {
+        assert!(session.verify_partial(i, partial));
+        assert_eq!(PartialSignature::from_bytes(&partial.to_bytes()).unwrap(), *partial);
+    }
+
+    // The aggregate is a plain BIP340 signature under the aggregate key
+    let signature = session.aggregate(&partials).unwrap();
+    let aggregate_key: VerifyingKey = key_agg.aggregate_key();
+    assert!(aggregate_key.verify_raw(message, &signature).is_ok());
+    assert!(aggregate_key.verify_raw(b"another state", &signature).is_err());
+
+    // A single signer degenerates to ordinary Schnorr
+    let solo = KeyAggContext::new(&public_keys[..1]).unwrap();
+    let (session, partials) = run_session(&solo, std::slice::from_ref(&alice), message);
+    let signature = session.aggregate(&partials).unwrap();
+    assert!(solo.aggregate_key().verify_raw(message, &signature).is_ok());
+}
+
+#[test]
+fn test_session_errors() {
+    let keys: Vec<SigningKey> = (0..3).map(|_| SigningKey::random(&mut OsRng)).collect();
+    let public_keys: Vec<VerifyingKey> = keys.iter().map(|key| *key.verifying_key()).collect();
+    let key_agg = KeyAggContext::new(&public_keys).unwrap();
+    let message = b"state 7";
+
+    assert_eq!(KeyAggContext::new(&[]), Err(MusigError::NoSigners));
+
+    let (session, mut partials) = run_session(&key_agg, &keys, message);
+    partials.swap(0, 2);
+    assert_eq!(session.aggregate(&partials), Err(MusigError::PartialSignatureVerificationFailed(0)));
+    assert_eq!(
+        session.aggregate(&partials[..2]),
+        Err(MusigError::PartialSignatureCountMismatch { expected: 3, got: 2 })
+    );
+
+    let nonces: Vec<SecretNonce> = keys.iter().map(|key| SecretNonce::generate(key, &key_agg, message)).collect();
+    let public_nonces: Vec<PublicNonce> = nonces.iter().map(SecretNonce::public_nonce).collect();
+    assert_eq!(
+        SigningSession::new(&key_agg, &public_nonces[..2], message).unwrap_err(),
+        MusigError::NonceCountMismatch { expected: 3, got: 2 }
+    );
+    for nonce in &public_nonces {
+        assert_eq!(PublicNonce::from_bytes(&nonce.to_bytes()).unwrap(), *nonce);
+    }
+    assert_eq!(PublicNonce::from_bytes(&[0u8; 66]), Err(MusigError::InvalidNonce));
+
+    // Signing with a nonce that wasn't shared, or as an outsider, is refused
+    let session = SigningSession::new(&key_agg, &public_nonces, message).unwrap();
+    let signer = &keys[0];
+    let fresh = SecretNonce::generate(signer, &key_agg, message);
+    assert_eq!(session.sign(signer, fresh).unwrap_err(), MusigError::NonceMismatch);
+    let outsider = SigningKey::random(&mut OsRng);
+    let nonce = SecretNonce::generate(&outsider, &key_agg, message);
+    assert_eq!(session.sign(&outsider, nonce).unwrap_err(), MusigError::UnknownSigner);
+}
+
+#[test]
+fn test_channel_update_with_aggregate_signature() {
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let participants = vec![alice.public_key(), bob.public_key()];
+    let schnorr_keys = [SigningKey::random(&mut OsRng), SigningKey::random(&mut OsRng)];
+    let key_agg = KeyAggContext::new(&[*schnorr_keys[0].verifying_key(), *schnorr_keys[1].verifying_key()]).unwrap();
+    let mut channel = create_test_channel(&participants, 1_000).with_aggregate_key(&key_agg);
+
+    let mut affected = participants.clone();
+    sort_participants(&mut affected);
+    let changes = HashMap::from([(alice.public_key(), -300), (bob.public_key(), 300)]);
+    let mut message = StateUpdateForSigning::new(1, channel.channel_id, &changes, &affected);
+    message.timestamp = 42;
+    let message = bincode::serialize(&message).unwrap();
+
+    let (session, partials) = run_session(&key_agg, &schnorr_keys, &message);
+    let mut update = AggregateStateUpdate {
+        sequence_number: 1,
+        balance_changes: changes,
+        affected_participants: affected,
+        timestamp: 42,
+        revocations: Vec::new(),
+        signature: SchemeSignature::Schnorr(session.aggregate(&partials).unwrap()),
+    };
+
+    // Without an aggregate key the channel only takes individual signatures
+    let mut plain = create_test_channel(&participants, 1_000);
+    assert_eq!(plain.apply_aggregate_update(&update), Err("Channel has no aggregate key"));
+
+    // Tampering with any signed field invalidates the signature
+    update.timestamp += 1;
+    assert_eq!(channel.apply_aggregate_update(&update), Err("Invalid signatures"));
+    update.timestamp -= 1;
+
+    channel.apply_aggregate_update(&update).unwrap();
+    assert_eq!(channel.sequence_number, 1);
+    assert_eq!(channel.balances[&alice.public_key()], 700);
+    assert_eq!(channel.balances[&bob.public_key()], 1_300);
+
+    // Replays are rejected by the sequence number check
+    assert_eq!(channel.apply_aggregate_update(&update), Err("Invalid sequence number"));
+}
+
+#[test]
+fn test_aggregate_update_rejections_leave_channel_untouched() {
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let participants = vec![alice.public_key(), bob.public_key()];
+    let schnorr_keys = [SigningKey::random(&mut OsRng), SigningKey::random(&mut OsRng)];
+    let key_agg = KeyAggContext::new(&[*schnorr_keys[0].verifying_key(), *schnorr_keys[1].verifying_key()]).unwrap();
+    let mut channel = create_test_channel(&participants, 1_000).with_aggregate_key(&key_agg);
+    let before = channel.clone();
+
+    // Even with everyone's signature, an update can't change the channel's value
+    let minted = HashMap::from([(alice.public_key(), 500), (bob.public_key(), 0)]);
+    let update = aggregate_update(&channel, &key_agg, &schnorr_keys, minted);
+    assert_eq!(channel.apply_aggregate_update(&update), Err("Non-zero balance change"));
+    assert_eq!(channel, before);
+
+    // Overdrawing one side is rejected without applying the other side's credit
+    let overdraw = HashMap::from([(alice.public_key(), -5_000), (bob.public_key(), 5_000)]);
+    let update = aggregate_update(&channel, &key_agg, &schnorr_keys, overdraw);
+    assert_eq!(channel.apply_aggregate_update(&update), Err("Negative balance"));
+    assert_eq!(channel, before);
+}
+
+#[test]
+fn test_wal_recovers_aggregate_updates() {
+    let wal_path = PathBuf::from("test_wal_aggregate.log");
+    let _ = fs::remove_file(&wal_path);
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let participants = vec![alice.public_key(), bob.public_key()];
+    let schnorr_keys = [SigningKey::random(&mut OsRng), SigningKey::random(&mut OsRng)];
+    let key_agg = KeyAggContext::new(&[*schnorr_keys[0].verifying_key(), *schnorr_keys[1].verifying_key()]).unwrap();
+    let mut channel = create_test_channel(&participants, 1_000).with_aggregate_key(&key_agg);
+
+    let mut wal = ChannelWal::open(&wal_path).unwrap();
+    wal.log_snapshot(&channel).unwrap();
+    let changes = HashMap::from([(alice.public_key(), -300), (bob.public_key(), 300)]);
+    let update = aggregate_update(&channel, &key_agg, &schnorr_keys, changes);
+    wal.apply_aggregate_update(&mut channel, &update).unwrap();
+
+    // A rejected update is neither applied nor logged
+    assert!(matches!(
+        wal.apply_aggregate_update(&mut channel, &update),
+        Err(WalError::ApplyFailed("Invalid sequence number"))
+    ));
+    assert_eq!(channel.sequence_number, 1);
+
+    let recovered = ChannelWal::open(&wal_path).unwrap().recover().unwrap();
+    assert_eq!(recovered[&channel.channel_id], channel);
+    assert_eq!(recovered[&channel.channel_id].balances[&bob.public_key()], 1_300);
+
+    // Cleanup
+    drop(wal);
+    fs::remove_file(&wal_path).unwrap();
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use primitive_types::H256;
+use state_channel_node::channel::state::ChannelState;
+use state_channel_node::crypto::{self, SchemeKeyPair, SchemePublicKey, SchemeSignature, SignatureScheme};
+use state_channel_node::utxo::models::{Input, Output, SighashType, Transaction};
+use state_channel_node::utxo::script::{self, Script, ScriptError};
+
+const SCHEMES: [SignatureScheme; 3] = [
+    SignatureScheme::Ed25519,
+    SignatureScheme::EcdsaSecp256k1,
+    SignatureScheme::SchnorrSecp256k1,
+];
+
+#[test]
+fn test_sign_and_verify_each_scheme() {
+    let message = b"settlement transaction";
+
+    for scheme in SCHEMES {
+        let keypair = SchemeKeyPair::generate(scheme);
+        let public_key = keypair.public_key();
+        let signature = keypair.sign(message);
+        assert_eq!(public_key.scheme(), scheme);
+        assert_eq!(signature.scheme(), scheme);
+
+        assert!(public_key.verify(&signature, message));
+        assert!(!public_key.verify(&signature, b"another transaction"));
+
+        // Keys and signatures survive their byte encodings
+        let key_len = if scheme == SignatureScheme::EcdsaSecp256k1 { 33 } else { 32 };
+        assert_eq!(public_key.to_bytes().len(), key_len);
+        assert_eq!(SchemePublicKey::from_bytes(scheme, &public_key.to_bytes()).unwrap(), public_key);
+        assert_eq!(signature.to_bytes().len(), 64);
+        assert_eq!(SchemeSignature::from_bytes(scheme, &signature.to_bytes()).unwrap(), signature);
+
+        let json = serde_json::to_string(&(&public_key, &signature)).unwrap();
+        let decoded: (SchemePublicKey, SchemeSignature) = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, (public_key, signature));
+    }
+
+    // A signature never verifies under a key of another scheme
+    let ed25519 = SchemeKeyPair::generate(SignatureScheme::Ed25519);
+    let schnorr = SchemeKeyPair::generate(SignatureScheme::SchnorrSecp256k1);
+    assert!(!schnorr.public_key().verify(&ed25519.sign(message), message));
+
+    // Existing Ed25519 keys convert without re-encoding
+    let keypair = crypto::generate_keypair();
+    let signature = keypair.sign(message);
+    let public_key = SchemePublicKey::from(keypair.public_key());
+    assert!(public_key.verify(&signature.into(), message));
+    assert_eq!(public_key.public_key_hash(), keypair.public_key().public_key_hash());
+}
+
+#[test]
+fn test_ecdsa_low_s() {
+    let keypair = SchemeKeyPair::generate(SignatureScheme::EcdsaSecp256k1);
+    let message = b"low s";
+
+    for _ in 0..8 {
+        let SchemeSignature::Ecdsa(signature) = keypair.sign(message) else { panic!("expected ECDSA") };
+        assert!(signature.normalize_s().is_none());
+
+        // The high-S twin is valid ECDSA but malleable, so it's rejected
+        let high_s = k256::ecdsa::Signature::from_scalars(*signature.r(), -*signature.s()).unwrap();
+        assert!(high_s.normalize_s().is_some());
+        assert!(!keypair.public_key().verify(&SchemeSignature::Ecdsa(high_s), message));
+        assert!(keypair.public_key().verify(&SchemeSignature::Ecdsa(signature), message));
+    }
+
+    let signature = keypair.sign(message);
+    let der = signature.to_der().unwrap();
+    assert_eq!(SchemeSignature::from_der(&der).unwrap(), signature);
+}
+
+#[test]
+fn test_bip340_vector() {
+    // Test vector 0 from BIP340
+    let public_key = hex::decode("F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9").unwrap();
+    let signature = hex::decode(
+        "E907831F80848D1069A5371B402410364BDF1C5F8307B0084C55F1CE2DCA8215\
+         25F66A4A85EA8B71E482A74F382D2CE5EBEEE8FDB2172F477DF4900D310536C0",
+    ).unwrap();
+    let message = [0u8; 32];
+
+    let public_key = SchemePublicKey::from_bytes(SignatureScheme::SchnorrSecp256k1, &public_key).unwrap();
+    let signature = SchemeSignature::from_bytes(SignatureScheme::SchnorrSecp256k1, &signature).unwrap();
+    assert!(public_key.verify(&signature, &message));
+    assert!(!public_key.verify(&signature, &[1u8; 32]));
+}
+
+#[test]
+fn test_ecdsa_settlement_script() {
+    let keypair = SchemeKeyPair::generate(SignatureScheme::EcdsaSecp256k1);
+    let public_key = keypair.public_key().to_bytes();
+    let lock = Script::p2pkh(&keypair.public_key().public_key_hash());
+
+    let mut tx = Transaction {
+        version: 2,
+        inputs: vec![Input {
+            previous_output: H256::repeat_byte(1),
+            index: 0,
+            signature: vec![],
+            sequence: 0xffffffff,
+        }],
+        outputs: vec![Output { value: 900, public_key_hash: vec![2; 20], lock_script: vec![] }],
+        lock_time: 0,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+
+    let signature = script::sign_with_scheme(&tx, 0, &keypair, SighashType::All).unwrap();
+    let unlock = Script::new().push_data(&signature).push_data(&public_key);
+    assert!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0).is_ok());
+
+    tx.outputs[0].value = 800;
+    assert_eq!(
+        script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0),
+        Err(ScriptError::NullFail)
+    );
+
+    let schnorr = SchemeKeyPair::generate(SignatureScheme::SchnorrSecp256k1);
+    assert_eq!(
+        script::sign_with_scheme(&tx, 0, &schnorr, SighashType::All),
+        Err(ScriptError::UnsupportedScheme(SignatureScheme::SchnorrSecp256k1))
+    );
+}
+
+#[test]
+fn test_channel_declares_scheme() {
+    let participants = vec![crypto::generate_keypair().public_key(), crypto::generate_keypair().public_key()];
+    let balances: HashMap<_, _> = participants.iter().map(|key| (key.clone(), 100)).collect();
+
+    let channel = ChannelState::new(participants.clone(), balances.clone());
+    assert_eq!(channel.signature_scheme, SignatureScheme::Ed25519);
+
+    let channel = ChannelState::new(participants, balances)
+        .with_signature_scheme(SignatureScheme::EcdsaSecp256k1);
+    assert_eq!(channel.signature_scheme, SignatureScheme::EcdsaSecp256k1);
+}
@@ -92,6 +92,7 @@ async fn test_concurrent_state_updates() {
             signatures: vec![signature1, signature2],
             affected_participants,
             timestamp,
+            revocations: Vec::new(),
         };
         
         updates.push(update);
@@ -142,9 +143,10 @@ async fn test_concurrent_state_updates() {
                 println!("  Update timestamp: {}", update.timestamp);
                 println!("  Balance changes: {:?}", update.balance_changes);
                 
-                match validate_state_transition(&mut *channel_guard, &update) {
+                match validate_state_transition(&channel_guard, &update) {
                     Ok(_) => {
-                        channel_guard.apply_update(&update);
+                        channel_guard.apply_update(&update)
+                            .expect("validated update should apply");
                         success = true;
                         println!("✓ Successfully applied update {} (sequence {})", i + 1, i + 1);
                         
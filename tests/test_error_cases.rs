@@ -43,12 +43,14 @@ fn test_multi_hop_payment_with_insufficient_funds() {
         signatures,
         affected_participants,
         timestamp,
+        revocations: Vec::new(),
     };
     
     println!("\nAttempting to apply update that exceeds balance");
     match validate_state_transition(&channel, &update) {
         Ok(_) => {
-            channel.apply_update(&update.clone());
+            channel.apply_update(&update.clone())
+                .expect("validated update should apply");
             println!("Successfully applied update");
             println!("New balances: {:?}", channel.balances);
         },
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use ed25519_dalek::Signer;
 
 // Helper function to create a test channel with initial balances
+#[allow(dead_code)]
 pub fn create_test_channel(participants: &[PublicKey], initial_balance: i64) -> ChannelState {
     let mut balances = HashMap::new();
     for participant in participants {
@@ -14,6 +15,7 @@ pub fn create_test_channel(participants: &[PublicKey], initial_balance: i64) ->
 }
 
 // Helper function to create a state update with signatures
+#[allow(dead_code)]
 pub fn create_state_update(
     sequence_number: u64,
     changes: Vec<(PublicKey, i64)>,
@@ -28,9 +30,11 @@ pub fn create_state_update(
         signatures,
         affected_participants,
         timestamp,
+        revocations: Vec::new(),
     }
 }
 
+#[allow(dead_code)]
 pub fn sign_update(
     kp: &KeyPair,
     affected_participants: &[PublicKey],
@@ -53,5 +57,5 @@ pub fn sign_update(
 
 // Helper function to sort public keys consistently
 pub fn sort_participants(participants: &mut [PublicKey]) {
-    participants.sort_by(|a, b| a.as_bytes().cmp(&b.as_bytes()));
+    participants.sort_by_key(|a| a.as_bytes());
 }
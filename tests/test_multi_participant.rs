@@ -46,6 +46,7 @@ fn test_multi_participant_circular_payment() {
         signatures: signatures1,
         affected_participants: affected_participants1,
         timestamp: timestamp1,
+        revocations: Vec::new(),
     };
     
     // Second transfer: P2 -> P3
@@ -70,6 +71,7 @@ fn test_multi_participant_circular_payment() {
         signatures: signatures2,
         affected_participants: affected_participants2,
         timestamp: timestamp2,
+        revocations: Vec::new(),
     };
     
     // Third transfer: P3 -> P1
@@ -94,6 +96,7 @@ fn test_multi_participant_circular_payment() {
         signatures: signatures3,
         affected_participants: affected_participants3,
         timestamp: timestamp3,
+        revocations: Vec::new(),
     };
     
     // Apply all updates
@@ -101,7 +104,8 @@ fn test_multi_participant_circular_payment() {
         println!("\nApplying update {}", i + 1);
         match validate_state_transition(&channel, update) {
             Ok(_) => {
-                channel.apply_update(&update.clone());
+                channel.apply_update(&update.clone())
+                    .expect("validated update should apply");
                 println!("Successfully applied update {}", i + 1);
                 println!("New balances: {:?}", channel.balances);
             },
@@ -143,12 +147,13 @@ fn test_realistic_multi_participant_scenario() {
     // Create a series of random updates
     let mut rng = rand::thread_rng();
     let mut updates = Vec::new();
-    let mut timestamp = std::time::SystemTime::now()
+    let base_timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
         .as_secs();
     
     for seq in 1..=10 {
+        let timestamp = base_timestamp + seq - 1;  // Increment timestamp for each update
         // Randomly select two participants for this update
         let mut update_participants = participants.clone();
         update_participants.shuffle(&mut rng);
@@ -185,10 +190,10 @@ fn test_realistic_multi_participant_scenario() {
             signatures,
             affected_participants,
             timestamp,
+            revocations: Vec::new(),
         };
         
         updates.push(update);
-        timestamp += 1;  // Increment timestamp for each update
     }
     
     // Apply all updates
@@ -199,7 +204,8 @@ fn test_realistic_multi_participant_scenario() {
         
         match validate_state_transition(&channel, update) {
             Ok(_) => {
-                channel.apply_update(&update.clone());
+                channel.apply_update(&update.clone())
+                    .expect("validated update should apply");
                 println!("Successfully applied update {}", i + 1);
                 println!("New balances: {:?}", channel.balances);
             },
@@ -0,0 +1,164 @@
+mod common;
+
+use std::path::PathBuf;
+use state_channel_node::utxo::cache::{CacheConfig, UtxoCache, WritePolicy};
+use state_channel_node::utxo::store::SdbStore;
+use common::test_utils;
+use common::utxo;
+
+fn bounded(max_entries: usize, write_policy: WritePolicy) -> CacheConfig {
+    CacheConfig {
+        max_entries: Some(max_entries),
+        max_bytes: None,
+        write_policy,
+    }
+}
+
+#[test]
+fn test_lru_eviction_by_entry_count() {
+    let test_db_path = PathBuf::from("test_cache_lru.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::with_config(store, bounded(2, WritePolicy::WriteThrough));
+
+    let tx = utxo::create_transaction(vec![
+        utxo::create_output(1_000),
+        utxo::create_output(2_000),
+        utxo::create_output(3_000),
+    ]);
+    cache.add_transaction(&tx, Some(1)).unwrap();
+
+    let stats = cache.stats().unwrap();
+    assert_eq!(stats.entries, 2);
+    assert_eq!(stats.evictions, 1);
+    assert_eq!(stats.write_backs, 0);
+
+    // Output 0 was least recently used; it is read back from storage
+    assert_eq!(cache.get_utxo(tx.hash, 0).unwrap().unwrap().output.value, 1_000);
+    let stats = cache.stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (0, 1, 2));
+
+    // Touching output 0 makes output 2 the eviction candidate
+    assert!(cache.get_utxo(tx.hash, 0).unwrap().is_some());
+    assert!(cache.get_utxo(tx.hash, 1).unwrap().is_some());
+    let stats = cache.stats().unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 2, 3));
+    assert!(cache.get_utxo(tx.hash, 0).unwrap().is_some());
+    assert_eq!(cache.stats().unwrap().hits, 2);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_lru_eviction_by_memory_budget() {
+    let probe_path = PathBuf::from("test_cache_budget_probe.db");
+    let test_db_path = PathBuf::from("test_cache_budget.db");
+    test_utils::cleanup_test_db(&probe_path);
+    test_utils::cleanup_test_db(&test_db_path);
+
+    let outputs: Vec<_> = (1..=10).map(utxo::create_output).collect();
+    let tx = utxo::create_transaction(outputs);
+
+    // Measure the footprint of a single entry
+    let probe = UtxoCache::new(SdbStore::new(&probe_path).unwrap());
+    probe.add_transaction(&utxo::create_transaction(vec![utxo::create_output(1)]), None).unwrap();
+    let entry_bytes = probe.stats().unwrap().bytes;
+    assert!(entry_bytes > 0);
+
+    let config = CacheConfig {
+        max_entries: None,
+        max_bytes: Some(entry_bytes * 3 + entry_bytes / 2),
+        write_policy: WritePolicy::WriteThrough,
+    };
+    let cache = UtxoCache::with_config(SdbStore::new(&test_db_path).unwrap(), config);
+    cache.add_transaction(&tx, Some(1)).unwrap();
+
+    let stats = cache.stats().unwrap();
+    assert_eq!(stats.entries, 3);
+    assert_eq!(stats.bytes, entry_bytes * 3);
+    assert_eq!(stats.evictions, 7);
+
+    // Evicted entries are still served from storage
+    for index in 0..10 {
+        assert!(cache.get_utxo(tx.hash, index).unwrap().is_some());
+    }
+    assert!(cache.stats().unwrap().bytes <= entry_bytes * 3);
+
+    // Cleanup
+    drop(probe);
+    drop(cache);
+    test_utils::cleanup_test_db(&probe_path);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_write_back_persists_dirty_entries() {
+    let test_db_path = PathBuf::from("test_cache_write_back.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::with_config(store, bounded(2, WritePolicy::WriteBack));
+
+    let tx = utxo::create_transaction(vec![
+        utxo::create_output_for(1_000, &[0xaa; 20]),
+        utxo::create_output_for(2_000, &[0xaa; 20]),
+        utxo::create_output_for(3_000, &[0xaa; 20]),
+    ]);
+    cache.add_transaction(&tx, Some(1)).unwrap();
+
+    // Only the evicted entry has reached storage so far
+    let stats = cache.stats().unwrap();
+    assert_eq!(stats.evictions, 1);
+    assert_eq!(stats.write_backs, 1);
+
+    // Index queries see outputs still held only in memory
+    assert_eq!(cache.balance_of(&[0xaa; 20]).unwrap(), 6_000);
+    assert_eq!(cache.stats().unwrap().write_backs, 3);
+
+    // Dirty entries can be spent; they're persisted before the spend
+    let more = utxo::create_transaction(vec![utxo::create_output(4_000)]);
+    cache.add_transaction(&more, Some(2)).unwrap();
+    let spend = utxo::create_spending_transaction(&tx, &[2], vec![utxo::create_output(2_900)]);
+    cache.apply_transaction(&spend, None).unwrap();
+
+    // Dropping the cache flushes anything left
+    drop(cache);
+    let store = test_utils::reopen_store(&test_db_path);
+    assert!(store.get_utxo(tx.hash, 0).unwrap().is_some());
+    assert!(store.get_utxo(tx.hash, 1).unwrap().is_some());
+    assert!(store.get_utxo(tx.hash, 2).unwrap().is_none());
+    assert_eq!(store.get_utxo(more.hash, 0).unwrap().unwrap().block_height, 2);
+    assert!(store.get_utxo(spend.hash, 0).unwrap().is_some());
+
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_flush_write_back() {
+    let test_db_path = PathBuf::from("test_cache_flush.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::with_config(store, bounded(100, WritePolicy::WriteBack));
+
+    let tx = utxo::create_transaction(vec![utxo::create_output(1_000), utxo::create_output(2_000)]);
+    cache.add_transaction(&tx, None).unwrap();
+    assert_eq!(cache.stats().unwrap().write_backs, 0);
+
+    cache.flush().unwrap();
+    assert_eq!(cache.stats().unwrap().write_backs, 2);
+
+    // Entries stay cached and clean after a flush
+    cache.flush().unwrap();
+    let stats = cache.stats().unwrap();
+    assert_eq!(stats.write_backs, 2);
+    assert_eq!(stats.entries, 2);
+    assert!(cache.get_utxo(tx.hash, 1).unwrap().is_some());
+    assert_eq!(cache.stats().unwrap().hits, 1);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
@@ -1,7 +1,7 @@
 mod common;
 
 use std::path::PathBuf;
-use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::cache::{CacheError, UtxoCache};
 use state_channel_node::utxo::store::SdbStore;
 use common::test_utils;
 use common::utxo;
@@ -133,3 +133,214 @@ fn test_complex_transaction_chain() {
     // Cleanup
     test_utils::cleanup_test_db(&test_db_path);
 }
+
+#[test]
+fn test_get_utxo_after_restart() {
+    let test_db_path = PathBuf::from("test_restart.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    
+    let confirmed_tx = utxo::create_transaction(vec![
+        utxo::create_output(2_500_000_000), // 25 BTC
+    ]);
+    let unconfirmed_tx = utxo::create_transaction(vec![
+        utxo::create_output(1_000_000_000), // 10 BTC
+        utxo::create_output(500_000_000),   // 5 BTC
+    ]);
+    
+    {
+        let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+        let cache = UtxoCache::new(store);
+        cache.add_transaction(&confirmed_tx, Some(7))
+            .expect("Failed to add confirmed transaction");
+        cache.add_transaction(&unconfirmed_tx, None)
+            .expect("Failed to add unconfirmed transaction");
+    }
+    
+    // Simulate a node restart: fresh cache over the same database
+    let store = test_utils::reopen_store(&test_db_path);
+    let cache = UtxoCache::new(store);
+    
+    let utxo = cache.get_utxo(confirmed_tx.hash, 0)
+        .expect("Lookup failed")
+        .expect("Confirmed UTXO should be loaded from storage");
+    assert_eq!(utxo.output.value, 2_500_000_000);
+    assert_eq!(utxo.block_height, 7);
+    assert_eq!(utxo.tx_hash, confirmed_tx.hash);
+    assert!(utxo.is_confirmed);
+    
+    let utxo = cache.get_utxo(unconfirmed_tx.hash, 1)
+        .expect("Lookup failed")
+        .expect("Unconfirmed UTXO should be loaded from storage");
+    assert_eq!(utxo.output.value, 500_000_000);
+    assert_eq!(utxo.output_index, 1);
+    assert!(!utxo.is_confirmed);
+    
+    // Unknown outpoints are still reported as missing
+    assert!(cache.get_utxo(unconfirmed_tx.hash, 2).expect("Lookup failed").is_none());
+    
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_confirmation_persists_across_restart() {
+    let test_db_path = PathBuf::from("test_confirm.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    
+    let tx = utxo::create_transaction(vec![
+        utxo::create_output(1_000_000_000), // 10 BTC
+        utxo::create_output(2_000_000_000), // 20 BTC
+        utxo::create_output(3_000_000_000), // 30 BTC
+    ]);
+    let spend = utxo::create_spending_transaction(
+        &tx,
+        &[1],
+        vec![utxo::create_output(1_900_000_000)],
+    );
+    
+    {
+        let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+        let cache = UtxoCache::new(store);
+        cache.add_transaction(&tx, None)
+            .expect("Failed to add unconfirmed transaction");
+        cache.remove_spent(&spend)
+            .expect("Failed to remove spent outputs");
+        
+        cache.confirm_transaction(tx.hash, 42)
+            .expect("Failed to confirm transaction");
+        let utxo = cache.get_utxo(tx.hash, 0).unwrap().unwrap();
+        assert!(utxo.is_confirmed);
+        assert_eq!(utxo.block_height, 42);
+    }
+    
+    let store = test_utils::reopen_store(&test_db_path);
+    
+    // The transaction index only tracks outputs that are still unspent
+    assert_eq!(store.unspent_outputs_of(tx.hash).unwrap(), vec![0, 2]);
+    
+    for index in [0, 2] {
+        let utxo = store.get_utxo(tx.hash, index).unwrap()
+            .expect("Confirmed UTXO should be persisted");
+        assert!(utxo.is_confirmed);
+        assert_eq!(utxo.block_height, 42);
+    }
+    assert!(store.get_utxo(tx.hash, 1).unwrap().is_none());
+    
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_apply_transaction_atomic() {
+    let test_db_path = PathBuf::from("test_apply_tx.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+    
+    let initial_tx = utxo::create_transaction(vec![
+        utxo::create_output(3_000_000_000), // 30 BTC
+        utxo::create_output(2_000_000_000), // 20 BTC
+    ]);
+    cache.add_transaction(&initial_tx, Some(1))
+        .expect("Failed to add initial transaction");
+    
+    // Spend both outputs and create a new one in one step
+    let spending_tx = utxo::create_spending_transaction(
+        &initial_tx,
+        &[0, 1],
+        vec![utxo::create_output(4_900_000_000)],
+    );
+    let spent = cache.apply_transaction(&spending_tx, Some(2))
+        .expect("Failed to apply spending transaction");
+    
+    assert_eq!(spent.len(), 2);
+    assert_eq!(spent[0].output.value, 3_000_000_000);
+    assert_eq!(spent[1].output.value, 2_000_000_000);
+    assert!(cache.get_utxo(initial_tx.hash, 0).unwrap().is_none());
+    assert!(cache.get_utxo(initial_tx.hash, 1).unwrap().is_none());
+    let created = cache.get_utxo(spending_tx.hash, 0).unwrap().unwrap();
+    assert_eq!(created.block_height, 2);
+    
+    // Spending an already spent output fails without creating outputs
+    let double_spend_tx = utxo::create_spending_transaction(
+        &initial_tx,
+        &[0],
+        vec![utxo::create_output(2_900_000_000)],
+    );
+    assert!(matches!(
+        cache.apply_transaction(&double_spend_tx, Some(3)),
+        Err(CacheError::MissingInput(hash, 0)) if hash == initial_tx.hash
+    ));
+    assert!(cache.get_utxo(double_spend_tx.hash, 0).unwrap().is_none());
+    
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_apply_transaction_partial_failure_rolls_back() {
+    let test_db_path = PathBuf::from("test_apply_rollback.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+    
+    let initial_tx = utxo::create_transaction(vec![
+        utxo::create_output(1_000_000_000), // 10 BTC
+    ]);
+    cache.add_transaction(&initial_tx, Some(1))
+        .expect("Failed to add initial transaction");
+    
+    // Second input references an output that doesn't exist
+    let spending_tx = utxo::create_spending_transaction(
+        &initial_tx,
+        &[0, 5],
+        vec![utxo::create_output(900_000_000)],
+    );
+    assert!(matches!(
+        cache.apply_transaction(&spending_tx, Some(2)),
+        Err(CacheError::MissingInput(_, 5))
+    ));
+    
+    // The valid input must not have been spent
+    drop(cache);
+    let store = test_utils::reopen_store(&test_db_path);
+    assert!(store.get_utxo(initial_tx.hash, 0).unwrap().is_some());
+    assert!(store.get_utxo(spending_tx.hash, 0).unwrap().is_none());
+    
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_apply_transaction_duplicate_input() {
+    let test_db_path = PathBuf::from("test_apply_duplicate.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+    
+    let initial_tx = utxo::create_transaction(vec![
+        utxo::create_output(1_000_000_000), // 10 BTC
+    ]);
+    cache.add_transaction(&initial_tx, Some(1))
+        .expect("Failed to add initial transaction");
+    
+    // Same outpoint referenced twice
+    let spending_tx = utxo::create_spending_transaction(
+        &initial_tx,
+        &[0, 0],
+        vec![utxo::create_output(1_900_000_000)],
+    );
+    assert!(matches!(
+        cache.apply_transaction(&spending_tx, Some(2)),
+        Err(CacheError::DuplicateInput(_, 0))
+    ));
+    assert!(cache.get_utxo(initial_tx.hash, 0).unwrap().is_some());
+    
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
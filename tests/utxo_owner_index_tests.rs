@@ -0,0 +1,110 @@
+mod common;
+
+use std::path::PathBuf;
+use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::store::SdbStore;
+use common::test_utils;
+use common::utxo;
+
+const ALICE: [u8; 20] = [0xaa; 20];
+const BOB: [u8; 20] = [0xbb; 20];
+
+#[test]
+fn test_owner_index_tracks_spends() {
+    let test_db_path = PathBuf::from("test_owner_index.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let mut store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+
+    let funding_tx = utxo::create_transaction(vec![
+        utxo::create_output_for(3_000, &ALICE),
+        utxo::create_output_for(2_000, &BOB),
+        utxo::create_output_for(1_000, &ALICE),
+    ]);
+    store.add_outputs(&funding_tx, Some(1)).unwrap();
+
+    let alice_utxos = store.utxos_for_owner(&ALICE).unwrap();
+    assert_eq!(alice_utxos.iter().map(|u| u.output_index).collect::<Vec<_>>(), vec![0, 2]);
+    assert_eq!(store.balance_of(&ALICE).unwrap(), 4_000);
+    assert_eq!(store.balance_of(&BOB).unwrap(), 2_000);
+
+    // A hash sharing Alice's leading bytes must not match her outputs
+    let mut longer = ALICE.to_vec();
+    longer.push(0);
+    assert!(store.utxos_for_owner(&longer).unwrap().is_empty());
+    assert!(store.utxos_for_owner(&ALICE[..19]).unwrap().is_empty());
+
+    // Alice pays Bob out of her first output
+    let payment_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output_for(2_500, &BOB), utxo::create_output_for(400, &ALICE)],
+    );
+    store.apply_transaction(&payment_tx, None).unwrap();
+    assert_eq!(store.balance_of(&ALICE).unwrap(), 1_400);
+    assert_eq!(store.balance_of(&BOB).unwrap(), 4_500);
+
+    store.remove_inputs(&utxo::create_spending_transaction(&funding_tx, &[1], vec![]).inputs).unwrap();
+    let bob_utxos = store.utxos_for_owner(&BOB).unwrap();
+    assert_eq!(bob_utxos.len(), 1);
+    assert_eq!(bob_utxos[0].tx_hash, payment_tx.hash);
+    assert!(!bob_utxos[0].is_confirmed);
+
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_owner_index_follows_rollback() {
+    let test_db_path = PathBuf::from("test_owner_index_reorg.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+
+    let funding_tx = utxo::create_transaction(vec![utxo::create_output_for(10_000, &ALICE)]);
+    cache.connect_block(1, std::slice::from_ref(&funding_tx)).unwrap();
+
+    let payment_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output_for(9_000, &BOB)],
+    );
+    cache.connect_block(2, std::slice::from_ref(&payment_tx)).unwrap();
+    assert_eq!(cache.balance_of(&ALICE).unwrap(), 0);
+    assert_eq!(cache.balance_of(&BOB).unwrap(), 9_000);
+
+    // Rolling back block 2 returns the output to Alice
+    cache.disconnect_block(2).unwrap();
+    assert_eq!(cache.balance_of(&BOB).unwrap(), 0);
+    let alice_utxos = cache.utxos_for_owner(&ALICE).unwrap();
+    assert_eq!(alice_utxos.len(), 1);
+    assert_eq!(alice_utxos[0].block_height, 1);
+    assert!(alice_utxos[0].is_confirmed);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_owner_index_persists_across_restart() {
+    let test_db_path = PathBuf::from("test_owner_index_restart.db");
+    test_utils::cleanup_test_db(&test_db_path);
+
+    let funding_tx = utxo::create_transaction(vec![
+        utxo::create_output_for(7_000, &ALICE),
+        utxo::create_output_for(5_000, &BOB),
+    ]);
+    {
+        let mut store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+        store.add_outputs(&funding_tx, Some(1)).unwrap();
+    }
+
+    let store = test_utils::reopen_store(&test_db_path);
+    assert_eq!(store.balance_of(&ALICE).unwrap(), 7_000);
+    assert_eq!(store.utxos_for_owner(&BOB).unwrap()[0].output_index, 1);
+
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&test_db_path);
+}
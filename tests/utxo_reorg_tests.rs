@@ -0,0 +1,230 @@
+mod common;
+
+use std::path::PathBuf;
+use state_channel_node::utxo::cache::{CacheError, UtxoCache};
+use state_channel_node::utxo::store::{SdbStore, StoreError};
+use common::test_utils;
+use common::utxo;
+
+#[test]
+fn test_connect_and_disconnect_block() {
+    let test_db_path = PathBuf::from("test_reorg.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+
+    // Block 1: coinbase-style funding transaction
+    let funding_tx = utxo::create_transaction(vec![
+        utxo::create_output(5_000_000_000), // 50 BTC
+        utxo::create_output(1_000_000_000), // 10 BTC
+    ]);
+    cache.connect_block(1, std::slice::from_ref(&funding_tx))
+        .expect("Failed to connect block 1");
+
+    // Block 2: spends the first funding output
+    let spending_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output(4_900_000_000)],
+    );
+    cache.connect_block(2, std::slice::from_ref(&spending_tx))
+        .expect("Failed to connect block 2");
+
+    assert!(cache.get_utxo(funding_tx.hash, 0).unwrap().is_none());
+    let created = cache.get_utxo(spending_tx.hash, 0).unwrap().unwrap();
+    assert_eq!(created.block_height, 2);
+
+    // Roll back block 2: spent output is restored exactly, new output removed
+    cache.disconnect_block(2).expect("Failed to disconnect block 2");
+
+    let restored = cache.get_utxo(funding_tx.hash, 0).unwrap()
+        .expect("Spent output should be restored");
+    assert_eq!(restored.block_height, 1);
+    assert!(restored.is_confirmed);
+    assert_eq!(restored.output.value, 5_000_000_000);
+    assert!(cache.get_utxo(spending_tx.hash, 0).unwrap().is_none());
+
+    // A competing block 2 can now be connected
+    let competing_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0, 1],
+        vec![utxo::create_output(5_900_000_000)],
+    );
+    cache.connect_block(2, std::slice::from_ref(&competing_tx))
+        .expect("Failed to connect competing block 2");
+    assert!(cache.get_utxo(funding_tx.hash, 1).unwrap().is_none());
+    assert_eq!(cache.get_utxo(competing_tx.hash, 0).unwrap().unwrap().block_height, 2);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_disconnect_reverts_confirmation() {
+    let test_db_path = PathBuf::from("test_reorg_unconfirm.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+
+    let funding_tx = utxo::create_transaction(vec![
+        utxo::create_output(2_000_000_000), // 20 BTC
+    ]);
+    cache.connect_block(10, std::slice::from_ref(&funding_tx))
+        .expect("Failed to connect block 10");
+
+    // Transaction first seen unconfirmed, then mined in block 11
+    let mempool_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output(1_000_000_000), utxo::create_output(900_000_000)],
+    );
+    cache.apply_transaction(&mempool_tx, None)
+        .expect("Failed to apply unconfirmed transaction");
+    cache.connect_block(11, std::slice::from_ref(&mempool_tx))
+        .expect("Failed to connect block 11");
+
+    let confirmed = cache.get_utxo(mempool_tx.hash, 1).unwrap().unwrap();
+    assert!(confirmed.is_confirmed);
+    assert_eq!(confirmed.block_height, 11);
+
+    cache.disconnect_block(11).expect("Failed to disconnect block 11");
+
+    // Outputs stay in the set but are unconfirmed again; the input stays spent
+    for index in 0..2 {
+        let utxo = cache.get_utxo(mempool_tx.hash, index).unwrap()
+            .expect("Output should revert to unconfirmed");
+        assert!(!utxo.is_confirmed);
+        assert_eq!(utxo.block_height, 0);
+    }
+    assert!(cache.get_utxo(funding_tx.hash, 0).unwrap().is_none());
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_block_height_ordering() {
+    let test_db_path = PathBuf::from("test_reorg_heights.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let mut store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+
+    let tx1 = utxo::create_transaction(vec![utxo::create_output(1_000)]);
+    let tx2 = utxo::create_transaction(vec![utxo::create_output(2_000)]);
+
+    store.connect_block(100, &[tx1]).expect("Failed to connect block 100");
+    assert_eq!(store.chain_tip().unwrap(), Some(100));
+    assert!(store.block_undo(100).unwrap().is_some());
+
+    // Blocks must extend the tip
+    assert!(matches!(
+        store.connect_block(102, std::slice::from_ref(&tx2)),
+        Err(StoreError::InvalidBlockHeight { expected: 101, got: 102 })
+    ));
+
+    // Only the tip can be disconnected
+    store.connect_block(101, &[tx2]).expect("Failed to connect block 101");
+    assert!(matches!(
+        store.disconnect_block(100),
+        Err(StoreError::NotChainTip { height: 100, tip: Some(101) })
+    ));
+
+    store.disconnect_block(101).expect("Failed to disconnect block 101");
+    store.disconnect_block(100).expect("Failed to disconnect block 100");
+    assert_eq!(store.chain_tip().unwrap(), None);
+    assert!(store.is_empty().unwrap());
+
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_connect_block_with_missing_input_is_atomic() {
+    let test_db_path = PathBuf::from("test_reorg_atomic.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+
+    let funding_tx = utxo::create_transaction(vec![utxo::create_output(1_000_000)]);
+    cache.connect_block(1, std::slice::from_ref(&funding_tx))
+        .expect("Failed to connect block 1");
+
+    let valid_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output(900_000)],
+    );
+    let invalid_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[3],
+        vec![utxo::create_output(900_000)],
+    );
+    assert!(matches!(
+        cache.connect_block(2, &[valid_tx.clone(), invalid_tx]),
+        Err(CacheError::MissingInput(_, 3))
+    ));
+
+    // Nothing from the rejected block was applied
+    assert!(cache.get_utxo(funding_tx.hash, 0).unwrap().is_some());
+    assert!(cache.get_utxo(valid_tx.hash, 0).unwrap().is_none());
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_connect_unconfirmed_transaction_with_spent_outputs() {
+    let test_db_path = PathBuf::from("test_reorg_spent_pending.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+
+    let funding_tx = utxo::create_transaction(vec![utxo::create_output(3_000_000)]);
+    cache.connect_block(1, std::slice::from_ref(&funding_tx))
+        .expect("Failed to connect block 1");
+
+    // A parent and a child both seen unconfirmed; the child spends every
+    // output of the parent before either is mined
+    let parent_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output(1_000_000), utxo::create_output(1_900_000)],
+    );
+    cache.apply_transaction(&parent_tx, None)
+        .expect("Failed to apply unconfirmed parent");
+    let child_tx = utxo::create_spending_transaction(
+        &parent_tx,
+        &[0, 1],
+        vec![utxo::create_output(2_800_000)],
+    );
+    cache.apply_transaction(&child_tx, None)
+        .expect("Failed to apply unconfirmed child");
+    assert!(cache.get_utxo(parent_tx.hash, 0).unwrap().is_none());
+
+    // Neither transaction's inputs are spent a second time
+    cache.connect_block(2, &[parent_tx.clone(), child_tx.clone()])
+        .expect("Failed to connect block 2");
+    let confirmed = cache.get_utxo(child_tx.hash, 0).unwrap().unwrap();
+    assert!(confirmed.is_confirmed);
+    assert_eq!(confirmed.block_height, 2);
+    assert!(cache.get_utxo(parent_tx.hash, 0).unwrap().is_none());
+
+    // Rolling back restores the unconfirmed state, and the block can be
+    // connected again
+    cache.disconnect_block(2).expect("Failed to disconnect block 2");
+    let pending = cache.get_utxo(child_tx.hash, 0).unwrap().unwrap();
+    assert!(!pending.is_confirmed);
+    assert!(cache.get_utxo(parent_tx.hash, 1).unwrap().is_none());
+    assert!(cache.get_utxo(funding_tx.hash, 0).unwrap().is_none());
+
+    cache.connect_block(2, &[parent_tx, child_tx.clone()])
+        .expect("Failed to reconnect block 2");
+    assert!(cache.get_utxo(child_tx.hash, 0).unwrap().unwrap().is_confirmed);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
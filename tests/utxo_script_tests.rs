@@ -0,0 +1,255 @@
+mod common;
+
+use std::path::PathBuf;
+use primitive_types::H256;
+use sha2::{Digest, Sha256};
+use state_channel_node::crypto::{self, KeyPair};
+use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::models::{Input, Output, SighashType, Transaction};
+use state_channel_node::utxo::script::{self, opcodes::*, Interpreter, Script, ScriptError};
+use state_channel_node::utxo::store::SdbStore;
+use state_channel_node::utxo::timelock::RelativeLock;
+use state_channel_node::utxo::validation::{self, ValidationError};
+use common::test_utils;
+use common::utxo;
+
+/// A version 2 transaction spending one (unspecified) output
+fn spending(sequence: u32, lock_time: u64) -> Transaction {
+    let mut tx = Transaction {
+        version: 2,
+        inputs: vec![Input {
+            previous_output: H256::repeat_byte(9),
+            index: 0,
+            signature: vec![],
+            sequence,
+        }],
+        outputs: vec![utxo::create_output(1_000)],
+        lock_time,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+    tx
+}
+
+fn sign(tx: &Transaction, keypair: &KeyPair) -> Vec<u8> {
+    script::sign(tx, 0, keypair, SighashType::All).unwrap()
+}
+
+/// Claimable by `claimer` with the preimage, or by `refunder` after 144 blocks
+fn htlc(hash: &[u8; 32], claimer: &KeyPair, refunder: &KeyPair) -> Script {
+    Script::new()
+        .push_opcode(OP_IF)
+        .push_opcode(OP_SHA256)
+        .push_data(hash)
+        .push_opcode(OP_EQUALVERIFY)
+        .push_data(&claimer.public_key().as_bytes())
+        .push_opcode(OP_ELSE)
+        .push_int(144)
+        .push_opcode(OP_CHECKSEQUENCEVERIFY)
+        .push_opcode(OP_DROP)
+        .push_data(&refunder.public_key().as_bytes())
+        .push_opcode(OP_ENDIF)
+        .push_opcode(OP_CHECKSIG)
+}
+
+#[test]
+fn test_pay_to_public_key_hash() {
+    let alice = crypto::generate_keypair();
+    let mallory = crypto::generate_keypair();
+    let lock = Script::p2pkh(&alice.public_key().public_key_hash());
+    let tx = spending(0xffffffff, 0);
+
+    let unlock = Script::new().push_data(&sign(&tx, &alice)).push_data(&alice.public_key().as_bytes());
+    assert!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0).is_ok());
+
+    // Right key hash is required before the signature is even looked at
+    let unlock = Script::new().push_data(&sign(&tx, &mallory)).push_data(&mallory.public_key().as_bytes());
+    assert_eq!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0), Err(ScriptError::VerifyFailed));
+
+    // A signature that doesn't verify is an error, not just a false result
+    let mut signature = sign(&tx, &alice);
+    signature[0] ^= 0x01;
+    let unlock = Script::new().push_data(&signature).push_data(&alice.public_key().as_bytes());
+    assert_eq!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0), Err(ScriptError::NullFail));
+
+    // Unlock scripts may only push data
+    let unlock = Script::new().push_data(&sign(&tx, &alice)).push_opcode(OP_DUP);
+    assert_eq!(
+        script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0),
+        Err(ScriptError::UnlockNotPushOnly)
+    );
+}
+
+#[test]
+fn test_multisig() {
+    let keys: Vec<KeyPair> = (0..3).map(|_| crypto::generate_keypair()).collect();
+    let public_keys: Vec<_> = keys.iter().map(|key| key.public_key()).collect();
+    let lock = Script::multisig(2, &public_keys);
+    let tx = spending(0xffffffff, 0);
+
+    // Any two signatures, in key order
+    for (first, second) in [(0, 1), (0, 2), (1, 2)] {
+        let unlock = Script::new().push_data(&sign(&tx, &keys[first])).push_data(&sign(&tx, &keys[second]));
+        assert!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0).is_ok());
+    }
+
+    // Out of order signatures don't match
+    let unlock = Script::new().push_data(&sign(&tx, &keys[2])).push_data(&sign(&tx, &keys[0]));
+    assert_eq!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0), Err(ScriptError::NullFail));
+
+    // 2-of-2 with only empty signatures is simply false
+    let lock = Script::multisig(2, &public_keys[..2]);
+    let unlock = Script::new().push_data(&[]).push_data(&[]);
+    assert_eq!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0), Err(ScriptError::EvalFalse));
+
+    // Too few elements for the declared signature count
+    let unlock = Script::new().push_data(&sign(&tx, &keys[0]));
+    assert_eq!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0), Err(ScriptError::StackUnderflow));
+}
+
+#[test]
+fn test_hashlock_and_timelock_branches() {
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let preimage = b"channel secret";
+    let hash: [u8; 32] = Sha256::digest(preimage).into();
+
+    let hashlock = Script::hashlock(&hash);
+    let tx = spending(0xffffffff, 0);
+    assert!(script::verify_script(Script::new().push_data(preimage).as_bytes(), hashlock.as_bytes(), &tx, 0).is_ok());
+    assert_eq!(
+        script::verify_script(Script::new().push_data(b"guess").as_bytes(), hashlock.as_bytes(), &tx, 0),
+        Err(ScriptError::EvalFalse)
+    );
+
+    let lock = htlc(&hash, &bob, &alice);
+
+    // Claim branch: signature, preimage, then 0x01 to take the IF
+    let tx = spending(0xffffffff, 0);
+    let unlock = Script::new().push_data(&sign(&tx, &bob)).push_data(preimage).push_data(&[1]);
+    assert!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0).is_ok());
+
+    // Refund branch requires the input's relative lock to be at least 144 blocks
+    let tx = spending(RelativeLock::Blocks(144).to_sequence(), 0);
+    let unlock = Script::new().push_data(&sign(&tx, &alice)).push_data(&[]);
+    assert!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0).is_ok());
+
+    let tx = spending(RelativeLock::Blocks(143).to_sequence(), 0);
+    let unlock = Script::new().push_data(&sign(&tx, &alice)).push_data(&[]);
+    assert_eq!(
+        script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0),
+        Err(ScriptError::SequenceNotSatisfied)
+    );
+
+    // Five-byte operands aren't truncated to a sequence number
+    let wide = Script::new()
+        .push_int((1 << 32) + 144)
+        .push_opcode(OP_CHECKSEQUENCEVERIFY)
+        .push_opcode(OP_DROP)
+        .push_data(&alice.public_key().as_bytes())
+        .push_opcode(OP_CHECKSIG);
+    let tx = spending(RelativeLock::Blocks(144).to_sequence(), 0);
+    let unlock = Script::new().push_data(&sign(&tx, &alice));
+    assert_eq!(
+        script::verify_script(unlock.as_bytes(), wide.as_bytes(), &tx, 0),
+        Err(ScriptError::SequenceNotSatisfied)
+    );
+
+    // Branch selectors other than empty or 0x01 are malleable and rejected
+    let unlock = Script::new().push_data(&sign(&tx, &bob)).push_data(preimage).push_data(&[2]);
+    assert_eq!(
+        script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0),
+        Err(ScriptError::NonMinimalCondition)
+    );
+
+    // Absolute lock: <height> CHECKLOCKTIMEVERIFY DROP
+    let lock = Script::new()
+        .push_int(600_000)
+        .push_opcode(OP_CHECKLOCKTIMEVERIFY)
+        .push_opcode(OP_DROP)
+        .push_data(&alice.public_key().as_bytes())
+        .push_opcode(OP_CHECKSIG);
+    let tx = spending(0, 600_000);
+    let unlock = Script::new().push_data(&sign(&tx, &alice));
+    assert!(script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0).is_ok());
+
+    let tx = spending(0, 599_999);
+    let unlock = Script::new().push_data(&sign(&tx, &alice));
+    assert_eq!(
+        script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0),
+        Err(ScriptError::LockTimeNotSatisfied)
+    );
+}
+
+#[test]
+fn test_interpreter_limits() {
+    let tx = spending(0xffffffff, 0);
+    let verify = |unlock: Script, lock: Script| script::verify_script(unlock.as_bytes(), lock.as_bytes(), &tx, 0);
+
+    assert_eq!(
+        verify(Script::new(), Script::new().push_int(1).push_opcode(OP_IF)),
+        Err(ScriptError::UnbalancedConditional)
+    );
+    assert_eq!(verify(Script::new(), Script::new().push_opcode(OP_ENDIF)), Err(ScriptError::UnbalancedConditional));
+    assert_eq!(verify(Script::new(), Script::new().push_opcode(0xba)), Err(ScriptError::UnknownOpcode(0xba)));
+    assert_eq!(verify(Script::new(), Script::new().push_opcode(OP_RETURN)), Err(ScriptError::OpReturn));
+    assert_eq!(verify(Script::new(), Script::from_bytes(vec![5, 1, 2])), Err(ScriptError::TruncatedPush));
+    assert_eq!(verify(Script::new().push_int(1), Script::new().push_int(1)), Err(ScriptError::CleanStack));
+    assert_eq!(
+        verify(Script::new(), Script::new().push_data(&[0u8; 521])),
+        Err(ScriptError::PushTooLarge(521))
+    );
+
+    // Unknown opcodes are rejected even in branches that don't execute
+    let lock = Script::new().push_int(0).push_opcode(OP_IF).push_opcode(0xba).push_opcode(OP_ENDIF).push_int(1);
+    assert_eq!(verify(Script::new(), lock), Err(ScriptError::UnknownOpcode(0xba)));
+
+    // Steps count every instruction, across both scripts
+    let lock = (0..10).fold(Script::new().push_int(1), |script, _| script.push_opcode(OP_NOP));
+    let interpreter = Interpreter::new(&tx, 0).with_max_steps(11);
+    assert!(interpreter.verify(&[], lock.as_bytes()).is_ok());
+    assert_eq!(
+        interpreter.verify(Script::new().push_int(1).as_bytes(), lock.as_bytes()),
+        Err(ScriptError::StepLimitExceeded)
+    );
+}
+
+#[test]
+fn test_validation_runs_lock_scripts() {
+    let test_db_path = PathBuf::from("test_script_validation.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+
+    let funding_tx = utxo::create_transaction(vec![
+        Output {
+            value: 5_000,
+            public_key_hash: alice.public_key().public_key_hash().to_vec(),
+            lock_script: Script::multisig(2, &[alice.public_key(), bob.public_key()]).into_bytes(),
+        },
+        utxo::create_output_for(3_000, &alice.public_key().public_key_hash()),
+    ]);
+    cache.add_transaction(&funding_tx, Some(1)).unwrap();
+
+    let mut tx = utxo::create_spending_transaction(&funding_tx, &[0, 1], vec![utxo::create_output(7_900)]);
+    let multisig_unlock = Script::new()
+        .push_data(&script::sign(&tx, 0, &alice, SighashType::All).unwrap())
+        .push_data(&script::sign(&tx, 0, &bob, SighashType::All).unwrap());
+    tx.inputs[0].signature = multisig_unlock.into_bytes();
+    // Outputs without a lock script keep using the plain P2PKH witness
+    validation::sign_input(&mut tx, 1, &alice, SighashType::All).unwrap();
+    assert_eq!(validation::validate_transaction(&tx, &cache).unwrap(), 100);
+
+    // Missing the second signature
+    tx.inputs[0].signature = Script::new()
+        .push_data(&script::sign(&tx, 0, &alice, SighashType::All).unwrap())
+        .into_bytes();
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::Script { input: 0, source: ScriptError::StackUnderflow }));
+    assert_eq!(err.code(), "INVALID_SCRIPT");
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
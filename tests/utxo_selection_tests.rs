@@ -0,0 +1,157 @@
+mod common;
+
+use std::path::PathBuf;
+use primitive_types::H256;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::models::Utxo;
+use state_channel_node::utxo::selection::{
+    self, SelectionError, SelectionParams, SelectionStrategy,
+};
+use state_channel_node::utxo::store::SdbStore;
+use common::test_utils;
+use common::utxo;
+
+const ALICE: [u8; 20] = [0xaa; 20];
+const BOB: [u8; 20] = [0xbb; 20];
+
+/// Confirmed candidate with a unique outpoint
+fn candidate(index: u32, value: u64) -> Utxo {
+    Utxo {
+        output: utxo::create_output_for(value, &ALICE),
+        block_height: 1,
+        output_index: index,
+        tx_hash: H256::repeat_byte(0x11),
+        is_confirmed: true,
+    }
+}
+
+fn values(utxos: &[Utxo]) -> Vec<u64> {
+    utxos.iter().map(|utxo| utxo.output.value).collect()
+}
+
+#[test]
+fn test_largest_first() {
+    let candidates = vec![candidate(0, 1_000), candidate(1, 50_000), candidate(2, 20_000)];
+    let params = SelectionParams::new(30_000, 1);
+
+    let selection = selection::largest_first(&candidates, &params).unwrap();
+    assert_eq!(values(&selection.inputs), vec![50_000]);
+    assert_eq!(selection.fee, params.fee_for(1, 2));
+    assert_eq!(selection.change, 50_000 - 30_000 - selection.fee);
+
+    // Change below the dust threshold is given up as fee instead
+    let candidates = vec![candidate(0, 30_500)];
+    let selection = selection::largest_first(&candidates, &params).unwrap();
+    assert_eq!(selection.change, 0);
+    assert_eq!(selection.fee, 500);
+
+    assert!(matches!(
+        selection::largest_first(&candidates, &SelectionParams::new(40_000, 1)),
+        Err(SelectionError::InsufficientFunds { available: 30_500, .. })
+    ));
+    assert!(matches!(
+        selection::largest_first(&candidates, &SelectionParams::new(0, 1)),
+        Err(SelectionError::ZeroTarget)
+    ));
+}
+
+#[test]
+fn test_branch_and_bound_avoids_change() {
+    let candidates = vec![
+        candidate(0, 5_000),
+        candidate(1, 3_000),
+        candidate(2, 2_000),
+        candidate(3, 1_500),
+        candidate(4, 700),
+    ];
+
+    // Without fees an exact match is required
+    let selection = selection::branch_and_bound(&candidates, &SelectionParams::new(4_500, 0)).unwrap();
+    assert_eq!(selection.total_input(), 4_500);
+    assert_eq!(selection.change, 0);
+    assert_eq!(selection.fee, 0);
+
+    // With fees, excess smaller than the cost of change is acceptable
+    let params = SelectionParams::new(4_500, 1);
+    let selection = selection::branch_and_bound(&candidates, &params).unwrap();
+    assert_eq!(values(&selection.inputs), vec![3_000, 2_000]);
+    assert_eq!(selection.change, 0);
+    assert_eq!(selection.fee, 500);
+    assert!(selection.fee >= params.fee_for(2, 1));
+
+    // A single large coin can't match without change
+    assert!(matches!(
+        selection::branch_and_bound(&[candidate(0, 10_000)], &SelectionParams::new(4_000, 0)),
+        Err(SelectionError::NoExactMatch)
+    ));
+}
+
+#[test]
+fn test_random_improve() {
+    let candidates: Vec<Utxo> = (0..20).map(|i| candidate(i, 1_000)).collect();
+    let params = SelectionParams::new(5_000, 1);
+
+    let selection = selection::random_improve(&candidates, &params, &mut StdRng::seed_from_u64(7)).unwrap();
+    assert_eq!(selection.total_input(), params.target + selection.change + selection.fee);
+    assert!(selection.total_input() <= 3 * params.target);
+    assert!(selection.change > 0);
+    // Improvement pulls the selection well past the bare minimum
+    assert!(selection.inputs.len() > 6);
+
+    // Deterministic for a given seed
+    let again = selection::random_improve(&candidates, &params, &mut StdRng::seed_from_u64(7)).unwrap();
+    assert_eq!(selection, again);
+
+    assert!(matches!(
+        selection::random_improve(&candidates, &SelectionParams::new(25_000, 1), &mut StdRng::seed_from_u64(7)),
+        Err(SelectionError::InsufficientFunds { available: 20_000, .. })
+    ));
+}
+
+#[test]
+fn test_select_coins_respects_confirmations() {
+    let test_db_path = PathBuf::from("test_selection.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let store = SdbStore::new(&test_db_path).expect("Failed to create test store");
+    let cache = UtxoCache::new(store);
+
+    let old_tx = utxo::create_transaction(vec![utxo::create_output_for(1_000, &ALICE)]);
+    let other_tx = utxo::create_transaction(vec![utxo::create_output_for(9_000, &BOB)]);
+    let recent_tx = utxo::create_transaction(vec![utxo::create_output_for(10_000, &ALICE)]);
+    let pending_tx = utxo::create_transaction(vec![utxo::create_output_for(100_000, &ALICE)]);
+    cache.connect_block(1, std::slice::from_ref(&old_tx)).unwrap();
+    cache.connect_block(2, std::slice::from_ref(&other_tx)).unwrap();
+    cache.connect_block(3, std::slice::from_ref(&recent_tx)).unwrap();
+    cache.add_transaction(&pending_tx, None).unwrap();
+
+    // Unconfirmed outputs are only used when explicitly allowed
+    let params = SelectionParams::new(50_000, 1).with_min_confirmations(0);
+    let selection = selection::select_coins(&cache, &ALICE, &params, SelectionStrategy::LargestFirst).unwrap();
+    assert_eq!(selection.inputs[0].tx_hash, pending_tx.hash);
+
+    let params = SelectionParams::new(50_000, 1);
+    assert!(matches!(
+        selection::select_coins(&cache, &ALICE, &params, SelectionStrategy::LargestFirst),
+        Err(SelectionError::InsufficientFunds { available: 11_000, .. })
+    ));
+
+    // Only the output from block 1 has three confirmations
+    let params = SelectionParams::new(500, 1).with_min_confirmations(3);
+    for strategy in [SelectionStrategy::LargestFirst, SelectionStrategy::RandomImprove] {
+        let selection = selection::select_coins(&cache, &ALICE, &params, strategy).unwrap();
+        assert_eq!(selection.inputs.len(), 1);
+        assert_eq!(selection.inputs[0].tx_hash, old_tx.hash);
+    }
+
+    // Bob's outputs are never selected for Alice
+    let params = SelectionParams::new(11_000, 0);
+    let selection = selection::select_coins(&cache, &ALICE, &params, SelectionStrategy::BranchAndBound).unwrap();
+    assert_eq!(selection.total_input(), 11_000);
+    assert!(selection.inputs.iter().all(|utxo| utxo.output.public_key_hash == ALICE));
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use std::path::PathBuf;
+use primitive_types::H256;
+use state_channel_node::crypto;
+use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::models::{Input, SighashType, Transaction};
+use state_channel_node::utxo::store::SdbStore;
+use state_channel_node::utxo::validation;
+use common::test_utils;
+use common::utxo;
+
+fn two_in_two_out() -> Transaction {
+    let mut tx = Transaction {
+        version: 1,
+        inputs: vec![
+            Input { previous_output: H256::repeat_byte(1), index: 0, signature: vec![], sequence: 0xffffffff },
+            Input { previous_output: H256::repeat_byte(2), index: 1, signature: vec![], sequence: 0xffffffff },
+        ],
+        outputs: vec![utxo::create_output(1_000), utxo::create_output(2_000)],
+        lock_time: 0,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+    tx
+}
+
+#[test]
+fn test_txid_ignores_signatures() {
+    let mut tx = two_in_two_out();
+    let txid = tx.txid();
+    let wtxid = tx.wtxid();
+    assert_eq!(tx.hash, txid);
+
+    tx.inputs[0].signature = vec![0xab; 97];
+    assert_eq!(tx.txid(), txid);
+    assert_eq!(tx.calculate_hash(), txid);
+    assert_ne!(tx.wtxid(), wtxid);
+
+    // Anything else still changes the txid
+    tx.inputs[1].sequence = 0;
+    assert_ne!(tx.txid(), txid);
+}
+
+#[test]
+fn test_sighash_coverage() {
+    let tx = two_in_two_out();
+    let sighash = |tx: &Transaction, index, ty| tx.sighash(index, ty).unwrap();
+
+    // Every type commits to something different
+    let types = [
+        SighashType::All,
+        SighashType::None,
+        SighashType::Single,
+        SighashType::AllAnyoneCanPay,
+        SighashType::NoneAnyoneCanPay,
+        SighashType::SingleAnyoneCanPay,
+    ];
+    for (i, a) in types.iter().enumerate() {
+        assert_eq!(SighashType::from_u8(a.to_u8()), Some(*a));
+        for b in &types[i + 1..] {
+            assert_ne!(sighash(&tx, 0, *a), sighash(&tx, 0, *b));
+        }
+    }
+    assert_ne!(sighash(&tx, 0, SighashType::All), sighash(&tx, 1, SighashType::All));
+
+    // Changing output 1 only affects types covering it
+    let mut changed = tx.clone();
+    changed.outputs[1].value += 1;
+    assert_ne!(sighash(&tx, 0, SighashType::All), sighash(&changed, 0, SighashType::All));
+    assert_eq!(sighash(&tx, 0, SighashType::None), sighash(&changed, 0, SighashType::None));
+    assert_eq!(sighash(&tx, 0, SighashType::Single), sighash(&changed, 0, SighashType::Single));
+    assert_ne!(sighash(&tx, 1, SighashType::Single), sighash(&changed, 1, SighashType::Single));
+
+    // Other inputs' sequences are only covered by All
+    let mut changed = tx.clone();
+    changed.inputs[1].sequence = 5;
+    assert_ne!(sighash(&tx, 0, SighashType::All), sighash(&changed, 0, SighashType::All));
+    assert_eq!(sighash(&tx, 0, SighashType::None), sighash(&changed, 0, SighashType::None));
+    assert_eq!(sighash(&tx, 0, SighashType::Single), sighash(&changed, 0, SighashType::Single));
+
+    // AnyoneCanPay ignores other inputs entirely
+    let mut changed = tx.clone();
+    changed.inputs.push(Input {
+        previous_output: H256::repeat_byte(3),
+        index: 0,
+        signature: vec![],
+        sequence: 0,
+    });
+    assert_ne!(sighash(&tx, 0, SighashType::All), sighash(&changed, 0, SighashType::All));
+    assert_eq!(
+        sighash(&tx, 0, SighashType::AllAnyoneCanPay),
+        sighash(&changed, 0, SighashType::AllAnyoneCanPay)
+    );
+
+    // Signatures are never covered
+    let mut signed = tx.clone();
+    signed.inputs[1].signature = vec![1; 97];
+    assert_eq!(sighash(&tx, 0, SighashType::All), sighash(&signed, 0, SighashType::All));
+
+    // Out of range
+    assert!(tx.sighash(2, SighashType::All).is_none());
+    let mut one_output = tx.clone();
+    one_output.outputs.truncate(1);
+    assert!(one_output.sighash(1, SighashType::Single).is_none());
+    assert!(one_output.sighash(1, SighashType::None).is_some());
+    assert_eq!(SighashType::from_u8(0x04), None);
+}
+
+#[test]
+fn test_presigned_child_survives_parent_resigning() {
+    let test_db_path = PathBuf::from("test_sighash_presigned.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+
+    let alice = crypto::generate_keypair();
+    let bob = crypto::generate_keypair();
+    let alice_hash = alice.public_key().public_key_hash();
+    let shared_hash = bob.public_key().public_key_hash();
+
+    let funding_tx = utxo::create_transaction(vec![utxo::create_output_for(10_000, &alice_hash)]);
+    cache.add_transaction(&funding_tx, Some(1)).unwrap();
+
+    // Parent spends Alice's coin into an output controlled by Bob
+    let mut parent = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output_for(9_900, &shared_hash)],
+    );
+    let parent_txid = parent.txid();
+
+    // The refund-style child is signed before the parent is
+    let mut child = utxo::create_spending_transaction(
+        &parent,
+        &[0],
+        vec![utxo::create_output_for(9_800, &alice_hash)],
+    );
+    validation::sign_input(&mut child, 0, &bob, SighashType::All).unwrap();
+
+    // Signing (or re-signing) the parent leaves its txid untouched
+    validation::sign_input(&mut parent, 0, &alice, SighashType::All).unwrap();
+    assert_eq!(parent.txid(), parent_txid);
+    validation::sign_input(&mut parent, 0, &alice, SighashType::AllAnyoneCanPay).unwrap();
+    assert_eq!(parent.txid(), parent_txid);
+    assert_eq!(parent.hash, parent_txid);
+
+    assert_eq!(validation::validate_transaction(&parent, &cache).unwrap(), 100);
+    cache.apply_transaction(&parent, Some(2)).unwrap();
+
+    // Outputs are keyed on the txid, so the pre-signed child is valid
+    assert!(cache.get_utxo(parent_txid, 0).unwrap().is_some());
+    assert_eq!(validation::validate_transaction(&child, &cache).unwrap(), 100);
+
+    // A Single signature leaves the other output free to change
+    let mut split = utxo::create_spending_transaction(
+        &child,
+        &[0],
+        vec![utxo::create_output_for(5_000, &shared_hash), utxo::create_output_for(4_700, &alice_hash)],
+    );
+    cache.apply_transaction(&child, Some(3)).unwrap();
+    validation::sign_input(&mut split, 0, &alice, SighashType::Single).unwrap();
+    split.outputs[1].value = 4_600;
+    split.hash = split.calculate_hash();
+    assert_eq!(validation::validate_transaction(&split, &cache).unwrap(), 200);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use primitive_types::H256;
+use state_channel_node::utxo::snapshot::UtxoSnapshot;
+use state_channel_node::utxo::store::{SdbStore, StoreError};
+use common::test_utils;
+use common::utxo;
+
+fn cleanup_file(path: &PathBuf) {
+    if path.exists() {
+        fs::remove_file(path).expect("Failed to cleanup snapshot file");
+    }
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    let source_path = PathBuf::from("test_snapshot_source.db");
+    let target_path = PathBuf::from("test_snapshot_target.db");
+    let snapshot_path = PathBuf::from("test_snapshot_round_trip.snap");
+    test_utils::cleanup_test_db(&source_path);
+    test_utils::cleanup_test_db(&target_path);
+    cleanup_file(&snapshot_path);
+
+    let mut source = SdbStore::new(&source_path).expect("Failed to create source store");
+    let funding_tx = utxo::create_transaction(vec![
+        utxo::create_output(5_000_000_000), // 50 BTC
+        utxo::create_output(1_000_000_000), // 10 BTC
+    ]);
+    let spending_tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output(2_000_000_000), utxo::create_output(2_900_000_000)],
+    );
+    source.connect_block(1, std::slice::from_ref(&funding_tx)).unwrap();
+    source.connect_block(2, std::slice::from_ref(&spending_tx)).unwrap();
+
+    let root = source.export_snapshot(&snapshot_path).expect("Failed to export snapshot");
+    assert_eq!(root, source.merkle_root().unwrap());
+    assert_ne!(root, H256::zero());
+
+    // Restore into a fresh database, verifying against the exported root
+    let mut target = SdbStore::new(&target_path).expect("Failed to create target store");
+    let stale_tx = utxo::create_transaction(vec![utxo::create_output(42)]);
+    target.add_outputs(&stale_tx, None).unwrap();
+
+    let snapshot = target.import_snapshot(&snapshot_path, Some(root))
+        .expect("Failed to import snapshot");
+    assert_eq!(snapshot.height, Some(2));
+    assert_eq!(snapshot.utxos.len(), 3);
+
+    // The live set now matches the source exactly
+    assert_eq!(target.merkle_root().unwrap(), root);
+    assert_eq!(target.all_utxos().unwrap(), source.all_utxos().unwrap());
+    assert!(target.get_utxo(stale_tx.hash, 0).unwrap().is_none());
+    assert!(target.unspent_outputs_of(stale_tx.hash).unwrap().is_empty());
+    assert_eq!(target.unspent_outputs_of(spending_tx.hash).unwrap(), vec![0, 1]);
+
+    // The snapshot height becomes the tip; history below it can't be undone
+    assert_eq!(target.chain_tip().unwrap(), Some(2));
+    assert!(matches!(target.disconnect_block(2), Err(StoreError::MissingUndoData(2))));
+    let next_tx = utxo::create_transaction(vec![utxo::create_output(7)]);
+    assert!(matches!(
+        target.connect_block(4, std::slice::from_ref(&next_tx)),
+        Err(StoreError::InvalidBlockHeight { expected: 3, got: 4 })
+    ));
+    target.connect_block(3, std::slice::from_ref(&next_tx)).unwrap();
+    target.disconnect_block(3).unwrap();
+
+    // Cleanup
+    drop(source);
+    drop(target);
+    test_utils::cleanup_test_db(&source_path);
+    test_utils::cleanup_test_db(&target_path);
+    cleanup_file(&snapshot_path);
+}
+
+#[test]
+fn test_snapshot_rejects_wrong_root() {
+    let db_path = PathBuf::from("test_snapshot_wrong_root.db");
+    let snapshot_path = PathBuf::from("test_snapshot_wrong_root.snap");
+    test_utils::cleanup_test_db(&db_path);
+    cleanup_file(&snapshot_path);
+
+    let mut store = SdbStore::new(&db_path).expect("Failed to create test store");
+    let tx = utxo::create_transaction(vec![utxo::create_output(1_000)]);
+    store.add_outputs(&tx, Some(1)).unwrap();
+    let root = store.export_snapshot(&snapshot_path).unwrap();
+
+    // Live set changes after the snapshot was taken
+    let later_tx = utxo::create_transaction(vec![utxo::create_output(2_000)]);
+    store.add_outputs(&later_tx, Some(2)).unwrap();
+    let live_root = store.merkle_root().unwrap();
+
+    let trusted_root = H256::repeat_byte(0xab);
+    assert!(matches!(
+        store.import_snapshot(&snapshot_path, Some(trusted_root)),
+        Err(StoreError::SnapshotRootMismatch { expected, actual }) if expected == trusted_root && actual == root
+    ));
+
+    // Nothing was replaced
+    assert_eq!(store.merkle_root().unwrap(), live_root);
+    assert!(store.get_utxo(later_tx.hash, 0).unwrap().is_some());
+
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&db_path);
+    cleanup_file(&snapshot_path);
+}
+
+#[test]
+fn test_snapshot_detects_corruption() {
+    let db_path = PathBuf::from("test_snapshot_corrupt.db");
+    let snapshot_path = PathBuf::from("test_snapshot_corrupt.snap");
+    test_utils::cleanup_test_db(&db_path);
+    cleanup_file(&snapshot_path);
+
+    let mut store = SdbStore::new(&db_path).expect("Failed to create test store");
+    let tx = utxo::create_transaction(vec![
+        utxo::create_output(1_000),
+        utxo::create_output(2_000),
+    ]);
+    store.add_outputs(&tx, Some(1)).unwrap();
+    store.export_snapshot(&snapshot_path).unwrap();
+
+    // Flip a byte in the body
+    let mut bytes = fs::read(&snapshot_path).unwrap();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0xff;
+    assert!(matches!(
+        UtxoSnapshot::decode(&bytes),
+        Err(StoreError::InvalidSnapshot(_))
+    ));
+
+    // Truncated file
+    let bytes = fs::read(&snapshot_path).unwrap();
+    assert!(matches!(
+        UtxoSnapshot::decode(&bytes[..bytes.len() - 1]),
+        Err(StoreError::InvalidSnapshot(_))
+    ));
+
+    // Unsupported version
+    let mut bytes = fs::read(&snapshot_path).unwrap();
+    bytes[8] = 99;
+    assert!(matches!(
+        UtxoSnapshot::decode(&bytes),
+        Err(StoreError::InvalidSnapshot(_))
+    ));
+
+    // Cleanup
+    drop(store);
+    test_utils::cleanup_test_db(&db_path);
+    cleanup_file(&snapshot_path);
+}
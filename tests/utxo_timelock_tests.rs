@@ -0,0 +1,147 @@
+mod common;
+
+use primitive_types::H256;
+use state_channel_node::utxo::models::{Input, Transaction, Utxo};
+use state_channel_node::utxo::timelock::{
+    self, ChainTip, RelativeLock, SEQUENCE_FINAL, SEQUENCE_LOCKTIME_DISABLE_FLAG,
+};
+use state_channel_node::utxo::validation::ValidationError;
+use common::utxo;
+
+const COIN_HEIGHT: u32 = 100;
+const COIN_PRIOR_TIME: u64 = 1_600_000_000;
+
+fn spending(version: u32, sequence: u32, lock_time: u64) -> Transaction {
+    let mut tx = Transaction {
+        version,
+        inputs: vec![Input {
+            previous_output: H256::repeat_byte(7),
+            index: 0,
+            signature: vec![],
+            sequence,
+        }],
+        outputs: vec![utxo::create_output(1_000)],
+        lock_time,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+    tx
+}
+
+fn coin(confirmed: bool) -> Utxo {
+    let output = utxo::create_output(2_000);
+    if confirmed {
+        Utxo::new(output, COIN_HEIGHT, 0, H256::repeat_byte(7))
+    } else {
+        Utxo::new_unconfirmed(output, 0, H256::repeat_byte(7))
+    }
+}
+
+fn tip(height: u32, median_time_past: u64) -> ChainTip {
+    ChainTip { height, median_time_past }
+}
+
+fn block_times(height: u32) -> Option<u64> {
+    (height == COIN_HEIGHT - 1).then_some(COIN_PRIOR_TIME)
+}
+
+#[test]
+fn test_absolute_height_lock() {
+    let tx = spending(1, 0, 100);
+
+    // Included in block tip + 1; lock_time must be below that height
+    let err = timelock::check_lock_time(&tx, &tip(99, 0)).unwrap_err();
+    assert!(matches!(err, ValidationError::LockTimeNotReached { lock_time: 100 }));
+    assert_eq!(err.code(), "INVALID_TIMELOCK");
+    assert!(timelock::check_lock_time(&tx, &tip(100, 0)).is_ok());
+
+    // Final inputs disable lock_time entirely
+    let tx = spending(1, SEQUENCE_FINAL, 100);
+    assert!(timelock::check_lock_time(&tx, &tip(0, 0)).is_ok());
+}
+
+#[test]
+fn test_absolute_time_lock() {
+    let lock_time = 1_700_000_000;
+    let tx = spending(1, 0, lock_time);
+
+    // Compared against median time past, not height
+    assert!(timelock::check_lock_time(&tx, &tip(1_000_000, lock_time)).is_err());
+    assert!(timelock::check_lock_time(&tx, &tip(0, lock_time + 1)).is_ok());
+}
+
+#[test]
+fn test_relative_block_lock() {
+    let sequence = RelativeLock::Blocks(10).to_sequence();
+    let tx = spending(2, sequence, 0);
+    let spent = [coin(true)];
+
+    let err = timelock::validate_timelocks(&tx, &spent, &tip(108, 0), block_times).unwrap_err();
+    assert!(matches!(
+        err,
+        ValidationError::SequenceLockNotReached { input: 0, lock: RelativeLock::Blocks(10) }
+    ));
+    assert_eq!(err.code(), "INVALID_TIMELOCK");
+    assert!(timelock::validate_timelocks(&tx, &spent, &tip(109, 0), block_times).is_ok());
+
+    // Not enforced below version 2, or with the disable flag set
+    let tx = spending(1, sequence, 0);
+    assert!(timelock::validate_timelocks(&tx, &spent, &tip(100, 0), block_times).is_ok());
+    let tx = spending(2, sequence | SEQUENCE_LOCKTIME_DISABLE_FLAG, 0);
+    assert!(timelock::validate_timelocks(&tx, &spent, &tip(100, 0), block_times).is_ok());
+
+    // Unconfirmed outputs count as confirmed in the next block
+    let spent = [coin(false)];
+    let tx = spending(2, RelativeLock::Blocks(0).to_sequence(), 0);
+    assert!(timelock::validate_timelocks(&tx, &spent, &tip(500, 0), block_times).is_ok());
+    let tx = spending(2, RelativeLock::Blocks(1).to_sequence(), 0);
+    assert!(timelock::validate_timelocks(&tx, &spent, &tip(500, 0), block_times).is_err());
+}
+
+#[test]
+fn test_relative_time_lock() {
+    let lock = RelativeLock::Time(2);
+    assert_eq!(lock.seconds(), Some(1_024));
+    assert_eq!(RelativeLock::from_sequence(lock.to_sequence()), Some(lock));
+
+    let tx = spending(2, lock.to_sequence(), 0);
+    let spent = [coin(true)];
+
+    // Measured from the median time past of the block before the coin's block
+    let early = tip(200, COIN_PRIOR_TIME + 1_023);
+    assert!(timelock::validate_timelocks(&tx, &spent, &early, block_times).is_err());
+    let mature = tip(200, COIN_PRIOR_TIME + 1_024);
+    assert!(timelock::validate_timelocks(&tx, &spent, &mature, block_times).is_ok());
+
+    assert!(matches!(
+        timelock::validate_timelocks(&tx, &spent, &mature, |_| None),
+        Err(ValidationError::MissingBlockTime(99))
+    ));
+    assert!(matches!(
+        timelock::validate_timelocks(&tx, &[], &mature, block_times),
+        Err(ValidationError::MissingInput(_, 0))
+    ));
+}
+
+#[test]
+fn test_script_lock_checks() {
+    // CHECKLOCKTIMEVERIFY semantics
+    let tx = spending(2, 0, 500);
+    assert!(timelock::lock_time_satisfies(&tx, 0, 500));
+    assert!(timelock::lock_time_satisfies(&tx, 0, 499));
+    assert!(!timelock::lock_time_satisfies(&tx, 0, 501));
+    assert!(!timelock::lock_time_satisfies(&tx, 0, 1_700_000_000));
+    assert!(!timelock::lock_time_satisfies(&spending(2, SEQUENCE_FINAL, 500), 0, 500));
+
+    // CHECKSEQUENCEVERIFY semantics
+    let tx = spending(2, RelativeLock::Blocks(144).to_sequence(), 0);
+    assert!(timelock::sequence_satisfies(&tx, 0, RelativeLock::Blocks(144).to_sequence()));
+    assert!(!timelock::sequence_satisfies(&tx, 0, RelativeLock::Blocks(145).to_sequence()));
+    assert!(!timelock::sequence_satisfies(&tx, 0, RelativeLock::Time(1).to_sequence()));
+    assert!(timelock::sequence_satisfies(&tx, 0, SEQUENCE_LOCKTIME_DISABLE_FLAG));
+    assert!(!timelock::sequence_satisfies(
+        &spending(1, RelativeLock::Blocks(144).to_sequence(), 0),
+        0,
+        RelativeLock::Blocks(1).to_sequence()
+    ));
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use std::path::PathBuf;
+use state_channel_node::crypto::{self, KeyPair};
+use state_channel_node::utxo::cache::UtxoCache;
+use state_channel_node::utxo::models::{SighashType, Transaction};
+use state_channel_node::utxo::store::SdbStore;
+use state_channel_node::utxo::timelock::{ChainTip, RelativeLock};
+use state_channel_node::utxo::validation::{self, ValidationError};
+use common::test_utils;
+use common::utxo;
+
+/// Fund `owner` with two outputs confirmed in block 1
+fn fund(cache: &UtxoCache, owner: &KeyPair) -> Transaction {
+    let owner_hash = owner.public_key().public_key_hash();
+    let funding_tx = utxo::create_transaction(vec![
+        utxo::create_output_for(5_000, &owner_hash),
+        utxo::create_output_for(3_000, &owner_hash),
+    ]);
+    cache.add_transaction(&funding_tx, Some(1)).unwrap();
+    funding_tx
+}
+
+fn signed_spend(funding_tx: &Transaction, indices: &[u32], value: u64, signer: &KeyPair) -> Transaction {
+    let recipient = crypto::generate_keypair().public_key().public_key_hash();
+    let mut tx = utxo::create_spending_transaction(
+        funding_tx,
+        indices,
+        vec![utxo::create_output_for(value, &recipient)],
+    );
+    for index in 0..tx.inputs.len() {
+        validation::sign_input(&mut tx, index, signer, SighashType::All).unwrap();
+    }
+    tx
+}
+
+#[test]
+fn test_valid_transaction() {
+    let test_db_path = PathBuf::from("test_validation_valid.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+    let alice = crypto::generate_keypair();
+    let funding_tx = fund(&cache, &alice);
+
+    let tx = signed_spend(&funding_tx, &[0, 1], 7_900, &alice);
+    assert_eq!(validation::validate_transaction(&tx, &cache).unwrap(), 100);
+
+    // Spending exactly the input value leaves no fee
+    let tx = signed_spend(&funding_tx, &[1], 3_000, &alice);
+    assert_eq!(validation::validate_transaction(&tx, &cache).unwrap(), 0);
+
+    // Validation doesn't spend anything
+    assert!(cache.get_utxo(funding_tx.hash, 0).unwrap().is_some());
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_structural_errors() {
+    let test_db_path = PathBuf::from("test_validation_structure.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+    let alice = crypto::generate_keypair();
+    let funding_tx = fund(&cache, &alice);
+
+    // Stale hash
+    let mut tx = signed_spend(&funding_tx, &[0], 4_000, &alice);
+    tx.lock_time = 10;
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::HashMismatch { .. }));
+    assert_eq!(err.code(), "INVALID_HASH");
+
+    // Same outpoint twice
+    let tx = signed_spend(&funding_tx, &[0, 0], 4_000, &alice);
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::DuplicateInput(hash, 0) if hash == funding_tx.hash));
+    assert_eq!(err.code(), "DUPLICATE_INPUT");
+
+    // Output that doesn't exist
+    let tx = signed_spend(&funding_tx, &[7], 4_000, &alice);
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::MissingInput(_, 7)));
+    assert_eq!(err.code(), "INVALID_ANCESTRY");
+
+    // No inputs
+    let err = validation::validate_transaction(&funding_tx, &cache).unwrap_err();
+    assert_eq!(err.code(), "EMPTY_TRANSACTION");
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_inflation_rejected() {
+    let test_db_path = PathBuf::from("test_validation_inflation.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+    let alice = crypto::generate_keypair();
+    let funding_tx = fund(&cache, &alice);
+
+    let tx = signed_spend(&funding_tx, &[1], 3_001, &alice);
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::Inflation { inputs: 3_000, outputs: 3_001 }));
+    assert_eq!(err.code(), "INFLATION");
+
+    // Output values that overflow can't sneak under the input total
+    let mut tx = utxo::create_spending_transaction(
+        &funding_tx,
+        &[0],
+        vec![utxo::create_output(u64::MAX), utxo::create_output(2)],
+    );
+    validation::sign_input(&mut tx, 0, &alice, SighashType::All).unwrap();
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::ValueOverflow));
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_signature_errors() {
+    let test_db_path = PathBuf::from("test_validation_signatures.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+    let alice = crypto::generate_keypair();
+    let mallory = crypto::generate_keypair();
+    let funding_tx = fund(&cache, &alice);
+
+    // Unsigned (dummy signature bytes)
+    let tx = utxo::create_spending_transaction(&funding_tx, &[0], vec![utxo::create_output(4_000)]);
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::MalformedSignature(0)));
+    assert_eq!(err.code(), "INVALID_SIGNATURE_FORMAT");
+
+    // Signed by someone other than the owner
+    let tx = signed_spend(&funding_tx, &[0], 4_000, &mallory);
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::OwnershipMismatch(0)));
+    assert_eq!(err.code(), "INVALID_OWNERSHIP");
+
+    // Outputs changed after signing
+    let mut tx = signed_spend(&funding_tx, &[0, 1], 7_000, &alice);
+    tx.outputs[0].value = 6_000;
+    tx.hash = tx.calculate_hash();
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::InvalidSignature(0)));
+    assert_eq!(err.code(), "INVALID_SIGNATURE");
+
+    // Only the second input's signature is corrupted
+    let mut tx = signed_spend(&funding_tx, &[0, 1], 7_000, &alice);
+    let signature_end = tx.inputs[1].signature.len() - 2;
+    tx.inputs[1].signature[signature_end] ^= 0x01;
+    assert!(matches!(
+        validation::validate_transaction(&tx, &cache),
+        Err(ValidationError::InvalidSignature(1))
+    ));
+
+    // Unknown sighash type byte
+    let mut tx = signed_spend(&funding_tx, &[0], 4_000, &alice);
+    let last = tx.inputs[0].signature.len() - 1;
+    tx.inputs[0].signature[last] = 0x04;
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::InvalidSighash(0)));
+    assert_eq!(err.code(), "INVALID_SIGHASH");
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
+
+#[test]
+fn test_immature_timelocks_rejected() {
+    let test_db_path = PathBuf::from("test_validation_timelocks.db");
+    test_utils::cleanup_test_db(&test_db_path);
+    let cache = UtxoCache::new(SdbStore::new(&test_db_path).unwrap());
+    let alice = crypto::generate_keypair();
+    let funding_tx = fund(&cache, &alice);
+
+    // Height lock: only includable in blocks after 10
+    let mut tx = utxo::create_spending_transaction(&funding_tx, &[0], vec![utxo::create_output(4_000)]);
+    tx.lock_time = 10;
+    tx.inputs[0].sequence = 0;
+    tx.hash = tx.calculate_hash();
+    validation::sign_input(&mut tx, 0, &alice, SighashType::All).unwrap();
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::LockTimeNotReached { lock_time: 10 }));
+    assert_eq!(err.code(), "INVALID_TIMELOCK");
+    let tip = ChainTip { height: 10, median_time_past: 0 };
+    assert_eq!(validation::validate_transaction_at(&tx, &cache, &tip, |_| None).unwrap(), 1_000);
+
+    // CSV spend: the output confirmed in block 1 needs five confirmations
+    let mut tx = utxo::create_spending_transaction(&funding_tx, &[1], vec![utxo::create_output(2_000)]);
+    tx.version = 2;
+    tx.inputs[0].sequence = RelativeLock::Blocks(5).to_sequence();
+    tx.hash = tx.calculate_hash();
+    validation::sign_input(&mut tx, 0, &alice, SighashType::All).unwrap();
+    let err = validation::validate_transaction(&tx, &cache).unwrap_err();
+    assert!(matches!(err, ValidationError::SequenceLockNotReached { input: 0, lock: RelativeLock::Blocks(5) }));
+    assert_eq!(err.code(), "INVALID_TIMELOCK");
+    let tip = ChainTip { height: 4, median_time_past: 0 };
+    assert!(validation::validate_transaction_at(&tx, &cache, &tip, |_| None).is_err());
+    let tip = ChainTip { height: 5, median_time_past: 0 };
+    assert_eq!(validation::validate_transaction_at(&tx, &cache, &tip, |_| None).unwrap(), 1_000);
+
+    // Cleanup
+    drop(cache);
+    test_utils::cleanup_test_db(&test_db_path);
+}
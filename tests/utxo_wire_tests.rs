@@ -0,0 +1,139 @@
+mod common;
+
+use primitive_types::H256;
+use state_channel_node::crypto;
+use state_channel_node::utxo::models::{Input, Output, Transaction};
+use state_channel_node::utxo::script::Script;
+use state_channel_node::utxo::wire::{self, WireError, WireInput, WireOutput, WireTransaction};
+use common::utxo;
+
+/// Coinbase transaction of the Bitcoin genesis block
+const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+const GENESIS_TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+#[test]
+fn test_genesis_coinbase_vector() {
+    let tx = WireTransaction::from_hex(GENESIS_COINBASE).unwrap();
+    assert_eq!(tx.version, 1);
+    assert_eq!(tx.inputs.len(), 1);
+    assert_eq!(tx.inputs[0].previous_output, H256::zero());
+    assert_eq!(tx.inputs[0].index, 0xffffffff);
+    assert_eq!(tx.outputs, vec![WireOutput { value: 5_000_000_000, script_pubkey: tx.outputs[0].script_pubkey.clone() }]);
+    assert_eq!(tx.outputs[0].script_pubkey.len(), 67);
+    assert!(!tx.has_witness());
+
+    assert_eq!(tx.to_hex(), GENESIS_COINBASE);
+    assert_eq!(tx.txid_hex(), GENESIS_TXID);
+    assert_eq!(tx.wtxid(), tx.txid());
+
+    // Pay-to-pubkey output isn't P2PKH, so it's kept as the lock script
+    let imported = tx.clone().into_transaction().unwrap();
+    assert_eq!(imported.outputs[0].lock_script, tx.outputs[0].script_pubkey);
+    assert_eq!(imported.hash, imported.calculate_hash());
+    assert_eq!(WireTransaction::from_transaction(&imported).unwrap(), tx);
+}
+
+#[test]
+fn test_varints() {
+    for (value, encoded) in [
+        (0u64, "00"),
+        (0xfc, "fc"),
+        (0xfd, "fdfd00"),
+        (0xffff, "fdffff"),
+        (0x1_0000, "fe00000100"),
+        (0x1_0000_0000, "ff0000000001000000"),
+    ] {
+        let mut out = Vec::new();
+        wire::write_varint(&mut out, value);
+        assert_eq!(hex::encode(&out), encoded);
+        assert_eq!(wire::read_varint(&out).unwrap(), (value, out.len()));
+    }
+
+    assert_eq!(wire::read_varint(&hex::decode("fd1000").unwrap()), Err(WireError::NonCanonicalVarInt));
+    assert_eq!(wire::read_varint(&hex::decode("fe0000").unwrap()), Err(WireError::UnexpectedEof));
+}
+
+#[test]
+fn test_segwit_serialization() {
+    let mut tx = WireTransaction {
+        version: 2,
+        inputs: vec![WireInput {
+            previous_output: H256::repeat_byte(0xab),
+            index: 1,
+            script_sig: vec![],
+            sequence: 0xfffffffd,
+            witness: vec![vec![0x30; 71], vec![0x02; 33]],
+        }],
+        outputs: vec![WireOutput { value: 99_000, script_pubkey: vec![0x00, 0x14, 0x11, 0x22] }],
+        lock_time: 700_000,
+    };
+
+    let encoded = tx.encode();
+    assert_eq!(&encoded[4..6], &[wire::SEGWIT_MARKER, wire::SEGWIT_FLAG]);
+    assert_eq!(WireTransaction::decode(&encoded).unwrap(), tx);
+
+    // Witness data changes the wtxid but not the txid
+    let txid = tx.txid();
+    let wtxid = tx.wtxid();
+    assert_ne!(txid, wtxid);
+    tx.inputs[0].witness[0][0] = 0x31;
+    assert_eq!(tx.txid(), txid);
+    assert_ne!(tx.wtxid(), wtxid);
+
+    // Our Input has nowhere to keep the witness
+    assert_eq!(tx.clone().into_transaction(), Err(WireError::UnsupportedWitness(0)));
+
+    // Malformed encodings
+    let mut bad_flag = encoded.clone();
+    bad_flag[5] = 0x02;
+    assert_eq!(WireTransaction::decode(&bad_flag), Err(WireError::InvalidSegwitFlag(0x02)));
+    let mut trailing = encoded.clone();
+    trailing.push(0);
+    assert_eq!(WireTransaction::decode(&trailing), Err(WireError::TrailingBytes(1)));
+    assert_eq!(WireTransaction::decode(&encoded[..encoded.len() - 1]), Err(WireError::UnexpectedEof));
+    tx.inputs[0].witness.clear();
+    let mut empty_witness = tx.encode();
+    empty_witness.splice(4..4, [wire::SEGWIT_MARKER, wire::SEGWIT_FLAG]);
+    empty_witness.insert(empty_witness.len() - 4, 0);
+    assert_eq!(WireTransaction::decode(&empty_witness), Err(WireError::SuperfluousWitness));
+}
+
+#[test]
+fn test_transaction_mapping() {
+    let alice = crypto::generate_keypair();
+    let multisig = Script::multisig(1, &[alice.public_key()]).into_bytes();
+    let mut tx = Transaction {
+        version: 2,
+        inputs: vec![Input {
+            previous_output: H256::repeat_byte(3),
+            index: 4,
+            signature: vec![0xaa; 97],
+            sequence: 10,
+        }],
+        outputs: vec![
+            utxo::create_output_for(1_000, &alice.public_key().public_key_hash()),
+            Output { value: 2_000, public_key_hash: vec![], lock_script: multisig.clone() },
+        ],
+        lock_time: 123,
+        hash: H256::zero(),
+    };
+    tx.hash = tx.calculate_hash();
+
+    let wire_tx = WireTransaction::from_transaction(&tx).unwrap();
+    assert_eq!(
+        wire_tx.outputs[0].script_pubkey,
+        Script::p2pkh(&alice.public_key().public_key_hash()).into_bytes()
+    );
+    assert_eq!(wire_tx.outputs[1].script_pubkey, multisig);
+    assert_eq!(wire_tx.inputs[0].script_sig, tx.inputs[0].signature);
+
+    let decoded = WireTransaction::from_hex(&wire_tx.to_hex()).unwrap();
+    assert_eq!(decoded.into_transaction().unwrap(), tx);
+
+    // Bitcoin lock times are 32-bit
+    tx.lock_time = u32::MAX as u64 + 1;
+    assert_eq!(
+        WireTransaction::from_transaction(&tx),
+        Err(WireError::LockTimeOutOfRange(u32::MAX as u64 + 1))
+    );
+}